@@ -0,0 +1,553 @@
+use crate::i18n::*;
+use tty_sender_core::capture::{Capture, Timestamps};
+use tty_sender_core::config::Config;
+use tty_sender_core::engine::{Engine, SendError, SendEvent, SendReport, StopAt};
+use tty_sender_core::script::{lines_from_text, section_lines, sections, select_range};
+use tty_sender_core::target::{discover, SharedTarget, TargetSpec};
+use tty_sender_core::trigger::{TriggerEvent, TriggerRunner};
+use regex::Regex;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_SEND_FAILED: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_TARGET: i32 = 3;
+pub const EXIT_INPUT: i32 = 4;
+pub const EXIT_ABORTED: i32 = 5;
+pub const EXIT_UNCONFIRMED: i32 = 6;
+
+#[derive(Debug, Default)]
+struct SendArgs {
+    target: String,
+    file: PathBuf,
+    from_line: Option<usize>,
+    to_line: Option<usize>,
+    count: Option<usize>,
+    section: Option<String>,
+    vars: Vec<(String, String)>,
+    stop: Option<StopAt>,
+    send_blank_lines: bool,
+    check_status: bool,
+    char_delay: Option<u64>,
+    line_delay: Option<u64>,
+    prompt: Option<String>,
+    prompt_timeout: Option<u64>,
+    dry_run: bool,
+    yes: bool,
+    yes_protected: bool,
+    log_dir: Option<PathBuf>,
+    no_transcript: bool,
+    capture: Option<PathBuf>,
+    capture_timestamps: Option<Timestamps>,
+    no_capture: bool,
+    junit: Option<PathBuf>,
+    report_json: Option<PathBuf>,
+}
+
+enum Command {
+    Send(Box<SendArgs>),
+    ListTargets,
+    Help,
+}
+
+/// 命令行入口, 返回进程退出码
+pub fn run(args: &[String]) -> i32 {
+    #[cfg(windows)]
+    attach_parent_console();
+
+    let config = Config::load();
+    set_language(Lang::from_setting(&config.language));
+
+    match parse_args(args) {
+        Ok(Command::Help) => {
+            println!("{}", tr(Msg::CliUsage));
+            EXIT_OK
+        }
+        Ok(Command::ListTargets) => list_targets(&config),
+        Ok(Command::Send(send_args)) => send(&config, *send_args),
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, tr(Msg::CliUsage));
+            EXIT_USAGE
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => return Ok(Command::Help),
+    };
+    match command {
+        "-h" | "--help" | "help" => Ok(Command::Help),
+        "list-targets" => Ok(Command::ListTargets),
+        "send" => parse_send_args(rest).map(|args| Command::Send(Box::new(args))),
+        _ => Err(tr_fmt(Msg::CliUnknownCommand, &[("command", &command)])),
+    }
+}
+
+fn parse_send_args(args: &[String]) -> Result<SendArgs, String> {
+    let mut parsed = SendArgs::default();
+    let mut target = None;
+    let mut file = None;
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        // 同时支持 "--name value" 与 "--name=value"
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| iter.next().cloned())
+                .ok_or_else(|| tr_fmt(Msg::CliMissingValue, &[("option", &name)]))
+        };
+        match name {
+            "--target" => target = Some(value()?),
+            "--file" => file = Some(PathBuf::from(value()?)),
+            "--from-line" => parsed.from_line = Some(parse_number(name, &value()?)?),
+            "--to-line" => parsed.to_line = Some(parse_number(name, &value()?)?),
+            "--count" => {
+                let count = value()?;
+                parsed.count = Some(
+                    count
+                        .parse()
+                        .ok()
+                        .filter(|&count: &usize| count > 0)
+                        .ok_or_else(|| tr_fmt(Msg::CliInvalidValue, &[("option", &name), ("value", &count)]))?,
+                );
+            }
+            "--section" => parsed.section = Some(value()?),
+            "--char-delay" => parsed.char_delay = Some(parse_number(name, &value()?)? as u64),
+            "--line-delay" => parsed.line_delay = Some(parse_number(name, &value()?)? as u64),
+            "--prompt" => parsed.prompt = Some(value()?),
+            "--prompt-timeout" => parsed.prompt_timeout = Some(parse_number(name, &value()?)? as u64),
+            "--var" => {
+                let pair = value()?;
+                let (key, val) = pair
+                    .split_once('=')
+                    .filter(|(key, _)| !key.is_empty())
+                    .ok_or_else(|| tr_fmt(Msg::CliInvalidValue, &[("option", &name), ("value", &pair)]))?;
+                parsed.vars.push((key.to_string(), val.to_string()));
+            }
+            "--stop" => {
+                let stop = value()?;
+                parsed.stop = Some(
+                    StopAt::parse(&stop)
+                        .ok_or_else(|| tr_fmt(Msg::CliInvalidValue, &[("option", &name), ("value", &stop)]))?,
+                );
+            }
+            "--stop-at-blank" => parsed.stop = Some(StopAt::Blank),
+            "--send-blank-lines" => parsed.send_blank_lines = true,
+            "--check-status" => parsed.check_status = true,
+            "--dry-run" => parsed.dry_run = true,
+            "--yes" => parsed.yes = true,
+            "--yes-protected" => parsed.yes_protected = true,
+            "--log-dir" => parsed.log_dir = Some(PathBuf::from(value()?)),
+            "--no-transcript" => parsed.no_transcript = true,
+            "--capture" => parsed.capture = Some(PathBuf::from(value()?)),
+            "--capture-timestamps" => {
+                let timestamps = value()?;
+                parsed.capture_timestamps = Some(
+                    Timestamps::parse(&timestamps)
+                        .ok_or_else(|| tr_fmt(Msg::CliInvalidValue, &[("option", &name), ("value", &timestamps)]))?,
+                );
+            }
+            "--no-capture" => parsed.no_capture = true,
+            "--junit" => parsed.junit = Some(PathBuf::from(value()?)),
+            "--report-json" => parsed.report_json = Some(PathBuf::from(value()?)),
+            _ => return Err(tr_fmt(Msg::CliUnknownOption, &[("option", &name)])),
+        }
+    }
+
+    if parsed.section.is_some() && (parsed.from_line.is_some() || parsed.to_line.is_some() || parsed.count.is_some()) {
+        return Err(tr_fmt(Msg::CliConflictingOptions, &[("option", &"--section"), ("other", &"--from-line/--to-line/--count")]));
+    }
+    parsed.target = target.ok_or_else(|| tr_fmt(Msg::CliMissingOption, &[("option", &"--target")]))?;
+    parsed.file = file.ok_or_else(|| tr_fmt(Msg::CliMissingOption, &[("option", &"--file")]))?;
+    Ok(parsed)
+}
+
+fn parse_number(option: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| tr_fmt(Msg::CliInvalidValue, &[("option", &option), ("value", &value)]))
+}
+
+fn list_targets(config: &Config) -> i32 {
+    println!("{}", tr(Msg::CliConfiguredTargets));
+    if config.targets.is_empty() {
+        println!("{}", tr(Msg::CliNoTargets));
+    }
+    for (name, profile) in &config.targets {
+        println!("  {:<20} {}  {}", name, profile.target, profile.description);
+    }
+
+    println!("{}", tr(Msg::CliDetectedTargets));
+    let found = discover();
+    if found.is_empty() {
+        println!("{}", tr(Msg::CliNoTargets));
+    }
+    for (rule, description) in found {
+        println!("  {:<40} {}", rule, description);
+    }
+    EXIT_OK
+}
+
+fn send(config: &Config, args: SendArgs) -> i32 {
+    let spec = match TargetSpec::parse(config.resolve_target(&args.target)) {
+        Ok(spec) => spec,
+        Err(e) => {
+            eprintln!("{}", describe_target_error(&e));
+            return EXIT_TARGET;
+        }
+    };
+
+    let text = match fs::read_to_string(&args.file) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{}", tr_fmt(Msg::CliReadFileFailed, &[("path", &args.file.display()), ("error", &e)]));
+            return EXIT_INPUT;
+        }
+    };
+    let mut options = config.send.options();
+    let document = lines_from_text(&text);
+    let lines = match &args.section {
+        Some(name) => match section_lines(&document, &options.syntax, name) {
+            Some(lines) => lines,
+            None => {
+                let names: Vec<_> = sections(&document, &options.syntax).into_iter().map(|s| s.name).collect();
+                eprintln!("{}", tr_fmt(Msg::UnknownSection, &[("name", name), ("sections", &names.join(", "))]));
+                return EXIT_INPUT;
+            }
+        },
+        None => {
+            let from = args.from_line.unwrap_or(1);
+            let to = match (args.to_line, args.count) {
+                (Some(to), _) => to,
+                // --count 从起始行算起, 不足时到文件末尾为止
+                (None, Some(count)) => (from + count - 1).min(document.len()),
+                (None, None) => document.len(),
+            };
+            match select_range(&document, from, to) {
+                Ok(lines) => lines,
+                Err(e) => {
+                    eprintln!("{}", describe_range_error(&e));
+                    return EXIT_INPUT;
+                }
+            }
+        }
+    };
+
+    // 命令行默认发送整个文件 (或指定范围), 不受界面多行发送的停止条件影响
+    options.stop = args.stop.unwrap_or(StopAt::EndOfInput);
+    options.send_blank_lines |= args.send_blank_lines;
+    options.check_status |= args.check_status;
+    if let Some(ms) = args.char_delay {
+        options.char_delay = Duration::from_millis(ms);
+    }
+    if let Some(ms) = args.line_delay {
+        options.line_delay = Duration::from_millis(ms);
+    }
+    if let Some(ms) = args.prompt_timeout {
+        options.prompt_timeout = Duration::from_millis(ms);
+    }
+    let prompt = match &args.prompt {
+        Some(prompt) => Regex::new(prompt).map(Some),
+        None => config.send.prompt_regex(),
+    };
+    options.prompt = match prompt {
+        Ok(prompt) => prompt,
+        Err(e) => {
+            eprintln!("{}", tr_fmt(Msg::CliInvalidPrompt, &[("error", &e)]));
+            return EXIT_USAGE;
+        }
+    };
+    let mut engine = Engine::new(options, Arc::new(AtomicBool::new(false)));
+    engine.variables.extend(args.vars);
+    engine.source = Some(args.file.clone());
+    engine.document = document;
+    engine.protected = config.is_protected(&args.target);
+    engine.guard = match config.guard.build() {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("{}", tr_fmt(Msg::InvalidGuardPattern, &[("error", &e)]));
+            return EXIT_USAGE;
+        }
+    };
+
+    let triggers = match config.triggers_for(&args.target) {
+        Ok(triggers) => triggers,
+        Err(e) => {
+            eprintln!("{}", describe_trigger_error(&e));
+            return EXIT_USAGE;
+        }
+    };
+
+    // 先展开 #!include, 出错时不必打开目标
+    let planned = match engine.plan(&lines) {
+        Ok(planned) => planned,
+        Err(e) => {
+            eprintln!("{}", describe_script_error(&e));
+            return EXIT_INPUT;
+        }
+    };
+    let hits = engine.dangerous_lines(&lines).unwrap_or_default();
+    if !hits.is_empty() {
+        eprintln!("{}", tr(Msg::GuardHits));
+        for hit in &hits {
+            eprintln!("{:>5}  {}    ({})", hit.line.number, hit.text, hit.pattern);
+        }
+    }
+    if args.dry_run {
+        for (line, text) in &planned {
+            println!("{:>5}  {}", line.number, text);
+        }
+        eprintln!("{}", tr_fmt(Msg::CliDryRunSummary, &[("count", &planned.len()), ("target", &spec)]));
+        return EXIT_OK;
+    }
+
+    if !hits.is_empty() {
+        // 受保护的目标只认 --yes-protected, 交互确认时须输入目标名称
+        let confirmed = if engine.protected {
+            args.yes_protected || confirm_on_terminal(Some(&args.target))
+        } else {
+            args.yes || args.yes_protected || confirm_on_terminal(None)
+        };
+        if !confirmed {
+            let msg = if engine.protected { Msg::CliGuardProtectedRefused } else { Msg::CliGuardRefused };
+            eprintln!("{}", tr_fmt(msg, &[("target", &args.target)]));
+            return EXIT_UNCONFIRMED;
+        }
+        engine.confirmed = true;
+    }
+
+    let mut target = match spec.open() {
+        Ok(target) => target,
+        Err(e) => {
+            eprintln!("{}", describe_target_error(&e));
+            return EXIT_TARGET;
+        }
+    };
+    let mut log = config.log.clone();
+    if let Some(dir) = &args.log_dir {
+        log.dir = dir.to_string_lossy().into_owned();
+    }
+    if !args.no_transcript {
+        engine.transcript = log.transcript();
+    }
+    let capture_file = match &args.capture {
+        Some(file) => Some(file.clone()),
+        None => (log.capture && !args.no_capture).then(|| log.capture_file()),
+    };
+    if let Some(file) = capture_file {
+        let mut options = log.capture_options();
+        if let Some(timestamps) = args.capture_timestamps {
+            options.timestamps = timestamps;
+        }
+        engine.capture = match target.output() {
+            Some(output) => match Capture::create(&file, &target.describe(), output, options) {
+                Ok(capture) => Some(capture),
+                Err(e) => {
+                    eprintln!("{}", tr_fmt(Msg::CaptureFailed, &[("error", &e)]));
+                    None
+                }
+            },
+            None => {
+                eprintln!("{}", tr_fmt(Msg::CliCaptureUnsupported, &[("target", &target.describe())]));
+                None
+            }
+        };
+    }
+    // 触发器与发送共用同一个连接
+    let mut runner = None;
+    if !triggers.is_empty() {
+        match target.output() {
+            Some(output) => {
+                let shared = SharedTarget::new(target);
+                runner = Some(TriggerRunner::start(triggers, output, shared.clone(), |event| match event {
+                    TriggerEvent::Fired { name } => eprintln!("{}", tr_fmt(Msg::TriggerFired, &[("name", &name)])),
+                    TriggerEvent::Failed { name, error } => {
+                        eprintln!("{}", tr_fmt(Msg::TriggerFailed, &[("name", &name), ("error", &error)]))
+                    }
+                }));
+                target = Box::new(shared);
+            }
+            None => eprintln!("{}", tr_fmt(Msg::TriggersUnsupported, &[("target", &target.describe())])),
+        }
+    }
+    let target_name = target.describe();
+    let result = engine.run(target.as_mut(), &lines, &mut |event| match event {
+        SendEvent::Sending { line, text } => println!("{:>5}  {}", line.number, text),
+        SendEvent::Captured { line, name, value } => {
+            eprintln!("{}", tr_fmt(Msg::SendCaptured, &[("line", &line.number), ("name", &name), ("value", &value)]))
+        }
+        SendEvent::ErrorHandler { error, section, .. } => {
+            let error = describe_send_error(error, &target_name);
+            eprintln!("{}", tr_fmt(Msg::SendErrorHandler, &[("error", &error), ("section", &section)]))
+        }
+        SendEvent::ErrorGoto { error, label, .. } => {
+            let error = describe_send_error(error, &target_name);
+            eprintln!("{}", tr_fmt(Msg::SendErrorGoto, &[("error", &error), ("label", &label)]))
+        }
+        SendEvent::Iteration { line, iteration, total } => {
+            eprintln!("{}", describe_iteration(line.number, iteration, total))
+        }
+        SendEvent::Sent { .. } => {}
+    });
+    if let Some(error) = result.as_ref().ok().and_then(|report| report.transcript_error.as_ref()) {
+        eprintln!("{}", tr_fmt(Msg::TranscriptFailed, &[("error", error)]));
+    }
+    if let Some(runner) = runner {
+        runner.stop();
+    }
+    let results = engine.results();
+    if results.has_assertions() || args.junit.is_some() || args.report_json.is_some() {
+        eprintln!("{}", describe_results(&results));
+    }
+    let name = args.file.display().to_string();
+    if let Some(path) = &args.junit {
+        write_report(path, &results.to_junit(&name));
+    }
+    if let Some(path) = &args.report_json {
+        write_report(path, &format!("{:#}\n", results.to_json(&name)));
+    }
+    if let Some(Err(e)) = engine.capture.as_ref().map(Capture::finish) {
+        eprintln!("{}", tr_fmt(Msg::CaptureFailed, &[("error", &e)]));
+    }
+    match &result {
+        Ok(report) if report.aborted => {
+            eprintln!("{}", tr_fmt(Msg::CliSendAborted, &[("count", &report.sent), ("target", &target.describe())]))
+        }
+        Ok(report) => eprintln!("{}", tr_fmt(Msg::CliSendDone, &[("count", &report.sent), ("target", &target.describe())])),
+        Err(e) => eprintln!("{}", describe_send_error(e, &target.describe())),
+    }
+    exit_code(&result)
+}
+
+/// 发送结果对应的退出码; 发送中途由捕获的值引起的危险命令未确认时同样按未确认结束
+fn exit_code(result: &Result<SendReport, SendError>) -> i32 {
+    match result {
+        Ok(report) if report.aborted => EXIT_ABORTED,
+        Ok(_) => EXIT_OK,
+        Err(SendError::Unconfirmed(_)) => EXIT_UNCONFIRMED,
+        Err(_) => EXIT_SEND_FAILED,
+    }
+}
+
+/// 写入测试报告; 失败时只提示, 不影响退出码
+fn write_report(path: &Path, report: &str) {
+    if let Err(e) = fs::write(path, report) {
+        eprintln!("{}", tr_fmt(Msg::CliReportFailed, &[("path", &path.display()), ("error", &e)]));
+    }
+}
+
+/// 在终端中询问是否继续; expected_name 不为空时须输入该名称。标准输入不是终端时视为拒绝
+fn confirm_on_terminal(expected_name: Option<&str>) -> bool {
+    if !io::stdin().is_terminal() {
+        return false;
+    }
+    match expected_name {
+        Some(name) => eprint!("{}", tr_fmt(Msg::CliGuardTypeName, &[("target", &name)])),
+        None => eprint!("{}", tr(Msg::CliGuardAsk)),
+    }
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    let answer = answer.trim();
+    match expected_name {
+        Some(name) => answer == name,
+        None => answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"),
+    }
+}
+
+/// 程序是 Windows 子系统, 从命令行启动时需要附加到父进程的控制台才能输出
+#[cfg(windows)]
+fn attach_parent_console() {
+    use winapi::um::wincon::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn parse_send(text: &str) -> Result<SendArgs, String> {
+        parse_send_args(&args(text))
+    }
+
+    #[test]
+    fn parses_send_options() {
+        let parsed = parse_send("--target board --file=cmds.txt --from-line 3 --count 2 --var IP=10.0.0.2 --stop blank --dry-run")
+            .unwrap();
+        assert_eq!(parsed.target, "board");
+        assert_eq!(parsed.file, PathBuf::from("cmds.txt"));
+        assert_eq!((parsed.from_line, parsed.count), (Some(3), Some(2)));
+        assert_eq!(parsed.vars, [("IP".to_string(), "10.0.0.2".to_string())]);
+        assert_eq!(parsed.stop, Some(StopAt::Blank));
+        assert!(parsed.dry_run && !parsed.yes);
+    }
+
+    #[test]
+    fn rejects_bad_send_options() {
+        assert!(parse_send("--file cmds.txt").is_err());
+        assert!(parse_send("--target board").is_err());
+        assert!(parse_send("--target board --file cmds.txt --bogus").is_err());
+        assert!(parse_send("--target board --file cmds.txt --from-line x").is_err());
+        assert!(parse_send("--target board --file cmds.txt --var =1").is_err());
+        assert!(parse_send("--target board --file cmds.txt --section a --count 2").is_err());
+        assert!(parse_send("--target board --file").is_err());
+        // --count 0 不会被当作 1
+        assert!(parse_send("--target board --file cmds.txt --count 0").is_err());
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert!(matches!(parse_args(&[]), Ok(Command::Help)));
+        assert!(matches!(parse_args(&args("list-targets")), Ok(Command::ListTargets)));
+        assert!(matches!(parse_args(&args("send --target a --file b")), Ok(Command::Send(_))));
+        assert!(parse_args(&args("transmit")).is_err());
+    }
+
+    #[test]
+    fn maps_results_to_exit_codes() {
+        let report = SendReport { sent: 2, aborted: false, transcript_error: None };
+        assert_eq!(exit_code(&Ok(report.clone())), EXIT_OK);
+        assert_eq!(exit_code(&Ok(SendReport { aborted: true, ..report })), EXIT_ABORTED);
+        assert_eq!(exit_code(&Err(SendError::PromptTimeout { line: 1 })), EXIT_SEND_FAILED);
+        assert_eq!(exit_code(&Err(SendError::Unconfirmed(Vec::new()))), EXIT_UNCONFIRMED);
+    }
+
+    #[test]
+    fn reports_target_and_input_errors() {
+        let config = Config::default();
+        let file = std::env::temp_dir().join(format!("tty_sender_cli_{}.txt", std::process::id()));
+        fs::write(&file, "echo 1\n#!include missing.txt\n").unwrap();
+        let send_args = |target: &str, file: &Path| SendArgs {
+            target: target.to_string(),
+            file: file.to_path_buf(),
+            dry_run: true,
+            ..SendArgs::default()
+        };
+        assert_eq!(send(&config, send_args("bogus:x", &file)), EXIT_TARGET);
+        assert_eq!(send(&config, send_args("spawn:/bin/sh", Path::new("/nonexistent/cmds.txt"))), EXIT_INPUT);
+        // #!include 在打开目标前展开
+        assert_eq!(send(&config, send_args("spawn:/bin/sh", &file)), EXIT_INPUT);
+        fs::write(&file, "echo 1\n").unwrap();
+        assert_eq!(send(&config, send_args("spawn:/bin/sh", &file)), EXIT_OK);
+        fs::remove_file(&file).unwrap();
+    }
+}
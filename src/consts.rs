@@ -1,7 +0,0 @@
-pub const CLASS_NAME: &str = "TtySenderClass";
-pub const IDC_BUTTON1: i32 = 1001;
-pub const IDC_BUTTON2: i32 = 1002;
-pub const IDC_BUTTON3: i32 = 1003;
-pub const IDC_EDIT1: i32 = 1004;
-pub const IDC_BUTTON4: i32 = 1005;
-pub const IDC_BUTTON_MULTI: i32 = 1006;
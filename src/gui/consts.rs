@@ -0,0 +1,40 @@
+use winapi::um::winuser::WM_USER;
+
+pub const CLASS_NAME: &str = "TtySenderClass";
+pub const IDC_BUTTON1: i32 = 1001;
+pub const IDC_BUTTON2: i32 = 1002;
+pub const IDC_BUTTON3: i32 = 1003;
+pub const IDC_EDIT1: i32 = 1004;
+pub const IDC_BUTTON4: i32 = 1005;
+pub const IDC_BUTTON_MULTI: i32 = 1006;
+pub const IDC_MSG_BOX: i32 = 1007;
+pub const IDC_SECTION_LIST: i32 = 1008;
+pub const IDC_TERMINAL: i32 = 1009;
+
+// 菜单项
+pub const IDM_THEME_DARK: i32 = 2001;
+pub const IDM_THEME_LIGHT: i32 = 2002;
+pub const IDM_THEME_HIGH_CONTRAST: i32 = 2003;
+pub const IDM_THEME_CUSTOM: i32 = 2004;
+pub const IDM_LANG_AUTO: i32 = 2011;
+pub const IDM_LANG_ZH_CN: i32 = 2012;
+pub const IDM_LANG_EN_US: i32 = 2013;
+pub const IDM_ABORT: i32 = 2101;
+pub const IDM_SEND_SELECTION: i32 = 2102;
+pub const IDM_RUN_TO_CURSOR: i32 = 2103;
+pub const IDM_SEND_RANGE: i32 = 2104;
+pub const IDM_SEND_COUNT: i32 = 2105;
+pub const IDM_RUN_SECTION: i32 = 2106;
+pub const IDM_STOP_BLOCK: i32 = 2111;
+pub const IDM_STOP_BLANK: i32 = 2112;
+pub const IDM_STOP_EOF: i32 = 2113;
+pub const IDM_CHECK_STATUS: i32 = 2121;
+pub const IDM_TERMINAL_CONNECT: i32 = 2201;
+pub const IDM_TERMINAL_DISCONNECT: i32 = 2202;
+
+// RichEdit 消息 (winapi 未提供 richedit 模块)
+pub const MSFTEDIT_CLASS: &str = "RICHEDIT50W";
+pub const EM_SETBKGNDCOLOR: u32 = WM_USER + 67;
+pub const EM_SETCHARFORMAT: u32 = WM_USER + 68;
+pub const SCF_SELECTION: usize = 0x0001;
+pub const CFM_COLOR: u32 = 0x4000_0000;
@@ -0,0 +1,606 @@
+use super::*;
+use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use tty_sender_core::engine::StopAt;
+
+use lazy_static::lazy_static;
+
+// 右侧按钮 (竖排), 顺序与 create_controls 中的句柄下标对应
+const BUTTONS: [(Msg, i32); 5] = [
+    (Msg::ButtonOpen, IDC_BUTTON2),
+    (Msg::ButtonSave, IDC_BUTTON3),
+    (Msg::ButtonSend, IDC_BUTTON1),
+    (Msg::ButtonMultiSend, IDC_BUTTON_MULTI),
+    (Msg::ButtonBind, IDC_BUTTON4),  // 绑定 (放在最下面)
+];
+
+lazy_static! {
+    pub static ref BUTTON_PROCS: Arc<Mutex<HashMap<isize, unsafe extern "system" fn(HWND, UINT, WPARAM, LPARAM) -> LRESULT>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+    pub static ref DRAGGING: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
+}
+
+pub fn create_controls(hwnd: HWND) -> io::Result<()> {
+    let hinstance = get_hinstance();
+
+    // 主编辑框 (左侧)
+    let edit_handle = unsafe {
+        CreateWindowExW(
+            WS_EX_CLIENTEDGE,
+            w("EDIT\0").as_ptr(),
+            null_mut(),
+            (WS_CHILD | WS_VISIBLE | ES_MULTILINE | WS_HSCROLL | WS_VSCROLL | ES_AUTOVSCROLL | ES_WANTRETURN) as _,
+            0, 0, 0, 0,  // 位置由 apply_layout 计算
+            hwnd,
+            IDC_EDIT1 as isize as HMENU,
+            hinstance,
+            null_mut(),
+        )
+    };
+
+    // 消息文本框 (左下), 使用 RichEdit 以便每条消息有自己的颜色
+    unsafe {
+        if LoadLibraryW(w("Msftedit.dll").as_ptr()).is_null() {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    let msg_handle = unsafe {
+        CreateWindowExW(
+            WS_EX_CLIENTEDGE,
+            w(MSFTEDIT_CLASS).as_ptr(),
+            null_mut(),
+            (WS_CHILD | WS_VISIBLE | ES_MULTILINE | WS_VSCROLL | ES_READONLY | ES_AUTOVSCROLL) as _,
+            0, 0, 0, 0,
+            hwnd,
+            IDC_MSG_BOX as isize as HMENU,
+            hinstance,
+            null_mut(),
+        )
+    };
+    if msg_handle.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+
+    // 分段列表 (右侧按钮下方), 单击跳到该段, 双击运行
+    let section_list_handle = unsafe {
+        CreateWindowExW(
+            WS_EX_CLIENTEDGE,
+            w("LISTBOX").as_ptr(),
+            null_mut(),
+            (WS_CHILD | WS_VISIBLE | WS_VSCROLL | WS_TABSTOP) | (LBS_NOTIFY | LBS_NOINTEGRALHEIGHT) as DWORD,
+            0, 0, 0, 0,
+            hwnd,
+            IDC_SECTION_LIST as isize as HMENU,
+            hinstance,
+            null_mut(),
+        )
+    };
+
+    // 终端窗格 (编辑框右侧), 连接目标后显示
+    let terminal_handle = create_terminal(hwnd);
+
+    // 右侧按钮 (竖排)
+    let button_handles = BUTTONS
+    .iter()
+    .map(|(text, id)| {
+        let handle = unsafe {
+            CreateWindowExW(
+                0,
+                w("BUTTON\0").as_ptr(),
+                w(tr(*text)).as_ptr(),
+                (WS_CHILD | WS_VISIBLE | WS_TABSTOP | BS_OWNERDRAW) as DWORD,
+                0,
+                0,
+                0,
+                0,
+                hwnd,
+                *id as isize as HMENU,
+                hinstance,
+                null_mut(),
+            )
+        };
+        if handle.is_null() {
+            show_error_message(hwnd, tr(Msg::CreateWindowFailed));
+            Err(io::Error::last_os_error())
+        } else {
+            if *id == IDC_BUTTON4 {
+                subclass_button(handle)?;
+            }
+            Ok(handle)
+        }
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+
+    // 更新WindowData
+    let data_ptr = unsafe { GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut WindowData };
+    if !data_ptr.is_null() {
+        let data = unsafe { &mut *data_ptr };
+        data.edit_handle = edit_handle;
+        data.send_button_handle = button_handles[2];  // 发送按钮
+        data.load_button_handle = button_handles[0];  // 打开按钮
+        data.save_button_handle = button_handles[1];  // 保存按钮
+        data.multi_send_button_handle = button_handles[3];  // 多发按钮
+        data.section_list_handle = section_list_handle;
+        data.terminal_handle = terminal_handle;
+        data.dpi = window_dpi(hwnd);
+        update_fonts(hwnd, data);
+        apply_layout(hwnd, data);
+        apply_theme(hwnd, data);
+        data.accel_table = register_shortcuts(hwnd, &data.config.keys);
+    }
+
+    Ok(())
+}
+
+fn create_menu(hwnd: HWND, data: &WindowData) {
+    unsafe {
+        let menu_bar = CreateMenu();
+        // 菜单项后附上对应的快捷键
+        let keys = &data.config.keys;
+        let send_menu = CreatePopupMenu();
+        for (text, id, key) in [
+            (Msg::MenuSendLine, IDC_BUTTON1, &keys.send_line),
+            (Msg::MenuMultiSend, IDC_BUTTON_MULTI, &keys.multi_send),
+            (Msg::MenuSendSelection, IDM_SEND_SELECTION, &keys.send_selection),
+            (Msg::MenuRunToCursor, IDM_RUN_TO_CURSOR, &keys.run_to_cursor),
+            (Msg::MenuSendRange, IDM_SEND_RANGE, &String::new()),
+            (Msg::MenuSendCount, IDM_SEND_COUNT, &String::new()),
+            (Msg::MenuRunSection, IDM_RUN_SECTION, &keys.run_section),
+            (Msg::MenuAbort, IDM_ABORT, &keys.abort),
+        ] {
+            let label = if key.is_empty() { tr(text).to_string() } else { format!("{}\t{}", tr(text), key) };
+            if id == IDM_ABORT {
+                AppendMenuW(send_menu, MF_SEPARATOR, 0, null_mut());
+            }
+            AppendMenuW(send_menu, MF_STRING, id as usize, w(&label).as_ptr());
+        }
+        let stop_menu = CreatePopupMenu();
+        for (text, id) in [
+            (Msg::MenuStopBlock, IDM_STOP_BLOCK),
+            (Msg::MenuStopBlank, IDM_STOP_BLANK),
+            (Msg::MenuStopEof, IDM_STOP_EOF),
+        ] {
+            AppendMenuW(stop_menu, MF_STRING, id as usize, w(tr(text)).as_ptr());
+        }
+        AppendMenuW(send_menu, MF_SEPARATOR, 0, null_mut());
+        AppendMenuW(send_menu, MF_POPUP, stop_menu as usize, w(tr(Msg::MenuStopAt)).as_ptr());
+        let check_status = if data.config.send.check_status { MF_CHECKED } else { MF_UNCHECKED };
+        AppendMenuW(send_menu, MF_STRING | check_status, IDM_CHECK_STATUS as usize, w(tr(Msg::MenuCheckStatus)).as_ptr());
+        AppendMenuW(menu_bar, MF_POPUP, send_menu as usize, w(tr(Msg::MenuSend)).as_ptr());
+
+        let terminal_menu = CreatePopupMenu();
+        AppendMenuW(terminal_menu, MF_STRING, IDM_TERMINAL_CONNECT as usize, w(tr(Msg::MenuTerminalConnect)).as_ptr());
+        AppendMenuW(terminal_menu, MF_STRING, IDM_TERMINAL_DISCONNECT as usize, w(tr(Msg::MenuTerminalDisconnect)).as_ptr());
+        AppendMenuW(menu_bar, MF_POPUP, terminal_menu as usize, w(tr(Msg::MenuTerminal)).as_ptr());
+
+        let theme_menu = CreatePopupMenu();
+        for (text, id) in [
+            (Msg::MenuThemeDark, IDM_THEME_DARK),
+            (Msg::MenuThemeLight, IDM_THEME_LIGHT),
+            (Msg::MenuThemeHighContrast, IDM_THEME_HIGH_CONTRAST),
+            (Msg::MenuThemeCustom, IDM_THEME_CUSTOM),
+        ] {
+            AppendMenuW(theme_menu, MF_STRING, id as usize, w(tr(text)).as_ptr());
+        }
+        AppendMenuW(menu_bar, MF_POPUP, theme_menu as usize, w(tr(Msg::MenuTheme)).as_ptr());
+
+        // 语言名称固定用各自的语言显示
+        let lang_menu = CreatePopupMenu();
+        AppendMenuW(lang_menu, MF_STRING, IDM_LANG_AUTO as usize, w(tr(Msg::MenuLanguageAuto)).as_ptr());
+        AppendMenuW(lang_menu, MF_STRING, IDM_LANG_ZH_CN as usize, w("中文").as_ptr());
+        AppendMenuW(lang_menu, MF_STRING, IDM_LANG_EN_US as usize, w("English").as_ptr());
+        AppendMenuW(menu_bar, MF_POPUP, lang_menu as usize, w(tr(Msg::MenuLanguage)).as_ptr());
+
+        let old_menu = GetMenu(hwnd);
+        SetMenu(hwnd, menu_bar);
+        if !old_menu.is_null() {
+            DestroyMenu(old_menu);
+        }
+        CheckMenuRadioItem(
+            menu_bar,
+            IDM_THEME_DARK as UINT,
+            IDM_THEME_CUSTOM as UINT,
+            ThemeKind::from_name(&data.config.theme.name).menu_id() as UINT,
+            MF_BYCOMMAND,
+        );
+        CheckMenuRadioItem(
+            menu_bar,
+            IDM_LANG_AUTO as UINT,
+            IDM_LANG_EN_US as UINT,
+            language_menu_id(&data.config.language) as UINT,
+            MF_BYCOMMAND,
+        );
+        CheckMenuRadioItem(
+            menu_bar,
+            IDM_STOP_BLOCK as UINT,
+            IDM_STOP_EOF as UINT,
+            stop_menu_id(data.config.send.options().stop) as UINT,
+            MF_BYCOMMAND,
+        );
+        DrawMenuBar(hwnd);
+    }
+}
+
+fn language_from_menu_id(id: i32) -> Option<&'static str> {
+    match id {
+        IDM_LANG_AUTO => Some("auto"),
+        IDM_LANG_ZH_CN => Some("zh-CN"),
+        IDM_LANG_EN_US => Some("en-US"),
+        _ => None,
+    }
+}
+
+fn language_menu_id(setting: &str) -> i32 {
+    match setting {
+        "zh-CN" => IDM_LANG_ZH_CN,
+        "en-US" => IDM_LANG_EN_US,
+        _ => IDM_LANG_AUTO,
+    }
+}
+
+fn stop_from_menu_id(id: i32) -> Option<StopAt> {
+    match id {
+        IDM_STOP_BLOCK => Some(StopAt::BlockEnd),
+        IDM_STOP_BLANK => Some(StopAt::Blank),
+        IDM_STOP_EOF => Some(StopAt::EndOfInput),
+        _ => None,
+    }
+}
+
+fn stop_menu_id(stop: StopAt) -> i32 {
+    match stop {
+        StopAt::BlockEnd => IDM_STOP_BLOCK,
+        StopAt::Blank => IDM_STOP_BLANK,
+        StopAt::EndOfInput => IDM_STOP_EOF,
+    }
+}
+
+/// 按配置切换界面语言, 重建菜单并更新按钮文字
+pub fn apply_language(hwnd: HWND, data: &WindowData) {
+    set_language(Lang::from_setting(&data.config.language));
+    create_menu(hwnd, data);
+    unsafe {
+        for (text, id) in BUTTONS {
+            SetWindowTextW(GetDlgItem(hwnd, id), w(tr(text)).as_ptr());
+        }
+    }
+}
+
+/// 按配置中的主题重建画刷并刷新所有控件
+pub fn apply_theme(hwnd: HWND, data: &mut WindowData) {
+    let theme = Theme::from_config(&data.config.theme);
+    *CURRENT_THEME.lock().unwrap() = theme;
+
+    unsafe {
+        for brush in [data.background_brush.take(), data.window_brush.take()].into_iter().flatten() {
+            DeleteObject(brush as _);
+        }
+        data.background_brush = Some(CreateSolidBrush(theme.editor_background));
+        data.window_brush = Some(CreateSolidBrush(theme.window_background));
+
+        create_menu(hwnd, data);
+        redraw_messages(hwnd);
+        InvalidateRect(hwnd, null_mut(), TRUE);
+        RedrawWindow(hwnd, null_mut(), null_mut(), RDW_INVALIDATE | RDW_ERASE | RDW_ALLCHILDREN);
+    }
+}
+
+/// 自绘按钮, 使用主题中的按钮配色
+unsafe fn draw_button(item: &DRAWITEMSTRUCT) {
+    let theme = current_theme();
+    let pressed = item.itemState & ODS_SELECTED != 0;
+    let mut rect = item.rcItem;
+
+    let fill = CreateSolidBrush(if pressed { theme.button_pressed } else { theme.button_background });
+    FillRect(item.hDC, &rect, fill);
+    DeleteObject(fill as _);
+
+    let border = CreateSolidBrush(theme.button_border);
+    FrameRect(item.hDC, &rect, border);
+    DeleteObject(border as _);
+
+    let length = GetWindowTextLengthW(item.hwndItem);
+    let mut text = vec![0u16; length as usize + 1];
+    GetWindowTextW(item.hwndItem, text.as_mut_ptr(), length + 1);
+    SetBkMode(item.hDC, TRANSPARENT as i32);
+    SetTextColor(item.hDC, theme.button_text);
+    DrawTextW(item.hDC, text.as_ptr(), length, &mut rect, DT_CENTER | DT_VCENTER | DT_SINGLELINE);
+
+    if item.itemState & ODS_FOCUS != 0 {
+        InflateRect(&mut rect, -3, -3);
+        DrawFocusRect(item.hDC, &rect);
+    }
+}
+
+pub fn subclass_button(hwnd: HWND) -> io::Result<()> {
+    unsafe {
+        let original_proc = GetWindowLongPtrW(hwnd, GWLP_WNDPROC);
+        BUTTON_PROCS
+            .lock()
+            .unwrap()
+            .insert(hwnd as isize, std::mem::transmute(original_proc));
+
+        if SetWindowLongPtrW(hwnd, GWLP_WNDPROC, drag_button_proc as usize as isize) == 0 {
+            show_error_message(null_mut(), tr(Msg::SubclassFailed));
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+
+unsafe extern "system" fn drag_button_proc(
+    hwnd: HWND,
+    u_msg: UINT,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    let hwnd_key = hwnd as isize;
+    let original_proc = BUTTON_PROCS.lock().unwrap().get(&hwnd_key).cloned();
+
+    match u_msg {
+        WM_LBUTTONDOWN => {
+            *DRAGGING.lock().unwrap() = true;
+            SetCapture(hwnd);
+            if let Some(proc) = original_proc {
+                CallWindowProcW(Some(proc), hwnd, u_msg, w_param, l_param)
+            } else {
+                DefWindowProcW(hwnd, u_msg, w_param, l_param)
+            }
+        }
+        WM_MOUSEMOVE => 0,
+        WM_LBUTTONUP => {
+            *DRAGGING.lock().unwrap() = false;
+            ReleaseCapture();
+            let mut pt = POINT {
+                x: LOWORD(l_param as DWORD) as i32,
+                y: HIWORD(l_param as DWORD) as i32,
+            };
+            ClientToScreen(hwnd, &mut pt);
+
+            let target_hwnd = WindowFromPoint(pt);
+            let root_hwnd = GetAncestor(target_hwnd, GA_ROOT);
+
+            if !root_hwnd.is_null() && IsWindow(root_hwnd) != 0 {
+                let parent = GetParent(hwnd);
+                let data_ptr = GetWindowLongPtrW(parent, GWLP_USERDATA) as *mut WindowData;
+                if !data_ptr.is_null() {
+                    let data = &mut *data_ptr;
+                    data.target_hwnd = root_hwnd;
+                    show_message(parent, &tr_fmt(Msg::BindSuccess, &[("hwnd", &format!("{:?}", root_hwnd))]));
+                } else {
+                    show_error_message(parent, tr(Msg::WindowDataMissing));
+                }
+            } else {
+                show_error_message(hwnd, tr(Msg::InvalidWindow));
+            }
+
+            if let Some(proc) = original_proc {
+                CallWindowProcW(Some(proc), hwnd, u_msg, w_param, l_param)
+            } else {
+                DefWindowProcW(hwnd, u_msg, w_param, l_param)
+            }
+        }
+        _ => {
+            if let Some(proc) = original_proc {
+                CallWindowProcW(Some(proc), hwnd, u_msg, w_param, l_param)
+            } else {
+                DefWindowProcW(hwnd, u_msg, w_param, l_param)
+            }
+        }
+    }
+}
+
+pub unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    u_msg: UINT,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    let data_ptr = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut WindowData;
+
+    match u_msg {
+        WM_CTLCOLOREDIT | WM_CTLCOLORSTATIC | WM_CTLCOLORLISTBOX => {  // 主编辑框 (只读时发送 WM_CTLCOLORSTATIC) 和分段列表
+            let edit_hwnd = l_param as HWND;
+            let ctrl_id = GetWindowLongPtrW(edit_hwnd, GWLP_ID) as i32;
+
+            if (ctrl_id == IDC_EDIT1 || ctrl_id == IDC_SECTION_LIST) && !data_ptr.is_null() {
+                let data = &mut *data_ptr;
+                if let Some(brush) = data.background_brush {
+                    let theme = current_theme();
+                    SetTextColor(w_param as HDC, theme.editor_text);
+                    SetBkColor(w_param as HDC, theme.editor_background);
+                    return brush as LRESULT;
+                }
+            }
+            DefWindowProcW(hwnd, u_msg, w_param, l_param)
+        }
+        WM_ERASEBKGND => {
+            if !data_ptr.is_null() {
+                if let Some(brush) = (*data_ptr).window_brush {
+                    let mut rect: RECT = std::mem::zeroed();
+                    GetClientRect(hwnd, &mut rect);
+                    FillRect(w_param as HDC, &rect, brush);
+                    return 1;
+                }
+            }
+            DefWindowProcW(hwnd, u_msg, w_param, l_param)
+        }
+        WM_SIZE => {
+            if !data_ptr.is_null() {
+                apply_layout(hwnd, &*data_ptr);
+            }
+            0
+        }
+        WM_DPICHANGED => {
+            if !data_ptr.is_null() {
+                let data = &mut *data_ptr;
+                data.dpi = HIWORD(w_param as DWORD) as u32;
+                let suggested = &*(l_param as *const RECT);
+                SetWindowPos(
+                    hwnd,
+                    null_mut(),
+                    suggested.left,
+                    suggested.top,
+                    suggested.right - suggested.left,
+                    suggested.bottom - suggested.top,
+                    SWP_NOZORDER | SWP_NOACTIVATE,
+                );
+                update_fonts(hwnd, data);
+                apply_layout(hwnd, data);
+            }
+            0
+        }
+        WM_GETMINMAXINFO => {
+            let info = &mut *(l_param as *mut MINMAXINFO);
+            let (min_width, min_height) = min_window_size(window_dpi(hwnd));
+            info.ptMinTrackSize.x = min_width;
+            info.ptMinTrackSize.y = min_height;
+            0
+        }
+        WM_SETCURSOR => {
+            if !data_ptr.is_null() && LOWORD(l_param as DWORD) as isize == HTCLIENT {
+                let mut pt: POINT = std::mem::zeroed();
+                GetCursorPos(&mut pt);
+                ScreenToClient(hwnd, &mut pt);
+                if current_layout(hwnd, &*data_ptr).splitter.contains(pt.x, pt.y) {
+                    SetCursor(LoadCursorW(null_mut(), IDC_SIZENS));
+                    return TRUE as LRESULT;
+                }
+            }
+            DefWindowProcW(hwnd, u_msg, w_param, l_param)
+        }
+        WM_LBUTTONDOWN => {
+            if !data_ptr.is_null() {
+                let data = &mut *data_ptr;
+                let (x, y) = (GET_X_LPARAM(l_param), GET_Y_LPARAM(l_param));
+                if current_layout(hwnd, data).splitter.contains(x, y) {
+                    data.splitter_dragging = true;
+                    SetCapture(hwnd);
+                }
+            }
+            0
+        }
+        WM_MOUSEMOVE => {
+            if !data_ptr.is_null() && (*data_ptr).splitter_dragging {
+                let data = &mut *data_ptr;
+                let mut rect: RECT = std::mem::zeroed();
+                GetClientRect(hwnd, &mut rect);
+                data.config.layout.message_height =
+                    message_height_from_drag(rect.bottom, data.dpi, GET_Y_LPARAM(l_param));
+                apply_layout(hwnd, data);
+            }
+            0
+        }
+        WM_LBUTTONUP => {
+            if !data_ptr.is_null() && (*data_ptr).splitter_dragging {
+                let data = &mut *data_ptr;
+                data.splitter_dragging = false;
+                ReleaseCapture();
+                if let Err(e) = data.config.save() {
+                    show_error_message(hwnd, &tr_fmt(Msg::SaveConfigFailed, &[("error", &e)]));
+                }
+            }
+            0
+        }
+        WM_DRAWITEM => {
+            let item = &*(l_param as *const DRAWITEMSTRUCT);
+            if item.CtlType == ODT_BUTTON {
+                draw_button(item);
+                return TRUE as LRESULT;
+            }
+            DefWindowProcW(hwnd, u_msg, w_param, l_param)
+        }
+        WM_HOTKEY => {
+            if !data_ptr.is_null() && is_send_line_hotkey(w_param) {
+                send_line_to_window(&mut *data_ptr);
+            }
+            0
+        }
+        WM_DESTROY => {
+            unregister_shortcuts(hwnd);
+            if !data_ptr.is_null() {
+                // 子窗口随后销毁时不再访问已释放的 WindowData
+                SetWindowLongPtrW(hwnd, GWLP_USERDATA, 0);
+                let mut data = Box::from_raw(data_ptr);
+                data.abort_flag.store(true, Ordering::SeqCst);
+                if let Some(thread) = data.sender_thread.take() {
+                    thread.join().unwrap();
+                }
+                if let Some(capture) = data.terminal.take().and_then(|session| session.capture) {
+                    let _ = capture.finish();
+                }
+                for brush in [data.background_brush, data.window_brush].into_iter().flatten() {
+                    DeleteObject(brush as _);
+                }
+                for font in [data.editor_font, data.ui_font].into_iter().flatten() {
+                    DeleteObject(font as _);
+                }
+            }
+            PostQuitMessage(0);
+            0
+        }
+        WM_COMMAND => {
+            if data_ptr.is_null() {
+                return DefWindowProcW(hwnd, u_msg, w_param, l_param);
+            }
+            let data = &mut *data_ptr;
+            let cmd_id = LOWORD(w_param as DWORD) as i32;
+            let notification = HIWORD(w_param as DWORD);
+            match cmd_id {
+                IDC_EDIT1 => {
+                    if notification == EN_CHANGE {
+                        refresh_sections(data);
+                    }
+                }
+                IDC_SECTION_LIST => match notification {
+                    LBN_SELCHANGE => goto_section(data),
+                    LBN_DBLCLK => run_section(data),
+                    _ => {}
+                },
+                IDC_BUTTON1 => send_line_to_window(data),
+                IDC_BUTTON2 => load_file(data),
+                IDC_BUTTON3 => save_file(data),
+                IDC_BUTTON_MULTI => multi_send_lines(data),
+                IDM_SEND_SELECTION => send_selection(data),
+                IDM_RUN_TO_CURSOR => run_to_cursor(data),
+                IDM_SEND_RANGE => send_range(hwnd, data),
+                IDM_SEND_COUNT => send_count(hwnd, data),
+                IDM_RUN_SECTION => run_section(data),
+                IDM_ABORT => abort_sending(data),
+                IDM_TERMINAL_CONNECT => connect_terminal(hwnd, data),
+                IDM_TERMINAL_DISCONNECT => disconnect_terminal(hwnd, data),
+                IDM_CHECK_STATUS => {
+                    data.config.send.check_status = !data.config.send.check_status;
+                    create_menu(hwnd, data);
+                    if let Err(e) = data.config.save() {
+                        show_error_message(hwnd, &tr_fmt(Msg::SaveConfigFailed, &[("error", &e)]));
+                    }
+                }
+                _ => {
+                    if let Some(language) = language_from_menu_id(cmd_id) {
+                        data.config.language = language.to_string();
+                        apply_language(hwnd, data);
+                        if let Err(e) = data.config.save() {
+                            show_error_message(hwnd, &tr_fmt(Msg::SaveConfigFailed, &[("error", &e)]));
+                        }
+                    } else if let Some(stop) = stop_from_menu_id(cmd_id) {
+                        data.config.send.stop = stop.name().to_string();
+                        create_menu(hwnd, data);
+                        if let Err(e) = data.config.save() {
+                            show_error_message(hwnd, &tr_fmt(Msg::SaveConfigFailed, &[("error", &e)]));
+                        }
+                    } else if let Some(kind) = ThemeKind::from_menu_id(cmd_id) {
+                        data.config.theme.name = kind.name().to_string();
+                        apply_theme(hwnd, data);
+                        if let Err(e) = data.config.save() {
+                            show_error_message(hwnd, &tr_fmt(Msg::SaveConfigFailed, &[("error", &e)]));
+                        }
+                    }
+                }
+            }
+            0
+        }
+        _ => DefWindowProcW(hwnd, u_msg, w_param, l_param),
+    }
+}
@@ -0,0 +1,208 @@
+use super::*;
+use tty_sender_core::guard::GuardHit;
+
+const PROMPT_CLASS_NAME: &str = "TtySenderPromptClass";
+const IDC_PROMPT_EDIT: i32 = 3001;
+
+struct PromptState<'a> {
+    edit: HWND,
+    preview: HWND,
+    describe: &'a dyn Fn(&str) -> String,
+    result: Option<String>,
+    done: bool,
+}
+
+/// 模态输入框; 输入变化时用 describe 生成预览文字显示在输入框下方. 取消时返回 None
+pub fn prompt_text(
+    owner: HWND,
+    title: &str,
+    label: &str,
+    initial: &str,
+    describe: &dyn Fn(&str) -> String,
+) -> Option<String> {
+    unsafe {
+        register_prompt_class();
+
+        let dpi = window_dpi(owner);
+        let (width, height) = (scale(360, dpi), scale(170, dpi));
+        let mut owner_rect: RECT = std::mem::zeroed();
+        GetWindowRect(owner, &mut owner_rect);
+        let x = owner_rect.left + (owner_rect.right - owner_rect.left - width) / 2;
+        let y = owner_rect.top + (owner_rect.bottom - owner_rect.top - height) / 2;
+
+        let dialog = CreateWindowExW(
+            WS_EX_DLGMODALFRAME,
+            w(PROMPT_CLASS_NAME).as_ptr(),
+            w(title).as_ptr(),
+            WS_POPUP | WS_CAPTION | WS_SYSMENU,
+            x, y, width, height,
+            owner,
+            null_mut(),
+            get_hinstance(),
+            null_mut(),
+        );
+        if dialog.is_null() {
+            return None;
+        }
+
+        let mut client: RECT = std::mem::zeroed();
+        GetClientRect(dialog, &mut client);
+        let margin = scale(12, dpi);
+        let row = scale(24, dpi);
+        let inner_width = client.right - margin * 2;
+        let button_width = scale(80, dpi);
+        let child = |class: &str, text: &str, style: DWORD, id: i32, x: i32, y: i32, cx: i32, cy: i32| {
+            CreateWindowExW(
+                if class == "EDIT" { WS_EX_CLIENTEDGE } else { 0 },
+                w(class).as_ptr(),
+                w(text).as_ptr(),
+                WS_CHILD | WS_VISIBLE | style,
+                x, y, cx, cy,
+                dialog,
+                id as isize as HMENU,
+                get_hinstance(),
+                null_mut(),
+            )
+        };
+        let label_handle = child("STATIC", label, 0, 0, margin, margin, inner_width, row);
+        let edit = child(
+            "EDIT", initial, WS_TABSTOP | ES_AUTOHSCROLL as DWORD, IDC_PROMPT_EDIT,
+            margin, margin + row, inner_width, row,
+        );
+        let preview = child("STATIC", "", 0, 0, margin, margin + row * 2 + scale(4, dpi), inner_width, row);
+        let button_y = client.bottom - margin - row;
+        let ok = child(
+            "BUTTON", tr(Msg::DialogOk), WS_TABSTOP | BS_DEFPUSHBUTTON as DWORD, IDOK,
+            client.right - margin * 2 - button_width * 2, button_y, button_width, row,
+        );
+        let cancel = child(
+            "BUTTON", tr(Msg::DialogCancel), WS_TABSTOP | BS_PUSHBUTTON as DWORD, IDCANCEL,
+            client.right - margin - button_width, button_y, button_width, row,
+        );
+
+        // 与主窗口按钮使用相同字体
+        let font = SendMessageW(GetDlgItem(owner, IDC_BUTTON1), WM_GETFONT, 0, 0) as WPARAM;
+        for handle in [label_handle, edit, preview, ok, cancel] {
+            SendMessageW(handle, WM_SETFONT, font, TRUE as LPARAM);
+        }
+
+        // 窗口过程通过同一个指针修改状态, 直到对话框销毁
+        let state = Box::into_raw(Box::new(PromptState { edit, preview, describe, result: None, done: false }));
+        SetWindowLongPtrW(dialog, GWLP_USERDATA, state as LPARAM);
+        update_preview(&*state);
+
+        EnableWindow(owner, FALSE);
+        ShowWindow(dialog, SW_SHOW);
+        SetFocus(edit);
+        SendMessageW(edit, EM_SETSEL as UINT, 0, -1);
+
+        let mut msg: MSG = std::mem::zeroed();
+        loop {
+            // done 由窗口过程设置
+            if (*state).done {
+                break;
+            }
+            match GetMessageW(&mut msg, null_mut(), 0, 0) {
+                0 => {
+                    // 退出消息留给主消息循环
+                    PostQuitMessage(msg.wParam as i32);
+                    break;
+                }
+                -1 => break,
+                _ => {
+                    if IsDialogMessageW(dialog, &mut msg) == 0 {
+                        TranslateMessage(&msg);
+                        DispatchMessageW(&msg);
+                    }
+                }
+            }
+        }
+
+        EnableWindow(owner, TRUE);
+        SetWindowLongPtrW(dialog, GWLP_USERDATA, 0);
+        DestroyWindow(dialog);
+        SetActiveWindow(owner);
+        Box::from_raw(state).result
+    }
+}
+
+/// 确认发送危险命令; 受保护的目标须输入其名称, 其他目标在是/否对话框中确认 (默认为否)
+pub fn confirm_dangerous(owner: HWND, hits: &[GuardHit], protected_name: Option<&str>) -> bool {
+    let lines: Vec<String> = hits
+        .iter()
+        .map(|hit| tr_fmt(Msg::GuardHitLine, &[("line", &hit.line.number), ("text", &hit.text)]))
+        .collect();
+    let lines = lines.join("\n");
+    match protected_name {
+        Some(name) => {
+            show_error_message(owner, &format!("{}\n{}", tr(Msg::GuardHits), lines));
+            let label = tr_fmt(Msg::GuardProtectedLabel, &[("target", &name)]);
+            let describe = |text: &str| {
+                if text.trim() == name { String::new() } else { tr_fmt(Msg::GuardHitCount, &[("count", &hits.len())]) }
+            };
+            prompt_text(owner, tr(Msg::GuardDialogTitle), &label, "", &describe).is_some_and(|text| text.trim() == name)
+        }
+        None => unsafe {
+            let text = tr_fmt(Msg::GuardConfirm, &[("lines", &lines)]);
+            MessageBoxW(
+                owner,
+                w(&text).as_ptr(),
+                w(tr(Msg::GuardDialogTitle)).as_ptr(),
+                MB_YESNO | MB_ICONWARNING | MB_DEFBUTTON2,
+            ) == IDYES
+        },
+    }
+}
+
+unsafe fn register_prompt_class() {
+    let class_name = w(PROMPT_CLASS_NAME);
+    let wc = WNDCLASSW {
+        style: 0,
+        lpfnWndProc: Some(prompt_proc),
+        cbClsExtra: 0,
+        cbWndExtra: 0,
+        hInstance: get_hinstance(),
+        hIcon: null_mut(),
+        hCursor: LoadCursorW(null_mut(), IDC_ARROW),
+        hbrBackground: GetSysColorBrush(COLOR_BTNFACE),
+        lpszMenuName: null_mut(),
+        lpszClassName: class_name.as_ptr(),
+    };
+    // 第二次注册会失败, 可以忽略
+    RegisterClassW(&wc);
+}
+
+unsafe fn update_preview(state: &PromptState) {
+    let text = (state.describe)(&editor_text(state.edit));
+    SetWindowTextW(state.preview, w(&text).as_ptr());
+}
+
+unsafe extern "system" fn prompt_proc(hwnd: HWND, u_msg: UINT, w_param: WPARAM, l_param: LPARAM) -> LRESULT {
+    let state_ptr = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut PromptState;
+    if state_ptr.is_null() {
+        return DefWindowProcW(hwnd, u_msg, w_param, l_param);
+    }
+    let state = &mut *state_ptr;
+    match u_msg {
+        WM_COMMAND => {
+            let id = LOWORD(w_param as DWORD) as i32;
+            let notification = HIWORD(w_param as DWORD);
+            match id {
+                IDOK => {
+                    state.result = Some(editor_text(state.edit));
+                    state.done = true;
+                }
+                IDCANCEL => state.done = true,
+                IDC_PROMPT_EDIT if notification == EN_CHANGE => update_preview(state),
+                _ => {}
+            }
+            0
+        }
+        // 关闭按钮等同于取消, 窗口由 prompt_text 销毁
+        WM_CLOSE => {
+            state.done = true;
+            0
+        }
+        _ => DefWindowProcW(hwnd, u_msg, w_param, l_param),
+    }
+}
@@ -33,24 +33,18 @@ pub fn load_file(data: &mut WindowData) {
 
         if hr < 0 || pfd.is_null() {
             CoUninitialize();
-            show_error_message(
-                data.edit_handle,
-                "Failed to create file open dialog",
-            );
+            show_error_message(data.edit_handle, tr(Msg::CreateOpenDialogFailed));
             return;
         }
 
         let dialog: ComPtr<IFileOpenDialog> = ComPtr::from_raw(pfd);
-        let _ = dialog.SetTitle(w("Select File to Open\0").as_ptr());
+        let _ = dialog.SetTitle(w(tr(Msg::OpenDialogTitle)).as_ptr());
         let _ = dialog.SetOptions(FOS_FORCEFILESYSTEM | FOS_ALLNONSTORAGEITEMS);
 
         let hr = dialog.Show(null_mut());
         if hr < 0 {
             CoUninitialize();
-            show_error_message(
-                data.edit_handle,
-                "Failed to show file open dialog",
-            );
+            show_error_message(data.edit_handle, tr(Msg::ShowOpenDialogFailed));
             return;
         }
 
@@ -96,21 +90,18 @@ pub fn save_file(data: &mut WindowData) {
 
         if hr < 0 || pfd.is_null() {
             CoUninitialize();
-            show_error_message(data.edit_handle, "Failed to create file save dialog");
+            show_error_message(data.edit_handle, tr(Msg::CreateSaveDialogFailed));
             return;
         }
 
         let dialog: ComPtr<IFileSaveDialog> = ComPtr::from_raw(pfd);
-        let _ = dialog.SetTitle(w("Save File As\0").as_ptr());
+        let _ = dialog.SetTitle(w(tr(Msg::SaveDialogTitle)).as_ptr());
         let _ = dialog.SetOptions(FOS_OVERWRITEPROMPT | FOS_FORCEFILESYSTEM);
 
         let hr = dialog.Show(null_mut());
         if hr < 0 {
             CoUninitialize();
-            show_error_message(
-                data.edit_handle,
-                "Failed to show file save dialog",
-            );
+            show_error_message(data.edit_handle, tr(Msg::ShowSaveDialogFailed));
             return;
         }
 
@@ -0,0 +1,359 @@
+use super::*;
+use tty_sender_core::engine::{Engine, SendEvent, SendOptions, SendReport, StopAt};
+use tty_sender_core::script::{lines_from_text, parse_range, sections, select_range, LineKind, ScriptLine, Section};
+use tty_sender_core::target::{Target, WindowTarget};
+
+/// 从光标所在行开始发送, 按配置的停止条件结束
+pub fn multi_send_lines(data: &mut WindowData) {
+    let caret_line = unsafe { caret_line(data.edit_handle) };
+    let lines: Vec<_> = editor_lines(data).into_iter().skip(caret_line).collect();
+    let options = data.config.send.options();
+    start_sending(data, lines, options);
+}
+
+/// 发送光标处的一条命令 (或一个 #!include、#!call); 光标在空行、注释或分段标记上时发送其后的第一条
+pub fn send_line_to_window(data: &mut WindowData) {
+    let caret_line = unsafe { caret_line(data.edit_handle) };
+    let options = data.config.send.options();
+    let lines: Vec<_> = editor_lines(data)
+        .into_iter()
+        .skip(caret_line)
+        .find(|line| {
+            matches!(
+                options.syntax.classify(&line.text),
+                LineKind::Command | LineKind::Directive { name: "include" | "call", .. }
+            )
+        })
+        .into_iter()
+        .collect();
+    start_sending(data, lines, options);
+}
+
+/// 发送选中文本覆盖的各行; 选区结束于某行行首时不包含该行
+pub fn send_selection(data: &mut WindowData) {
+    let (first, last) = unsafe {
+        let (start, end) = selection(data.edit_handle);
+        if start == end {
+            show_error_message(data.edit_handle, tr(Msg::NoSelection));
+            return;
+        }
+        let first = SendMessageW(data.edit_handle, EM_LINEFROMCHAR as u32, start as WPARAM, 0) as usize;
+        let mut last = SendMessageW(data.edit_handle, EM_LINEFROMCHAR as u32, end as WPARAM, 0) as usize;
+        let last_start = SendMessageW(data.edit_handle, EM_LINEINDEX as u32, last as WPARAM, 0) as DWORD;
+        if last > first && last_start == end {
+            last -= 1;
+        }
+        (first, last)
+    };
+    send_checked_range(data, first + 1, last + 1);
+}
+
+/// 从第一行发送到光标所在行 (包含该行)
+pub fn run_to_cursor(data: &mut WindowData) {
+    let caret_line = unsafe { caret_line(data.edit_handle) };
+    send_checked_range(data, 1, caret_line + 1);
+}
+
+/// 弹出输入框, 发送用户指定的行范围
+pub fn send_range(hwnd: HWND, data: &mut WindowData) {
+    let lines = editor_lines(data);
+    let current = unsafe { caret_line(data.edit_handle) } + 1;
+    let engine = preview_engine(data);
+    let describe = |text: &str| match parse_range(text) {
+        Some((from, to)) => match select_range(&lines, from, to) {
+            Ok(selected) => preview_text(&engine, &selected),
+            Err(e) => describe_range_error(&e),
+        },
+        None => tr(Msg::InvalidRangeInput).to_string(),
+    };
+    let initial = format!("{}-{}", current, lines.len().max(current));
+    if let Some((from, to)) = prompt_text(hwnd, tr(Msg::MenuSendRange), tr(Msg::SendRangeLabel), &initial, &describe)
+        .and_then(|text| parse_range(&text))
+    {
+        send_checked_range(data, from, to);
+    }
+}
+
+/// 弹出输入框, 从光标所在行开始发送指定行数
+pub fn send_count(hwnd: HWND, data: &mut WindowData) {
+    let lines = editor_lines(data);
+    let from = unsafe { caret_line(data.edit_handle) } + 1;
+    let engine = preview_engine(data);
+    let parse_count = |text: &str| text.trim().parse::<usize>().ok().filter(|&count| count > 0);
+    let describe = |text: &str| match parse_count(text) {
+        Some(count) => match select_range(&lines, from, from + count - 1) {
+            Ok(selected) => preview_text(&engine, &selected),
+            Err(e) => describe_range_error(&e),
+        },
+        None => tr(Msg::InvalidRangeInput).to_string(),
+    };
+    let label = tr_fmt(Msg::SendCountLabel, &[("line", &from)]);
+    if let Some(count) = prompt_text(hwnd, tr(Msg::MenuSendCount), &label, "1", &describe)
+        .and_then(|text| parse_count(&text))
+    {
+        send_checked_range(data, from, from + count - 1);
+    }
+}
+
+/// 编辑框内容变化后重新列出分段, 名称不变时保留列表以免闪烁
+pub fn refresh_sections(data: &mut WindowData) {
+    let sections = sections(&editor_lines(data), &data.config.send.options().syntax);
+    let names_changed = sections.len() != data.sections.len()
+        || sections.iter().zip(&data.sections).any(|(new, old)| new.name != old.name);
+    if names_changed {
+        unsafe {
+            let list = data.section_list_handle;
+            let selected = SendMessageW(list, LB_GETCURSEL, 0, 0);
+            let selected_name = usize::try_from(selected).ok().and_then(|i| data.sections.get(i)).map(|s| s.name.clone());
+            SendMessageW(list, LB_RESETCONTENT, 0, 0);
+            for section in &sections {
+                SendMessageW(list, LB_ADDSTRING, 0, w(&section.name).as_ptr() as LPARAM);
+            }
+            if let Some(index) = selected_name.and_then(|name| sections.iter().position(|s| s.name == name)) {
+                SendMessageW(list, LB_SETCURSEL, index, 0);
+            }
+        }
+    }
+    data.sections = sections;
+}
+
+fn selected_section(data: &WindowData) -> Option<Section> {
+    let selected = unsafe { SendMessageW(data.section_list_handle, LB_GETCURSEL, 0, 0) };
+    usize::try_from(selected).ok().and_then(|i| data.sections.get(i)).cloned()
+}
+
+/// 光标跳到列表中选中的段开头
+pub fn goto_section(data: &mut WindowData) {
+    if let Some(section) = selected_section(data) {
+        unsafe { move_caret_to_line(data.edit_handle, section.line - 1) };
+    }
+}
+
+/// 运行列表中选中的段, 与光标位置无关
+pub fn run_section(data: &mut WindowData) {
+    let section = match selected_section(data) {
+        Some(section) => section,
+        None => {
+            show_error_message(data.edit_handle, tr(Msg::NoSectionSelected));
+            return;
+        }
+    };
+    let lines: Vec<_> = editor_lines(data)
+        .into_iter()
+        .filter(|line| line.number > section.line && line.number <= section.end)
+        .collect();
+    show_message(data.edit_handle, &tr_fmt(Msg::RunningSection, &[("name", &section.name)]));
+    let options = range_options(data);
+    start_sending(data, lines, options);
+}
+
+pub fn abort_sending(data: &mut WindowData) {
+    if data.thread_running.load(Ordering::SeqCst) {
+        data.abort_flag.store(true, Ordering::SeqCst);
+        show_message(data.edit_handle, tr(Msg::SendAborted));
+    }
+}
+
+fn editor_lines(data: &WindowData) -> Vec<ScriptLine> {
+    lines_from_text(&editor_text(data.edit_handle))
+}
+
+/// 明确指定的行范围发送到范围末尾, 不受多行发送停止条件的影响
+fn range_options(data: &WindowData) -> SendOptions {
+    SendOptions {
+        stop: StopAt::EndOfInput,
+        ..data.config.send.options()
+    }
+}
+
+/// #!include 的相对路径以当前打开的文件为准
+fn new_engine(data: &WindowData, options: SendOptions) -> Engine {
+    let mut engine = Engine::new(options, Arc::clone(&data.abort_flag));
+    if !data.file_path.as_os_str().is_empty() {
+        engine.source = Some(data.file_path.clone());
+    }
+    engine.document = editor_lines(data);
+    engine
+}
+
+fn preview_engine(data: &WindowData) -> Engine {
+    new_engine(data, range_options(data))
+}
+
+fn preview_text(engine: &Engine, lines: &[ScriptLine]) -> String {
+    let (from, to) = match (lines.first(), lines.last()) {
+        (Some(first), Some(last)) => (first.number, last.number),
+        _ => (0, 0),
+    };
+    match engine.plan(lines) {
+        Ok(planned) => tr_fmt(Msg::SendPreview, &[("from", &from), ("to", &to), ("count", &planned.len())]),
+        Err(e) => describe_script_error(&e),
+    }
+}
+
+/// 发送第 from 到 to 行 (从 1 开始), 先在消息框中预告将发送的行数
+fn send_checked_range(data: &mut WindowData, from: usize, to: usize) {
+    match select_range(&editor_lines(data), from, to) {
+        Ok(lines) => {
+            show_message(data.edit_handle, &preview_text(&preview_engine(data), &lines));
+            let options = range_options(data);
+            start_sending(data, lines, options);
+        }
+        Err(e) => show_error_message(data.edit_handle, &describe_range_error(&e)),
+    }
+}
+
+fn start_sending(data: &mut WindowData, lines: Vec<ScriptLine>, options: SendOptions) {
+    // 终端窗格已连接时发往终端的目标, 否则发往绑定的窗口
+    let terminal = data.terminal.as_ref().filter(|session| !session.closed);
+    if terminal.is_none() && data.target_hwnd.is_null() {
+        show_error_message(data.edit_handle, tr(Msg::NoTargetWindow));
+        return;
+    }
+
+    // 检查线程是否在运行
+    if data.thread_running.load(Ordering::SeqCst) {
+        show_error_message(data.edit_handle, tr(Msg::SenderBusy));
+        return;
+    }
+
+    // 先展开 #!include 并检查危险命令, 出错或未确认时不开始发送
+    let mut engine = new_engine(data, options);
+    engine.guard = match data.config.guard.build() {
+        Ok(guard) => guard,
+        Err(e) => {
+            show_error_message(data.edit_handle, &tr_fmt(Msg::InvalidGuardPattern, &[("error", &e)]));
+            return;
+        }
+    };
+    let protected_name = match terminal {
+        Some(session) => data.config.is_protected(&session.name).then(|| session.name.clone()),
+        None => data.config.protected_window(&editor_text(data.target_hwnd)).map(str::to_string),
+    };
+    engine.protected = protected_name.is_some();
+    let hits = match engine.dangerous_lines(&lines) {
+        Ok(hits) => hits,
+        Err(e) => {
+            show_error_message(data.edit_handle, &describe_script_error(&e));
+            return;
+        }
+    };
+    if !hits.is_empty() {
+        let owner = unsafe { GetParent(data.edit_handle) };
+        if !confirm_dangerous(owner, &hits, protected_name.as_deref()) {
+            show_message(data.edit_handle, tr(Msg::GuardCancelled));
+            return;
+        }
+        engine.confirmed = true;
+    }
+    engine.transcript = data.config.log.transcript();
+
+    let mut target: Box<dyn Target> = match terminal {
+        Some(session) => {
+            engine.capture = session.capture.clone();
+            Box::new(session.target.clone())
+        }
+        None => match WindowTarget::new(data.target_hwnd as isize) {
+            Ok(target) => Box::new(target),
+            Err(_) => {
+                show_error_message(data.edit_handle, tr(Msg::TargetWindowGone));
+                return;
+            }
+        },
+    };
+
+    data.abort_flag.store(false, Ordering::SeqCst);
+    data.thread_running.store(true, Ordering::SeqCst);
+
+    let edit_handle = data.edit_handle as isize;
+    let thread_running = Arc::clone(&data.thread_running);
+
+    let thread_handler = thread::spawn(move || {
+        let edit_handle = edit_handle as HWND;
+        let target_name = target.describe();
+        let result = engine.run(target.as_mut(), &lines, &mut |event| match event {
+            SendEvent::Sent { line } => {
+                // line.number 从 1 开始, 正好是下一行的下标
+                unsafe { move_caret_to_line(edit_handle, line.number) };
+            }
+            SendEvent::Captured { line, name, value } => {
+                show_message(edit_handle, &tr_fmt(Msg::SendCaptured, &[("line", &line.number), ("name", &name), ("value", &value)]));
+            }
+            SendEvent::ErrorHandler { error, section, .. } => {
+                let error = describe_send_error(error, &target_name);
+                show_message(edit_handle, &tr_fmt(Msg::SendErrorHandler, &[("error", &error), ("section", &section)]));
+            }
+            SendEvent::ErrorGoto { error, label, .. } => {
+                let error = describe_send_error(error, &target_name);
+                show_message(edit_handle, &tr_fmt(Msg::SendErrorGoto, &[("error", &error), ("label", &label)]));
+            }
+            SendEvent::Iteration { line, iteration, total } => {
+                show_message(edit_handle, &describe_iteration(line.number, iteration, total));
+            }
+            SendEvent::Sending { .. } => {}
+        });
+        let results = engine.results();
+        if results.has_assertions() {
+            show_message(edit_handle, &describe_results(&results));
+        }
+        match result {
+            Ok(SendReport { transcript_error: Some(error), .. }) => {
+                show_error_message(edit_handle, &tr_fmt(Msg::TranscriptFailed, &[("error", &error)]));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                // 光标回到失败的那一行
+                if let Some(line) = e.line().filter(|_| e.is_failure()) {
+                    unsafe { move_caret_to_line(edit_handle, line - 1) };
+                }
+                show_error_message(edit_handle, &describe_send_error(&e, &target_name));
+            }
+        }
+        thread_running.store(false, Ordering::SeqCst);
+    });
+
+    data.sender_thread = Some(thread_handler);
+}
+
+pub fn editor_text(edit_handle: HWND) -> String {
+    unsafe {
+        let length = GetWindowTextLengthW(edit_handle) as usize;
+        let mut buffer = vec![0u16; length + 1];
+        let copied = GetWindowTextW(edit_handle, buffer.as_mut_ptr(), (length + 1) as i32);
+        String::from_utf16_lossy(&buffer[..copied as usize])
+    }
+}
+
+/// 选区的起止字符位置
+unsafe fn selection(edit_handle: HWND) -> (DWORD, DWORD) {
+    let mut start: DWORD = 0;
+    let mut end: DWORD = 0;
+    SendMessageW(
+        edit_handle,
+        EM_GETSEL as u32,
+        &mut start as *mut _ as WPARAM,
+        &mut end as *mut _ as LPARAM,
+    );
+    (start, end)
+}
+
+/// 光标所在行的下标 (从 0 开始)
+unsafe fn caret_line(edit_handle: HWND) -> usize {
+    let (start, _) = selection(edit_handle);
+    SendMessageW(edit_handle, EM_LINEFROMCHAR as u32, start as WPARAM, 0) as usize
+}
+
+/// 光标移到指定行首, 该行不存在时保持不动
+unsafe fn move_caret_to_line(edit_handle: HWND, line_index: usize) {
+    let line_start = SendMessageW(edit_handle, EM_LINEINDEX as u32, line_index as WPARAM, 0) as i32;
+    if line_start < 0 {
+        return;
+    }
+    SendMessageW(
+        edit_handle,
+        EM_SETSEL as u32,
+        line_start as WPARAM,
+        line_start as LPARAM,
+    );
+    SendMessageW(edit_handle, EM_SCROLLCARET as u32, 0, 0);
+}
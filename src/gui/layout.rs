@@ -0,0 +1,245 @@
+use super::*;
+
+// 以下尺寸均为 96 DPI 下的逻辑像素, 使用时按窗口 DPI 缩放
+const MARGIN: i32 = 10;
+const BUTTON_WIDTH: i32 = 100;
+const BUTTON_HEIGHT: i32 = 40;
+const BUTTON_PITCH: i32 = 60;
+const SPLITTER_HEIGHT: i32 = 10;
+const MIN_EDITOR_HEIGHT: i32 = 80;
+const MIN_MESSAGE_HEIGHT: i32 = 30;
+const EDITOR_FONT_HEIGHT: i32 = 16;
+const UI_FONT_HEIGHT: i32 = 15;
+pub const DEFAULT_WINDOW_SIZE: (i32, i32) = (800, 600);
+const MIN_WINDOW_SIZE: (i32, i32) = (400, 300);
+
+// 右侧上方竖排的按钮, 绑定按钮单独与消息框顶部对齐
+const TOP_BUTTONS: [i32; 4] = [IDC_BUTTON2, IDC_BUTTON3, IDC_BUTTON1, IDC_BUTTON_MULTI];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Layout {
+    pub editor: Rect,
+    pub splitter: Rect,
+    pub message: Rect,
+    /// 终端窗格, 连接终端后与编辑框左右平分; 未连接时宽度为 0
+    pub terminal: Rect,
+    /// 分段列表, 位于右侧按钮与绑定按钮之间
+    pub section_list: Rect,
+    pub buttons: Vec<(i32, Rect)>,
+}
+
+pub fn scale(value: i32, dpi: u32) -> i32 {
+    value * dpi as i32 / 96
+}
+
+fn unscale(value: i32, dpi: u32) -> i32 {
+    value * 96 / dpi.max(1) as i32
+}
+
+/// 根据客户区大小、DPI、消息框高度 (逻辑像素) 和是否显示终端计算各控件位置
+pub fn compute_layout(client_width: i32, client_height: i32, dpi: u32, message_height: i32, terminal: bool) -> Layout {
+    let margin = scale(MARGIN, dpi);
+    let button_width = scale(BUTTON_WIDTH, dpi);
+    let button_height = scale(BUTTON_HEIGHT, dpi);
+    let splitter_height = scale(SPLITTER_HEIGHT, dpi);
+
+    let left_width = (client_width - 3 * margin - button_width).max(0);
+    let available = (client_height - 2 * margin - splitter_height).max(0);
+    let message_height = scale(message_height, dpi)
+        .min(available - scale(MIN_EDITOR_HEIGHT, dpi))
+        .max(scale(MIN_MESSAGE_HEIGHT, dpi).min(available));
+    let editor_height = (available - message_height).max(0);
+
+    let editor_width = if terminal { ((left_width - margin) / 2).max(0) } else { left_width };
+    let editor = Rect { x: margin, y: margin, width: editor_width, height: editor_height };
+    let terminal = Rect {
+        x: margin * 2 + editor_width,
+        y: margin,
+        width: if terminal { (left_width - margin - editor_width).max(0) } else { 0 },
+        height: editor_height,
+    };
+    let splitter = Rect { x: margin, y: editor.y + editor_height, width: left_width, height: splitter_height };
+    let message = Rect { x: margin, y: splitter.y + splitter_height, width: left_width, height: message_height };
+
+    let button_x = margin * 2 + left_width;
+    let mut buttons: Vec<(i32, Rect)> = TOP_BUTTONS
+        .iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let y = margin + scale(BUTTON_PITCH, dpi) * i as i32;
+            (*id, Rect { x: button_x, y, width: button_width, height: button_height })
+        })
+        .collect();
+    buttons.push((IDC_BUTTON4, Rect { x: button_x, y: message.y, width: button_width, height: button_height }));
+
+    let list_y = margin + scale(BUTTON_PITCH, dpi) * TOP_BUTTONS.len() as i32;
+    let section_list = Rect {
+        x: button_x,
+        y: list_y,
+        width: button_width,
+        height: (message.y - margin - list_y).max(0),
+    };
+
+    Layout { editor, splitter, message, terminal, section_list, buttons }
+}
+
+/// 拖动分隔条时, 由鼠标纵坐标换算出新的消息框高度 (逻辑像素);
+/// 拖到客户区之外时限制在编辑框保留最小高度、消息框不为负的范围内
+pub fn message_height_from_drag(client_height: i32, dpi: u32, mouse_y: i32) -> i32 {
+    let margin = scale(MARGIN, dpi);
+    let splitter_height = scale(SPLITTER_HEIGHT, dpi);
+    let available = (client_height - 2 * margin - splitter_height).max(0);
+    let largest = unscale(available - scale(MIN_EDITOR_HEIGHT, dpi), dpi);
+    unscale(client_height - margin - mouse_y - splitter_height / 2, dpi)
+        .min(largest)
+        .max(MIN_MESSAGE_HEIGHT.min(unscale(available, dpi)))
+}
+
+/// user32 中的函数, 不存在时 (Windows 10 1607 之前没有按显示器 DPI 的函数) 返回空指针;
+/// 这些函数不能静态导入, 否则程序在旧系统上无法启动
+fn user32_function(name: &[u8]) -> FARPROC {
+    unsafe {
+        let module = GetModuleHandleW(w("user32.dll").as_ptr());
+        if module.is_null() {
+            return null_mut();
+        }
+        GetProcAddress(module, name.as_ptr() as *const i8)
+    }
+}
+
+/// 按显示器 DPI 缩放, 旧系统上退回按系统 DPI 缩放
+pub fn enable_dpi_awareness() {
+    let set_context = user32_function(b"SetProcessDpiAwarenessContext\0");
+    unsafe {
+        if !set_context.is_null() {
+            let set_context = std::mem::transmute::<FARPROC, extern "system" fn(DPI_AWARENESS_CONTEXT) -> BOOL>(set_context);
+            if set_context(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) != 0 {
+                return;
+            }
+        }
+        SetProcessDPIAware();
+    }
+}
+
+pub fn system_dpi() -> u32 {
+    let get_dpi = user32_function(b"GetDpiForSystem\0");
+    let dpi = unsafe {
+        if get_dpi.is_null() {
+            let dc = GetDC(null_mut());
+            let dpi = GetDeviceCaps(dc, LOGPIXELSY) as u32;
+            ReleaseDC(null_mut(), dc);
+            dpi
+        } else {
+            std::mem::transmute::<FARPROC, extern "system" fn() -> UINT>(get_dpi)()
+        }
+    };
+    if dpi == 0 { 96 } else { dpi }
+}
+
+/// 旧系统上各窗口都按系统 DPI 缩放
+pub fn window_dpi(hwnd: HWND) -> u32 {
+    let get_dpi = user32_function(b"GetDpiForWindow\0");
+    if get_dpi.is_null() {
+        return system_dpi();
+    }
+    let dpi = unsafe { std::mem::transmute::<FARPROC, extern "system" fn(HWND) -> UINT>(get_dpi)(hwnd) };
+    if dpi == 0 { 96 } else { dpi }
+}
+
+pub fn current_layout(hwnd: HWND, data: &WindowData) -> Layout {
+    let mut rect: RECT = unsafe { std::mem::zeroed() };
+    unsafe {
+        GetClientRect(hwnd, &mut rect);
+    }
+    compute_layout(
+        rect.right - rect.left,
+        rect.bottom - rect.top,
+        data.dpi,
+        data.config.layout.message_height,
+        data.terminal.is_some(),
+    )
+}
+
+/// 按当前窗口大小重新摆放所有控件
+pub fn apply_layout(hwnd: HWND, data: &WindowData) {
+    let layout = current_layout(hwnd, data);
+    let mut placements = vec![
+        (IDC_EDIT1, layout.editor),
+        (IDC_MSG_BOX, layout.message),
+        (IDC_SECTION_LIST, layout.section_list),
+        (IDC_TERMINAL, layout.terminal),
+    ];
+    placements.extend(layout.buttons.iter().copied());
+
+    unsafe {
+        let mut hdwp = BeginDeferWindowPos(placements.len() as i32);
+        for (id, rect) in placements {
+            let child = GetDlgItem(hwnd, id);
+            if child.is_null() || hdwp.is_null() {
+                continue;
+            }
+            hdwp = DeferWindowPos(
+                hdwp,
+                child,
+                null_mut(),
+                rect.x,
+                rect.y,
+                rect.width,
+                rect.height,
+                SWP_NOZORDER | SWP_NOACTIVATE,
+            );
+        }
+        if !hdwp.is_null() {
+            EndDeferWindowPos(hdwp);
+        }
+        InvalidateRect(hwnd, null_mut(), TRUE);
+    }
+}
+
+/// 按 DPI 重新创建字体并设置到各控件
+pub fn update_fonts(hwnd: HWND, data: &mut WindowData) {
+    unsafe {
+        for font in [data.editor_font.take(), data.ui_font.take()].into_iter().flatten() {
+            DeleteObject(font as _);
+        }
+        let editor_font = create_font(scale(EDITOR_FONT_HEIGHT, data.dpi), "Consolas\0");
+        let ui_font = create_font(scale(UI_FONT_HEIGHT, data.dpi), "Microsoft YaHei UI\0");
+
+        for id in [IDC_EDIT1, IDC_MSG_BOX] {
+            SendMessageW(GetDlgItem(hwnd, id), WM_SETFONT, editor_font as WPARAM, 1 as LPARAM);
+        }
+        for id in TOP_BUTTONS.iter().chain([&IDC_BUTTON4, &IDC_SECTION_LIST]) {
+            SendMessageW(GetDlgItem(hwnd, *id), WM_SETFONT, ui_font as WPARAM, 1 as LPARAM);
+        }
+        data.editor_font = Some(editor_font);
+        data.ui_font = Some(ui_font);
+    }
+    fit_terminal(data);
+}
+
+unsafe fn create_font(height: i32, face: &str) -> HFONT {
+    CreateFontW(
+        height, 0, 0, 0, FW_NORMAL, 0, 0, 0,
+        DEFAULT_CHARSET, OUT_DEFAULT_PRECIS, CLIP_DEFAULT_PRECIS,
+        DEFAULT_QUALITY, DEFAULT_PITCH | FF_DONTCARE,
+        w(face).as_ptr(),
+    )
+}
+
+pub fn min_window_size(dpi: u32) -> (i32, i32) {
+    (scale(MIN_WINDOW_SIZE.0, dpi), scale(MIN_WINDOW_SIZE.1, dpi))
+}
@@ -0,0 +1,146 @@
+use winapi::{
+    shared::{minwindef::*, windef::*, windowsx::{GET_X_LPARAM, GET_Y_LPARAM}, wtypesbase::CLSCTX_INPROC_SERVER},
+    um::{
+        combaseapi::*, libloaderapi::{GetModuleHandleW, GetProcAddress, LoadLibraryW}, objbase::COINIT_APARTMENTTHREADED,
+        shobjidl::*, shobjidl_core::*, wingdi::*,
+        winuser::*,
+    },
+};
+use std::{
+    ffi::OsString,  // Removed c_void
+    fs,
+    io::{self},
+    os::windows::prelude::OsStringExt,
+    ptr::null_mut,
+    sync::{Arc, atomic::Ordering},
+    thread,
+};
+
+mod consts;
+mod window_data;
+mod input;
+mod file_io;
+mod controls;
+mod utils;
+mod theme;
+mod layout;
+mod shortcuts;
+mod dialogs;
+mod terminal;
+
+use utils::*;
+use controls::*;
+use file_io::*;
+use input::*;
+use consts::*;
+use theme::*;
+use layout::*;
+use shortcuts::*;
+use dialogs::*;
+use terminal::*;
+use window_data::WindowData;
+use tty_sender_core::config::Config;
+use crate::i18n::*;
+
+fn get_hinstance() -> HINSTANCE {
+    unsafe { GetModuleHandleW(std::ptr::null_mut()) }
+}
+pub fn run() -> io::Result<()> {
+    enable_dpi_awareness();
+
+    let class_name_wide: Vec<u16> = CLASS_NAME.encode_utf16().chain(Some(0)).collect();
+
+    let wc = WNDCLASSW {
+        style: 0,
+        lpfnWndProc: Some(window_proc),
+        cbClsExtra: 0,
+        cbWndExtra: 0,
+        hInstance: get_hinstance(),
+        hIcon: std::ptr::null_mut(),
+        hCursor: unsafe { LoadCursorW(null_mut(), IDC_ARROW) },
+        hbrBackground: unsafe { GetSysColorBrush(COLOR_WINDOW + 1) },
+        lpszMenuName: std::ptr::null_mut(),
+        lpszClassName: class_name_wide.as_ptr(),
+    };
+
+    if unsafe { RegisterClassW(&wc) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let title: Vec<u16> = "Tty Sender\0".encode_utf16().collect();
+    let system_dpi = system_dpi();
+    let hwnd = unsafe {
+        CreateWindowExW(
+            0,
+            class_name_wide.as_ptr(),
+            title.as_ptr(),
+            WS_OVERLAPPEDWINDOW,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            scale(DEFAULT_WINDOW_SIZE.0, system_dpi),
+            scale(DEFAULT_WINDOW_SIZE.1, system_dpi),
+            null_mut(),
+            null_mut(),
+            get_hinstance(),
+            null_mut(),
+        )
+    };
+
+    if hwnd.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+
+    let config = Config::load();
+    set_language(Lang::from_setting(&config.language));
+    let data = Box::into_raw(Box::new(WindowData {
+        config,
+        ..WindowData::new()
+    }));
+    unsafe {
+        SetWindowLongPtrW(hwnd, GWLP_USERDATA, data as LPARAM);
+    }
+
+    if let Err(e) = create_controls(hwnd) {
+        unsafe {
+            DestroyWindow(hwnd);
+        }
+        return Err(e);
+    }
+
+    // WindowData 在 WM_DESTROY 中释放, 快捷键表和终端窗格句柄需先取出
+    let accel_table = unsafe { (*data).accel_table };
+    let terminal_handle = unsafe { (*data).terminal_handle };
+
+    unsafe {
+        ShowWindow(hwnd, SW_SHOW);
+        UpdateWindow(hwnd);
+    }
+
+    let mut msg = MSG {
+        hwnd: null_mut(),
+        message: 0,
+        wParam: 0,
+        lParam: 0,
+        pt: POINT { x: 0, y: 0 },
+        time: 0,
+    };
+
+    while unsafe { GetMessageW(&mut msg, null_mut(), 0, 0) } != 0 {
+        unsafe {
+            // 终端窗格中的按键 (Esc、Ctrl+R 等) 原样发给目标, 不作为快捷键
+            if !accel_table.is_null() && msg.hwnd != terminal_handle && TranslateAcceleratorW(hwnd, accel_table, &mut msg) != 0 {
+                continue;
+            }
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+
+    if !accel_table.is_null() {
+        unsafe {
+            DestroyAcceleratorTable(accel_table);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use super::*;
+use tty_sender_core::config::KeysConfig;
+
+const HOTKEY_SEND_LINE: i32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub key: u16,
+}
+
+impl KeyBinding {
+    /// 解析 "Ctrl+Enter"、"F5"、"Ctrl+Alt+S" 之类的按键描述, 不区分大小写
+    pub fn parse(text: &str) -> Option<KeyBinding> {
+        let mut binding = KeyBinding { ctrl: false, shift: false, alt: false, key: 0 };
+        for part in text.split('+').map(str::trim) {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => binding.ctrl = true,
+                "shift" => binding.shift = true,
+                "alt" => binding.alt = true,
+                name => {
+                    if binding.key != 0 {
+                        return None;
+                    }
+                    binding.key = virtual_key(name)?;
+                }
+            }
+        }
+        if binding.key == 0 { None } else { Some(binding) }
+    }
+
+    fn accel_flags(&self) -> BYTE {
+        let mut flags = FVIRTKEY;
+        if self.ctrl { flags |= FCONTROL; }
+        if self.shift { flags |= FSHIFT; }
+        if self.alt { flags |= FALT; }
+        flags
+    }
+
+    fn hotkey_modifiers(&self) -> UINT {
+        let mut modifiers = MOD_NOREPEAT as UINT;
+        if self.ctrl { modifiers |= MOD_CONTROL as UINT; }
+        if self.shift { modifiers |= MOD_SHIFT as UINT; }
+        if self.alt { modifiers |= MOD_ALT as UINT; }
+        modifiers
+    }
+}
+
+fn virtual_key(name: &str) -> Option<u16> {
+    let key = match name {
+        "enter" | "return" => VK_RETURN,
+        "esc" | "escape" => VK_ESCAPE,
+        "space" => VK_SPACE,
+        "tab" => VK_TAB,
+        "backspace" => VK_BACK,
+        "insert" => VK_INSERT,
+        "delete" | "del" => VK_DELETE,
+        "home" => VK_HOME,
+        "end" => VK_END,
+        "pageup" => VK_PRIOR,
+        "pagedown" => VK_NEXT,
+        "up" => VK_UP,
+        "down" => VK_DOWN,
+        "left" => VK_LEFT,
+        "right" => VK_RIGHT,
+        _ => {
+            let bytes = name.as_bytes();
+            if bytes.len() == 1 && bytes[0].is_ascii_alphanumeric() {
+                // 字母和数字的虚拟键码即其大写 ASCII 码
+                bytes[0].to_ascii_uppercase() as i32
+            } else if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<i32>().ok()) {
+                if !(1..=24).contains(&n) {
+                    return None;
+                }
+                VK_F1 + n - 1
+            } else {
+                return None;
+            }
+        }
+    };
+    Some(key as u16)
+}
+
+/// 根据配置创建快捷键表, 并注册全局热键 (如已配置)
+pub fn register_shortcuts(hwnd: HWND, keys: &KeysConfig) -> HACCEL {
+    let actions = [
+        (&keys.send_line, IDC_BUTTON1),
+        (&keys.multi_send, IDC_BUTTON_MULTI),
+        (&keys.send_selection, IDM_SEND_SELECTION),
+        (&keys.run_to_cursor, IDM_RUN_TO_CURSOR),
+        (&keys.run_section, IDM_RUN_SECTION),
+        (&keys.open, IDC_BUTTON2),
+        (&keys.save, IDC_BUTTON3),
+        (&keys.abort, IDM_ABORT),
+    ];
+
+    let mut table = Vec::new();
+    for (text, cmd) in actions {
+        if text.is_empty() {
+            continue;
+        }
+        match KeyBinding::parse(text) {
+            Some(binding) => table.push(ACCEL {
+                fVirt: binding.accel_flags(),
+                key: binding.key,
+                cmd: cmd as WORD,
+            }),
+            None => show_error_message(hwnd, &tr_fmt(Msg::InvalidShortcut, &[("key", text)])),
+        }
+    }
+
+    if !keys.global_send_line.is_empty() {
+        match KeyBinding::parse(&keys.global_send_line) {
+            Some(binding) => unsafe {
+                if RegisterHotKey(hwnd, HOTKEY_SEND_LINE, binding.hotkey_modifiers(), binding.key as UINT) == 0 {
+                    show_error_message(hwnd, &tr_fmt(Msg::HotkeyRegisterFailed, &[("key", &keys.global_send_line)]));
+                }
+            },
+            None => show_error_message(hwnd, &tr_fmt(Msg::InvalidShortcut, &[("key", &keys.global_send_line)])),
+        }
+    }
+
+    if table.is_empty() {
+        return null_mut();
+    }
+    unsafe { CreateAcceleratorTableW(table.as_mut_ptr(), table.len() as i32) }
+}
+
+pub fn unregister_shortcuts(hwnd: HWND) {
+    unsafe {
+        UnregisterHotKey(hwnd, HOTKEY_SEND_LINE);
+    }
+}
+
+pub fn is_send_line_hotkey(w_param: WPARAM) -> bool {
+    w_param as i32 == HOTKEY_SEND_LINE
+}
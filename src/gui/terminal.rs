@@ -0,0 +1,441 @@
+use super::*;
+use std::fmt;
+use tty_sender_core::capture::Capture;
+use tty_sender_core::keys::SpecialKey;
+use tty_sender_core::target::{OutputBuffer, SharedTarget, Target, TargetSpec};
+use tty_sender_core::terminal::{Attrs, Cell, Color, Terminal};
+use tty_sender_core::trigger::{TriggerEvent, TriggerRunner};
+
+const TERMINAL_CLASS_NAME: &str = "TtySenderTerminalClass";
+const POLL_TIMER: usize = 1;
+const POLL_INTERVAL_MS: u32 = 30;
+/// 鼠标滚轮每格翻看的行数
+const WHEEL_LINES: usize = 3;
+
+/// 终端窗格连接的目标、屏幕内容和输出记录
+pub struct TerminalSession {
+    /// 用户输入的目标名称或规则, 用于判断是否为受保护的目标
+    pub name: String,
+    pub target: SharedTarget,
+    output: OutputBuffer,
+    /// 已交给模拟器的输出位置
+    position: usize,
+    emulator: Terminal,
+    /// 向上翻看的行数, 0 表示跟随最新输出
+    scroll: usize,
+    /// 目标的输出已结束, 不再接受按键
+    pub closed: bool,
+    /// 配置了 [log] capture 时, 连接期间的输出都记录下来, 发送的命令也在其中标出
+    pub capture: Option<Capture>,
+    /// 目标配置中启用的触发器, 连接期间一直有效; 丢弃时停止
+    triggers: Option<TriggerRunner>,
+}
+
+impl fmt::Debug for TerminalSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TerminalSession").field("name", &self.name).field("closed", &self.closed).finish()
+    }
+}
+
+/// 创建终端窗格 (默认隐藏), 连接目标后显示
+pub fn create_terminal(hwnd: HWND) -> HWND {
+    unsafe {
+        let class_name = w(TERMINAL_CLASS_NAME);
+        let wc = WNDCLASSW {
+            style: CS_HREDRAW | CS_VREDRAW,
+            lpfnWndProc: Some(terminal_proc),
+            cbClsExtra: 0,
+            cbWndExtra: 0,
+            hInstance: get_hinstance(),
+            hIcon: null_mut(),
+            hCursor: LoadCursorW(null_mut(), IDC_IBEAM),
+            hbrBackground: null_mut(),
+            lpszMenuName: null_mut(),
+            lpszClassName: class_name.as_ptr(),
+        };
+        RegisterClassW(&wc);
+        CreateWindowExW(
+            WS_EX_CLIENTEDGE,
+            class_name.as_ptr(),
+            null_mut(),
+            WS_CHILD | WS_TABSTOP,
+            0, 0, 0, 0,
+            hwnd,
+            IDC_TERMINAL as isize as HMENU,
+            get_hinstance(),
+            null_mut(),
+        )
+    }
+}
+
+/// 询问目标并在终端窗格中连接; 之后发送的命令也发往该目标
+pub fn connect_terminal(hwnd: HWND, data: &mut WindowData) {
+    let config = data.config.clone();
+    let describe = |text: &str| match TargetSpec::parse(config.resolve_target(text.trim())) {
+        Ok(spec) => spec.to_string(),
+        Err(e) => describe_target_error(&e),
+    };
+    let title = tr(Msg::MenuTerminalConnect).trim_end_matches('.');
+    let rule = match prompt_text(hwnd, title, tr(Msg::TerminalConnectLabel), &config.terminal.target, &describe) {
+        Some(rule) => rule.trim().to_string(),
+        None => return,
+    };
+    let triggers = match data.config.triggers_for(&rule) {
+        Ok(triggers) => triggers,
+        Err(e) => {
+            show_error_message(hwnd, &describe_trigger_error(&e));
+            return;
+        }
+    };
+    let target = match TargetSpec::parse(data.config.resolve_target(&rule)).and_then(|spec| spec.open()) {
+        Ok(target) => target,
+        Err(e) => {
+            show_error_message(hwnd, &describe_target_error(&e));
+            return;
+        }
+    };
+    let output = match target.output() {
+        Some(output) => output,
+        None => {
+            show_error_message(hwnd, &tr_fmt(Msg::TerminalNoOutput, &[("target", &target.describe())]));
+            return;
+        }
+    };
+    disconnect_terminal(hwnd, data);
+
+    let capture = data.config.log.capture.then(|| {
+        let log = &data.config.log;
+        Capture::create(&log.capture_file(), &target.describe(), output.clone(), log.capture_options())
+            .map_err(|e| show_error_message(hwnd, &tr_fmt(Msg::CaptureFailed, &[("error", &e)])))
+            .ok()
+    });
+    let mut emulator = Terminal::new(80, 24);
+    emulator.set_scrollback_limit(data.config.terminal.scrollback);
+    let name = target.describe();
+    let target = SharedTarget::new(target);
+    let runner = (!triggers.is_empty()).then(|| {
+        // 后台线程中不能保存窗口句柄指针, 以整数传递
+        let owner = hwnd as isize;
+        TriggerRunner::start(triggers, output.clone(), target.clone(), move |event| match event {
+            TriggerEvent::Fired { name } => show_message(owner as HWND, &tr_fmt(Msg::TriggerFired, &[("name", &name)])),
+            TriggerEvent::Failed { name, error } => {
+                show_error_message(owner as HWND, &tr_fmt(Msg::TriggerFailed, &[("name", &name), ("error", &error)]))
+            }
+        })
+    });
+    data.terminal = Some(TerminalSession {
+        name: rule.clone(),
+        target,
+        output,
+        position: 0,
+        emulator,
+        scroll: 0,
+        closed: false,
+        capture: capture.flatten(),
+        triggers: runner,
+    });
+
+    data.config.terminal.target = rule;
+    if let Err(e) = data.config.save() {
+        show_error_message(hwnd, &tr_fmt(Msg::SaveConfigFailed, &[("error", &e)]));
+    }
+    unsafe {
+        ShowWindow(data.terminal_handle, SW_SHOW);
+        apply_layout(hwnd, data);
+        fit_terminal(data);
+        SetTimer(data.terminal_handle, POLL_TIMER, POLL_INTERVAL_MS, None);
+        SetFocus(data.terminal_handle);
+    }
+    show_message(hwnd, &tr_fmt(Msg::TerminalConnected, &[("target", &name)]));
+}
+
+pub fn disconnect_terminal(hwnd: HWND, data: &mut WindowData) {
+    let session = match data.terminal.take() {
+        Some(session) => session,
+        None => return,
+    };
+    // 只通知触发器线程退出, 不等待: 它可能正向本窗口显示消息
+    drop(session.triggers);
+    if let Some(Err(e)) = session.capture.as_ref().map(Capture::finish) {
+        show_error_message(hwnd, &tr_fmt(Msg::CaptureFailed, &[("error", &e)]));
+    }
+    unsafe {
+        KillTimer(data.terminal_handle, POLL_TIMER);
+        ShowWindow(data.terminal_handle, SW_HIDE);
+    }
+    apply_layout(hwnd, data);
+    show_message(hwnd, tr(Msg::TerminalDisconnected));
+}
+
+/// 按窗格大小和字体调整模拟器的行列数
+pub fn fit_terminal(data: &mut WindowData) {
+    let (cell_width, cell_height) = unsafe { cell_size(data.terminal_handle, data.editor_font) };
+    if let Some(session) = &mut data.terminal {
+        let mut rect: RECT = unsafe { std::mem::zeroed() };
+        unsafe {
+            GetClientRect(data.terminal_handle, &mut rect);
+        }
+        let cols = (rect.right / cell_width).max(1) as usize;
+        let rows = (rect.bottom / cell_height).max(1) as usize;
+        let screen = session.emulator.screen();
+        if (cols, rows) != (screen.cols(), screen.rows()) {
+            session.emulator.resize(cols, rows);
+            unsafe {
+                InvalidateRect(data.terminal_handle, null_mut(), FALSE);
+            }
+        }
+    }
+}
+
+/// 等宽字体中一个字符格的大小
+unsafe fn cell_size(hwnd: HWND, font: Option<HFONT>) -> (i32, i32) {
+    let hdc = GetDC(hwnd);
+    let old_font = font.map(|font| SelectObject(hdc, font as _));
+    let mut metrics: TEXTMETRICW = std::mem::zeroed();
+    GetTextMetricsW(hdc, &mut metrics);
+    if let Some(old_font) = old_font {
+        SelectObject(hdc, old_font);
+    }
+    ReleaseDC(hwnd, hdc);
+    (metrics.tmAveCharWidth.max(1), metrics.tmHeight.max(1))
+}
+
+/// 把新的输出交给模拟器, 并回复模拟器产生的应答 (如光标位置报告)
+fn poll_output(hwnd: HWND, data: &mut WindowData) {
+    let parent = unsafe { GetParent(hwnd) };
+    let session = match &mut data.terminal {
+        Some(session) if !session.closed => session,
+        _ => return,
+    };
+    let (text, end) = session.output.read_since(session.position);
+    if !text.is_empty() {
+        session.position = end;
+        session.emulator.feed(text.as_bytes());
+        let responses = session.emulator.take_responses();
+        if !responses.is_empty() {
+            let _ = session.target.send_str(&String::from_utf8_lossy(&responses));
+        }
+        unsafe {
+            InvalidateRect(hwnd, null_mut(), FALSE);
+        }
+    } else if session.output.is_closed() {
+        session.closed = true;
+        unsafe {
+            KillTimer(hwnd, POLL_TIMER);
+        }
+        show_error_message(parent, &tr_fmt(Msg::TerminalClosed, &[("target", &session.target.describe())]));
+    }
+}
+
+/// 把按键发给目标, 同时回到最新输出
+fn send_keys(hwnd: HWND, data: &mut WindowData, text: &str) {
+    let parent = unsafe { GetParent(hwnd) };
+    let session = match &mut data.terminal {
+        Some(session) if !session.closed => session,
+        _ => return,
+    };
+    if session.scroll != 0 {
+        session.scroll = 0;
+        unsafe {
+            InvalidateRect(hwnd, null_mut(), FALSE);
+        }
+    }
+    if let Err(e) = session.target.send_str(text) {
+        show_error_message(parent, &e.to_string());
+    }
+}
+
+fn special_key(vk: i32) -> Option<SpecialKey> {
+    let key = match vk {
+        VK_UP => SpecialKey::Up,
+        VK_DOWN => SpecialKey::Down,
+        VK_LEFT => SpecialKey::Left,
+        VK_RIGHT => SpecialKey::Right,
+        VK_HOME => SpecialKey::Home,
+        VK_END => SpecialKey::End,
+        VK_INSERT => SpecialKey::Insert,
+        VK_DELETE => SpecialKey::Delete,
+        VK_PRIOR => SpecialKey::PageUp,
+        VK_NEXT => SpecialKey::PageDown,
+        VK_F1..=VK_F12 => SpecialKey::F((vk - VK_F1 + 1) as u8),
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn scroll_by(hwnd: HWND, data: &mut WindowData, lines: isize) {
+    if let Some(session) = &mut data.terminal {
+        let limit = session.emulator.screen().scrollback_len();
+        session.scroll = session.scroll.saturating_add_signed(lines).min(limit);
+        unsafe {
+            InvalidateRect(hwnd, null_mut(), FALSE);
+        }
+    }
+}
+
+fn color_ref(color: Color, default: COLORREF) -> COLORREF {
+    color.rgb().map_or(default, |(r, g, b)| RGB(r, g, b))
+}
+
+/// 一段属性相同的字符的前景色和背景色; 粗体的基本色用亮色显示
+fn run_colors(attrs: &Attrs, theme: &Theme) -> (COLORREF, COLORREF) {
+    let fg = match attrs.fg {
+        Color::Indexed(index @ 0..=7) if attrs.bold => Color::Indexed(index + 8),
+        other => other,
+    };
+    let fg = color_ref(fg, theme.editor_text);
+    let bg = color_ref(attrs.bg, theme.editor_background);
+    if attrs.reverse { (bg, fg) } else { (fg, bg) }
+}
+
+/// 绘制一行, 相同属性的字符一次画出, 每个字符按格宽定位
+unsafe fn draw_line(hdc: HDC, cells: &[Cell], y: i32, cell_width: i32, cell_height: i32, theme: &Theme) {
+    let mut start = 0;
+    while start < cells.len() {
+        let attrs = cells[start].attrs;
+        let end = cells[start..].iter().position(|cell| cell.attrs != attrs).map_or(cells.len(), |n| start + n);
+        let mut text: Vec<u16> = Vec::new();
+        let mut advances: Vec<i32> = Vec::new();
+        for (i, cell) in cells[start..end].iter().enumerate() {
+            if cell.ch == '\0' {
+                continue;
+            }
+            // 宽字符连同其后的占位格一起占两格
+            let width = if cells.get(start + i + 1).is_some_and(|next| next.ch == '\0') { 2 } else { 1 };
+            let mut units = [0u16; 2];
+            for (n, unit) in cell.ch.encode_utf16(&mut units).iter().enumerate() {
+                text.push(*unit);
+                advances.push(if n == 0 { cell_width * width } else { 0 });
+            }
+        }
+        let (fg, bg) = run_colors(&attrs, theme);
+        SetTextColor(hdc, fg);
+        SetBkColor(hdc, bg);
+        let rect = RECT {
+            left: start as i32 * cell_width,
+            top: y,
+            right: end as i32 * cell_width,
+            bottom: y + cell_height,
+        };
+        ExtTextOutW(hdc, rect.left, y, ETO_OPAQUE, &rect, text.as_ptr(), text.len() as UINT, advances.as_ptr());
+        if attrs.underline {
+            let line = RECT { top: rect.bottom - 1, ..rect };
+            let brush = CreateSolidBrush(fg);
+            FillRect(hdc, &line, brush);
+            DeleteObject(brush as _);
+        }
+        start = end;
+    }
+}
+
+/// 先画到内存位图再一次复制到窗口, 避免闪烁
+unsafe fn paint(hwnd: HWND, data: &WindowData) {
+    let mut ps: PAINTSTRUCT = std::mem::zeroed();
+    let hdc = BeginPaint(hwnd, &mut ps);
+    let mut rect: RECT = std::mem::zeroed();
+    GetClientRect(hwnd, &mut rect);
+    let memory = CreateCompatibleDC(hdc);
+    let bitmap = CreateCompatibleBitmap(hdc, rect.right, rect.bottom);
+    let old_bitmap = SelectObject(memory, bitmap as _);
+    let old_font = data.editor_font.map(|font| SelectObject(memory, font as _));
+
+    let theme = current_theme();
+    let background = CreateSolidBrush(theme.editor_background);
+    FillRect(memory, &rect, background);
+    DeleteObject(background as _);
+
+    if let Some(session) = &data.terminal {
+        let (cell_width, cell_height) = cell_size(hwnd, data.editor_font);
+        let screen = session.emulator.screen();
+        for row in 0..screen.rows() {
+            let cells = screen.view_line(session.scroll, row);
+            draw_line(memory, cells, row as i32 * cell_height, cell_width, cell_height, &theme);
+        }
+        // 有焦点且未翻看历史时显示光标
+        if session.scroll == 0 && screen.cursor_visible() && GetFocus() == hwnd {
+            let (row, col) = screen.cursor();
+            PatBlt(memory, col as i32 * cell_width, row as i32 * cell_height, cell_width, cell_height, DSTINVERT);
+        }
+    }
+
+    BitBlt(hdc, 0, 0, rect.right, rect.bottom, memory, 0, 0, SRCCOPY);
+    if let Some(old_font) = old_font {
+        SelectObject(memory, old_font);
+    }
+    SelectObject(memory, old_bitmap);
+    DeleteObject(bitmap as _);
+    DeleteDC(memory);
+    EndPaint(hwnd, &ps);
+}
+
+unsafe extern "system" fn terminal_proc(hwnd: HWND, u_msg: UINT, w_param: WPARAM, l_param: LPARAM) -> LRESULT {
+    let data_ptr = GetWindowLongPtrW(GetParent(hwnd), GWLP_USERDATA) as *mut WindowData;
+    if data_ptr.is_null() {
+        return DefWindowProcW(hwnd, u_msg, w_param, l_param);
+    }
+    let data = &mut *data_ptr;
+    match u_msg {
+        WM_PAINT => {
+            paint(hwnd, data);
+            0
+        }
+        WM_ERASEBKGND => 1,
+        WM_SIZE => {
+            fit_terminal(data);
+            0
+        }
+        WM_TIMER => {
+            poll_output(hwnd, data);
+            0
+        }
+        WM_LBUTTONDOWN => {
+            SetFocus(hwnd);
+            0
+        }
+        WM_SETFOCUS | WM_KILLFOCUS => {
+            InvalidateRect(hwnd, null_mut(), FALSE);
+            0
+        }
+        WM_GETDLGCODE => (DLGC_WANTALLKEYS | DLGC_WANTARROWS | DLGC_WANTCHARS | DLGC_WANTTAB) as LRESULT,
+        WM_MOUSEWHEEL => {
+            let notches = GET_WHEEL_DELTA_WPARAM(w_param) as isize / WHEEL_DELTA as isize;
+            scroll_by(hwnd, data, notches * WHEEL_LINES as isize);
+            0
+        }
+        WM_KEYDOWN => {
+            let vk = w_param as i32;
+            let shift = GetKeyState(VK_SHIFT) < 0;
+            let rows = data.terminal.as_ref().map_or(1, |session| session.emulator.screen().rows()) as isize;
+            match (vk, shift) {
+                // Shift+PgUp/PgDn 翻看历史, 不发给目标
+                (VK_PRIOR, true) => scroll_by(hwnd, data, rows),
+                (VK_NEXT, true) => scroll_by(hwnd, data, -rows),
+                _ => {
+                    let bytes = match (special_key(vk), &data.terminal) {
+                        (Some(key), Some(session)) => session.emulator.key_bytes(key),
+                        _ => return DefWindowProcW(hwnd, u_msg, w_param, l_param),
+                    };
+                    send_keys(hwnd, data, &String::from_utf8_lossy(&bytes));
+                }
+            }
+            0
+        }
+        WM_CHAR => {
+            let text = match w_param as u32 {
+                // 退格按终端习惯发送 DEL
+                0x08 => "\x7f".to_string(),
+                unit => match char::from_u32(unit) {
+                    Some(c) => c.to_string(),
+                    None => return 0,
+                },
+            };
+            send_keys(hwnd, data, &text);
+            0
+        }
+        WM_DESTROY => {
+            KillTimer(hwnd, POLL_TIMER);
+            0
+        }
+        _ => DefWindowProcW(hwnd, u_msg, w_param, l_param),
+    }
+}
@@ -0,0 +1,165 @@
+use super::*;
+use tty_sender_core::config::{CustomColors, ThemeConfig};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    // 消息框可能在发送线程中写入, 颜色从这里取
+    pub static ref CURRENT_THEME: Mutex<Theme> = Mutex::new(Theme::dark());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeKind {
+    Dark,
+    Light,
+    HighContrast,
+    Custom,
+}
+
+impl ThemeKind {
+    pub fn from_name(name: &str) -> ThemeKind {
+        match name {
+            "light" => ThemeKind::Light,
+            "high-contrast" => ThemeKind::HighContrast,
+            "custom" => ThemeKind::Custom,
+            _ => ThemeKind::Dark,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ThemeKind::Dark => "dark",
+            ThemeKind::Light => "light",
+            ThemeKind::HighContrast => "high-contrast",
+            ThemeKind::Custom => "custom",
+        }
+    }
+
+    pub fn menu_id(self) -> i32 {
+        match self {
+            ThemeKind::Dark => IDM_THEME_DARK,
+            ThemeKind::Light => IDM_THEME_LIGHT,
+            ThemeKind::HighContrast => IDM_THEME_HIGH_CONTRAST,
+            ThemeKind::Custom => IDM_THEME_CUSTOM,
+        }
+    }
+
+    pub fn from_menu_id(id: i32) -> Option<ThemeKind> {
+        match id {
+            IDM_THEME_DARK => Some(ThemeKind::Dark),
+            IDM_THEME_LIGHT => Some(ThemeKind::Light),
+            IDM_THEME_HIGH_CONTRAST => Some(ThemeKind::HighContrast),
+            IDM_THEME_CUSTOM => Some(ThemeKind::Custom),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub window_background: COLORREF,
+    pub editor_background: COLORREF,
+    pub editor_text: COLORREF,
+    pub message_background: COLORREF,
+    pub message_info: COLORREF,
+    pub message_error: COLORREF,
+    pub button_background: COLORREF,
+    pub button_pressed: COLORREF,
+    pub button_text: COLORREF,
+    pub button_border: COLORREF,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            window_background: RGB(45, 45, 45),
+            editor_background: RGB(64, 64, 64),
+            editor_text: RGB(255, 255, 255),
+            message_background: RGB(64, 64, 64),
+            message_info: RGB(0, 255, 0),
+            message_error: RGB(255, 80, 80),
+            button_background: RGB(80, 80, 80),
+            button_pressed: RGB(110, 110, 110),
+            button_text: RGB(255, 255, 255),
+            button_border: RGB(128, 128, 128),
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            window_background: RGB(240, 240, 240),
+            editor_background: RGB(255, 255, 255),
+            editor_text: RGB(0, 0, 0),
+            message_background: RGB(255, 255, 255),
+            message_info: RGB(0, 128, 0),
+            message_error: RGB(200, 0, 0),
+            button_background: RGB(225, 225, 225),
+            button_pressed: RGB(200, 200, 200),
+            button_text: RGB(0, 0, 0),
+            button_border: RGB(160, 160, 160),
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Self {
+            window_background: RGB(0, 0, 0),
+            editor_background: RGB(0, 0, 0),
+            editor_text: RGB(255, 255, 255),
+            message_background: RGB(0, 0, 0),
+            message_info: RGB(0, 255, 255),
+            message_error: RGB(255, 255, 0),
+            button_background: RGB(0, 0, 0),
+            button_pressed: RGB(0, 0, 128),
+            button_text: RGB(255, 255, 255),
+            button_border: RGB(255, 255, 255),
+        }
+    }
+
+    /// 以深色主题为底, 用配置中填写的颜色覆盖
+    pub fn custom(colors: &CustomColors) -> Self {
+        let base = Self::dark();
+        let pick = |value: &Option<String>, fallback: COLORREF| {
+            value.as_deref().and_then(parse_color).unwrap_or(fallback)
+        };
+        Self {
+            window_background: pick(&colors.window_background, base.window_background),
+            editor_background: pick(&colors.editor_background, base.editor_background),
+            editor_text: pick(&colors.editor_text, base.editor_text),
+            message_background: pick(&colors.message_background, base.message_background),
+            message_info: pick(&colors.message_info, base.message_info),
+            message_error: pick(&colors.message_error, base.message_error),
+            button_background: pick(&colors.button_background, base.button_background),
+            button_pressed: pick(&colors.button_pressed, base.button_pressed),
+            button_text: pick(&colors.button_text, base.button_text),
+            button_border: pick(&colors.button_border, base.button_border),
+        }
+    }
+
+    pub fn from_kind(kind: ThemeKind, config: &ThemeConfig) -> Self {
+        match kind {
+            ThemeKind::Dark => Self::dark(),
+            ThemeKind::Light => Self::light(),
+            ThemeKind::HighContrast => Self::high_contrast(),
+            ThemeKind::Custom => Self::custom(&config.custom),
+        }
+    }
+
+    pub fn from_config(config: &ThemeConfig) -> Self {
+        Self::from_kind(ThemeKind::from_name(&config.name), config)
+    }
+}
+
+pub fn current_theme() -> Theme {
+    *CURRENT_THEME.lock().unwrap()
+}
+
+/// 解析 "#RRGGBB" 格式颜色
+pub fn parse_color(text: &str) -> Option<COLORREF> {
+    let hex = text.trim().strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some(RGB((value >> 16) as u8, (value >> 8) as u8, value as u8))
+}
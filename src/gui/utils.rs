@@ -0,0 +1,110 @@
+use super::*;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+const MAX_MESSAGES: usize = 500;
+
+lazy_static! {
+    // 已显示的消息, 切换主题时按新配色重新绘制
+    static ref MESSAGE_LOG: Mutex<Vec<(MessageKind, String)>> = Mutex::new(Vec::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Info,
+    Error,
+}
+
+#[repr(C)]
+struct CharFormatW {
+    cb_size: UINT,
+    dw_mask: DWORD,
+    dw_effects: DWORD,
+    y_height: i32,
+    y_offset: i32,
+    cr_text_color: COLORREF,
+    b_char_set: BYTE,
+    b_pitch_and_family: BYTE,
+    sz_face_name: [u16; 32],
+}
+
+pub fn w(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+pub fn show_message(hwnd: HWND, msg: &str) {
+    show_message_with_kind(hwnd, msg, MessageKind::Info);
+}
+
+
+pub fn show_error_message(hwnd: HWND, msg: &str) {
+    show_message_with_kind(hwnd, msg, MessageKind::Error);
+}
+
+fn show_message_with_kind(hwnd: HWND, msg: &str, kind: MessageKind) {
+    {
+        let mut log = MESSAGE_LOG.lock().unwrap();
+        log.push((kind, msg.to_string()));
+        if log.len() > MAX_MESSAGES {
+            log.remove(0);
+        }
+    }
+    unsafe {
+        let msg_handle = message_box_handle(hwnd);
+        if !msg_handle.is_null() {
+            append_colored(msg_handle, msg, kind, &current_theme());
+        }
+    }
+}
+
+/// 按当前主题重新绘制所有历史消息
+pub fn redraw_messages(hwnd: HWND) {
+    unsafe {
+        let msg_handle = message_box_handle(hwnd);
+        if msg_handle.is_null() {
+            return;
+        }
+        let theme = current_theme();
+        SendMessageW(msg_handle, EM_SETBKGNDCOLOR, 0, theme.message_background as LPARAM);
+        SetWindowTextW(msg_handle, w("").as_ptr());
+        for (kind, msg) in MESSAGE_LOG.lock().unwrap().iter() {
+            append_colored(msg_handle, msg, *kind, &theme);
+        }
+    }
+}
+
+// 调用方传入的可能是任意子控件, 统一从顶层窗口查找消息框
+unsafe fn message_box_handle(hwnd: HWND) -> HWND {
+    if hwnd.is_null() {
+        return null_mut();
+    }
+    let root = GetAncestor(hwnd, GA_ROOT);
+    GetDlgItem(if root.is_null() { hwnd } else { root }, IDC_MSG_BOX)
+}
+
+unsafe fn append_colored(msg_handle: HWND, msg: &str, kind: MessageKind, theme: &Theme) {
+    let color = match kind {
+        MessageKind::Info => theme.message_info,
+        MessageKind::Error => theme.message_error,
+    };
+
+    // 光标移到末尾, 先设置选区颜色再插入文本
+    SendMessageW(msg_handle, EM_SETSEL as u32, usize::MAX, -1);
+    let mut format = CharFormatW {
+        cb_size: std::mem::size_of::<CharFormatW>() as UINT,
+        dw_mask: CFM_COLOR,
+        dw_effects: 0,
+        y_height: 0,
+        y_offset: 0,
+        cr_text_color: color,
+        b_char_set: 0,
+        b_pitch_and_family: 0,
+        sz_face_name: [0; 32],
+    };
+    SendMessageW(msg_handle, EM_SETCHARFORMAT, SCF_SELECTION, &mut format as *mut _ as LPARAM);
+
+    let wide_msg = w(&format!("{}\r\n", msg));
+    SendMessageW(msg_handle, EM_REPLACESEL as u32, 0, wide_msg.as_ptr() as LPARAM);
+    SendMessageW(msg_handle, WM_VSCROLL, SB_BOTTOM as WPARAM, 0);
+}
@@ -2,7 +2,9 @@ use std::path::PathBuf;
 use std::ptr::null_mut;
 use std::sync::{Arc, atomic::{AtomicBool}};
 use winapi::shared::windef::*;
-use winapi::shared::minwindef::*;
+use tty_sender_core::config::Config;
+use tty_sender_core::script::Section;
+use super::terminal::TerminalSession;
 
 #[derive(Debug)]
 pub struct WindowData {
@@ -13,15 +15,24 @@ pub struct WindowData {
     pub file_path: PathBuf,
     pub com_initialized: bool,
     pub target_hwnd: HWND,
-    pub attached_thread_id: DWORD,
-    pub is_attached: bool,
     pub background_brush: Option<HBRUSH>,
-    pub cursor_pos: i32,
-    pub cursor_visible: bool,
+    pub window_brush: Option<HBRUSH>,
+    pub editor_font: Option<HFONT>,
+    pub ui_font: Option<HFONT>,
+    pub dpi: u32,
+    pub splitter_dragging: bool,
+    pub accel_table: HACCEL,
     pub multi_send_button_handle: HWND,
+    /// 右侧的分段列表, 与 sections 一一对应
+    pub section_list_handle: HWND,
+    pub sections: Vec<Section>,
+    pub terminal_handle: HWND,
+    /// 终端窗格连接的目标; 连接后发送的命令也发往该目标
+    pub terminal: Option<TerminalSession>,
     pub sender_thread: Option<std::thread::JoinHandle<()>>,
     pub abort_flag: Arc<AtomicBool>,
     pub thread_running: Arc<AtomicBool>,
+    pub config: Config,
 }
 
 impl WindowData {
@@ -34,15 +45,22 @@ impl WindowData {
             file_path: PathBuf::new(),
             com_initialized: false,
             target_hwnd: null_mut(),
-            attached_thread_id: 0,
-            is_attached: false,
             background_brush: None,
-            cursor_pos: 0,
-            cursor_visible: false,
+            window_brush: None,
+            editor_font: None,
+            ui_font: None,
+            dpi: 96,
+            splitter_dragging: false,
+            accel_table: null_mut(),
             multi_send_button_handle: null_mut(),
+            section_list_handle: null_mut(),
+            sections: Vec::new(),
+            terminal_handle: null_mut(),
+            terminal: None,
             sender_thread: None,
             abort_flag: Arc::new(AtomicBool::new(false)),
             thread_running: Arc::new(AtomicBool::new(false)),
+            config: Config::default(),
         }
     }
 }
@@ -0,0 +1,744 @@
+use tty_sender_core::directive::{DirectiveError, DirectiveErrorKind};
+use tty_sender_core::engine::{ScriptError, SendError, MAX_JUMPS_BACK};
+use tty_sender_core::include::{IncludeError, IncludeErrorKind, MAX_INCLUDE_DEPTH};
+use tty_sender_core::macros::MAX_CALL_DEPTH;
+use tty_sender_core::results::TestRun;
+use tty_sender_core::script::RangeError;
+use tty_sender_core::target::TargetError;
+use tty_sender_core::trigger::TriggerError;
+use std::fmt::Display;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref CURRENT_LANG: Mutex<Lang> = Mutex::new(Lang::EnUs);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    ZhCn,
+    EnUs,
+}
+
+impl Lang {
+    /// 配置值 "zh-CN" / "en-US", 其他值 (含 "auto") 跟随系统界面语言
+    pub fn from_setting(setting: &str) -> Lang {
+        match setting {
+            "zh-CN" => Lang::ZhCn,
+            "en-US" => Lang::EnUs,
+            _ => Lang::system(),
+        }
+    }
+
+    #[cfg(windows)]
+    pub fn system() -> Lang {
+        const LANG_CHINESE: u16 = 0x04;
+        let lang_id = unsafe { winapi::um::winnls::GetUserDefaultUILanguage() };
+        if lang_id & 0x3ff == LANG_CHINESE { Lang::ZhCn } else { Lang::EnUs }
+    }
+
+    #[cfg(not(windows))]
+    pub fn system() -> Lang {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        if locale.starts_with("zh") { Lang::ZhCn } else { Lang::EnUs }
+    }
+}
+
+/// 界面上出现的所有文本, 译文见 zh_cn / en_us
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msg {
+    ButtonOpen,
+    ButtonSave,
+    ButtonSend,
+    ButtonMultiSend,
+    ButtonBind,
+    MenuTheme,
+    MenuThemeDark,
+    MenuThemeLight,
+    MenuThemeHighContrast,
+    MenuThemeCustom,
+    MenuLanguage,
+    MenuLanguageAuto,
+    MenuSend,
+    MenuSendLine,
+    MenuMultiSend,
+    MenuSendSelection,
+    MenuRunToCursor,
+    MenuSendRange,
+    MenuSendCount,
+    MenuAbort,
+    MenuRunSection,
+    NoSectionSelected,
+    RunningSection,
+    MenuStopAt,
+    MenuStopBlock,
+    MenuStopBlank,
+    MenuStopEof,
+    MenuCheckStatus,
+    MenuTerminal,
+    MenuTerminalConnect,
+    MenuTerminalDisconnect,
+    TerminalConnectLabel,
+    TerminalNoOutput,
+    TerminalConnected,
+    TerminalDisconnected,
+    TerminalClosed,
+    DialogOk,
+    DialogCancel,
+    CreateWindowFailed,
+    SubclassFailed,
+    BindSuccess,
+    WindowDataMissing,
+    InvalidWindow,
+    SaveConfigFailed,
+    NoTargetWindow,
+    SenderBusy,
+    TargetWindowGone,
+    SendAborted,
+    NoSelection,
+    SendPreview,
+    SendRangeLabel,
+    SendCountLabel,
+    InvalidRangeInput,
+    OpenDialogTitle,
+    SaveDialogTitle,
+    CreateOpenDialogFailed,
+    ShowOpenDialogFailed,
+    CreateSaveDialogFailed,
+    ShowSaveDialogFailed,
+    InvalidShortcut,
+    HotkeyRegisterFailed,
+    TargetInvalidRule,
+    TargetUnknownKind,
+    TargetInvalidOption,
+    TargetUnsupported,
+    TargetNotFound,
+    TargetOpenFailed,
+    SendIoFailed,
+    SendPromptTimeout,
+    SendOutputClosed,
+    SendNoOutput,
+    SendCaptureTimeout,
+    SendCaptured,
+    SendStatusTimeout,
+    SendExitStatus,
+    SendErrorHandler,
+    SendErrorGoto,
+    SendIteration,
+    SendIterationWhile,
+    SendExpectFailed,
+    SendUnexpectedOutput,
+    SendTooManyJumps,
+    RunSummary,
+    CliUsage,
+    CliUnknownCommand,
+    CliUnknownOption,
+    CliMissingValue,
+    CliInvalidValue,
+    CliMissingOption,
+    CliReadFileFailed,
+    CliConflictingOptions,
+    InvalidGuardPattern,
+    GuardHits,
+    SendUnconfirmed,
+    GuardDialogTitle,
+    GuardConfirm,
+    GuardHitLine,
+    GuardHitCount,
+    GuardProtectedLabel,
+    GuardCancelled,
+    CliGuardAsk,
+    CliGuardTypeName,
+    CliGuardRefused,
+    CliGuardProtectedRefused,
+    EditorLine,
+    IncludeMissingPath,
+    IncludeNoBaseFile,
+    IncludeReadFailed,
+    IncludeCycle,
+    IncludeTooDeep,
+    IncludeUnknownSection,
+    DirectiveUnknown,
+    DirectiveUsage,
+    DirectiveInvalidPattern,
+    DirectiveInvalidTimeout,
+    DirectiveUnknownSection,
+    DirectiveUnmatched,
+    DirectiveUnclosed,
+    DirectiveUnknownLabel,
+    DirectiveDuplicateLabel,
+    DirectiveUnknownMacro,
+    DirectiveMacroArguments,
+    DirectiveDuplicateMacro,
+    DirectiveMacroRecursion,
+    DirectiveNestedTooDeep,
+    UnknownSection,
+    InvalidRange,
+    CliInvalidPrompt,
+    TranscriptFailed,
+    CaptureFailed,
+    CliCaptureUnsupported,
+    TriggerUnknown,
+    TriggerInvalidPattern,
+    TriggersUnsupported,
+    TriggerFired,
+    TriggerFailed,
+    CliSendDone,
+    CliReportFailed,
+    CliSendAborted,
+    CliDryRunSummary,
+    CliConfiguredTargets,
+    CliDetectedTargets,
+    CliNoTargets,
+}
+
+fn zh_cn(msg: Msg) -> &'static str {
+    match msg {
+        Msg::ButtonOpen => "打开",
+        Msg::ButtonSave => "保存",
+        Msg::ButtonSend => "发送",
+        Msg::ButtonMultiSend => "多发",
+        Msg::ButtonBind => "绑定",
+        Msg::MenuTheme => "主题",
+        Msg::MenuThemeDark => "深色",
+        Msg::MenuThemeLight => "浅色",
+        Msg::MenuThemeHighContrast => "高对比度",
+        Msg::MenuThemeCustom => "自定义",
+        Msg::MenuLanguage => "语言",
+        Msg::MenuLanguageAuto => "跟随系统",
+        Msg::MenuSend => "发送",
+        Msg::MenuSendLine => "发送当前行",
+        Msg::MenuMultiSend => "从光标处多行发送",
+        Msg::MenuSendSelection => "发送选中的行",
+        Msg::MenuRunToCursor => "从开头运行到光标处",
+        Msg::MenuSendRange => "发送指定行...",
+        Msg::MenuSendCount => "从光标处发送 N 行...",
+        Msg::MenuAbort => "中止发送",
+        Msg::MenuRunSection => "运行选中的分段",
+        Msg::NoSectionSelected => "请先在右侧列表中选择一个分段",
+        Msg::RunningSection => "运行分段 [{name}]",
+        Msg::MenuStopAt => "多行发送停止于",
+        Msg::MenuStopBlock => "下一个分段标记 (--- 步骤 ---)",
+        Msg::MenuStopBlank => "空行",
+        Msg::MenuStopEof => "文件末尾",
+        Msg::MenuCheckStatus => "检查退出状态",
+        Msg::MenuTerminal => "终端",
+        Msg::MenuTerminalConnect => "连接目标...",
+        Msg::MenuTerminalDisconnect => "断开",
+        Msg::TerminalConnectLabel => "目标名称或规则 (如 serial:COM3?baud=115200):",
+        Msg::TerminalNoOutput => "无法读取目标 {target} 的输出, 不能在终端中显示",
+        Msg::TerminalConnected => "终端已连接到 {target}, 发送的命令也将发往该目标",
+        Msg::TerminalDisconnected => "终端已断开, 发送的命令将发往绑定的窗口",
+        Msg::TerminalClosed => "{target} 的连接已结束",
+        Msg::DialogOk => "确定",
+        Msg::DialogCancel => "取消",
+        Msg::CreateWindowFailed => "创建控件失败",
+        Msg::SubclassFailed => "设置按钮窗口过程失败",
+        Msg::BindSuccess => "新窗口绑定成功: {hwnd}",
+        Msg::WindowDataMissing => "未找到窗口数据",
+        Msg::InvalidWindow => "无效的窗口",
+        Msg::SaveConfigFailed => "保存配置失败: {error}",
+        Msg::NoTargetWindow => "未绑定目标窗口!",
+        Msg::SenderBusy => "已有发送线程运行中",
+        Msg::TargetWindowGone => "目标窗口已失效",
+        Msg::SendAborted => "已中止发送",
+        Msg::NoSelection => "没有选中任何行",
+        Msg::SendPreview => "将发送第 {from}-{to} 行, 共 {count} 行",
+        Msg::SendRangeLabel => "行范围 (如 10-20 或 15):",
+        Msg::SendCountLabel => "从第 {line} 行开始发送的行数:",
+        Msg::InvalidRangeInput => "请输入有效的行号",
+        Msg::OpenDialogTitle => "选择要打开的文件",
+        Msg::SaveDialogTitle => "另存为",
+        Msg::CreateOpenDialogFailed => "创建打开文件对话框失败",
+        Msg::ShowOpenDialogFailed => "显示打开文件对话框失败",
+        Msg::CreateSaveDialogFailed => "创建保存文件对话框失败",
+        Msg::ShowSaveDialogFailed => "显示保存文件对话框失败",
+        Msg::InvalidShortcut => "无效的快捷键: {key}",
+        Msg::HotkeyRegisterFailed => "全局热键注册失败 (可能已被占用): {key}",
+        Msg::TargetInvalidRule => "无效的目标规则: {rule}",
+        Msg::TargetUnknownKind => "未知的目标类型: {kind}",
+        Msg::TargetInvalidOption => "无效的目标选项: {option}={value}",
+        Msg::TargetUnsupported => "当前平台不支持目标类型 {kind}",
+        Msg::TargetNotFound => "未找到目标: {target}",
+        Msg::TargetOpenFailed => "无法打开目标 {target}: {error}",
+        Msg::SendIoFailed => "发送第 {line} 行到 {target} 失败: {error}",
+        Msg::SendPromptTimeout => "第 {line} 行发送后等待 {target} 的提示符超时",
+        Msg::SendOutputClosed => "第 {line} 行发送后 {target} 的输出已结束 (程序已退出?)",
+        Msg::SendNoOutput => "第 {line} 行的指令需要读取目标的输出, 但无法读取 {target} 的输出",
+        Msg::SendCaptureTimeout => "第 {line} 行: 等待 {target} 的输出匹配超时, 未能取得 {name}",
+        Msg::SendCaptured => "第 {line} 行: {name} = {value}",
+        Msg::SendStatusTimeout => "第 {line} 行: 等待 {target} 输出退出状态超时",
+        Msg::SendExitStatus => "第 {line} 行在 {target} 上以状态 {status} 退出, 已停止: {text}",
+        Msg::SendErrorHandler => "{error}; 执行出错处理段 [{section}]",
+        Msg::SendErrorGoto => "{error}; 跳到标签 {label} 继续执行",
+        Msg::SendIteration => "第 {line} 行的循环: 第 {iteration}/{total} 次",
+        Msg::SendIterationWhile => "第 {line} 行的循环: 第 {iteration} 次",
+        Msg::SendExpectFailed => "第 {line} 行: {target} 的输出中没有出现 /{pattern}/",
+        Msg::SendUnexpectedOutput => "第 {line} 行: {target} 的输出中出现了 /{pattern}/: {text}",
+        Msg::SendTooManyJumps => "第 {line} 行: 跳回标签 {label} 已超过 {max} 次, 可能陷入了死循环, 已停止",
+        Msg::RunSummary => "测试结果: 通过 {passed}, 失败 {failed}, 跳过 {skipped}, 用时 {seconds} 秒",
+        Msg::CliUsage => CLI_USAGE_ZH_CN,
+        Msg::CliUnknownCommand => "未知命令: {command}",
+        Msg::CliUnknownOption => "未知选项: {option}",
+        Msg::CliMissingValue => "选项 {option} 缺少参数值",
+        Msg::CliInvalidValue => "选项 {option} 的值无效: {value}",
+        Msg::CliMissingOption => "缺少必需的选项 {option}",
+        Msg::CliReadFileFailed => "读取文件 {path} 失败: {error}",
+        Msg::CliConflictingOptions => "{option} 不能与 {other} 同时使用",
+        Msg::InvalidGuardPattern => "配置文件 [guard] 中的危险命令规则无效: {error}",
+        Msg::GuardHits => "以下行匹配危险命令规则:",
+        Msg::SendUnconfirmed => "第 {lines} 行匹配危险命令规则, 未经确认, 没有发送任何内容",
+        Msg::GuardDialogTitle => "危险命令",
+        Msg::GuardConfirm => "以下行匹配危险命令规则:\n\n{lines}\n\n确认发送?",
+        Msg::GuardHitLine => "第 {line} 行: {text}",
+        Msg::GuardHitCount => "共 {count} 条危险命令, 见消息框",
+        Msg::GuardProtectedLabel => "目标 {target} 受保护, 输入其名称以确认发送:",
+        Msg::GuardCancelled => "已取消发送",
+        Msg::CliGuardAsk => "确认发送? [y/N] ",
+        Msg::CliGuardTypeName => "目标 {target} 受保护, 输入目标名称以确认发送: ",
+        Msg::CliGuardRefused => "未确认危险命令, 没有发送任何内容 (无人值守时使用 --yes)",
+        Msg::CliGuardProtectedRefused => "目标 {target} 受保护, 未确认危险命令, 没有发送任何内容 (无人值守时使用 --yes-protected)",
+        Msg::EditorLine => "第 {line} 行",
+        Msg::IncludeMissingPath => "{location}: #!include 缺少文件路径",
+        Msg::IncludeNoBaseFile => "{location}: 文件尚未保存, 无法解析相对路径 {path}",
+        Msg::IncludeReadFailed => "{location}: 无法读取 {path}: {error}",
+        Msg::IncludeCycle => "{location}: 循环包含 {path}",
+        Msg::IncludeTooDeep => "{location}: #!include 嵌套超过 {max} 层",
+        Msg::IncludeUnknownSection => "{location}: {path} 中没有分段 [{name}]",
+        Msg::DirectiveUnknown => "{location}: 未知的指令 #!{name}",
+        Msg::DirectiveUsage => "{location}: 指令格式错误, 应为 {usage}",
+        Msg::DirectiveInvalidPattern => "{location}: 指令中的正则无效: {error}",
+        Msg::DirectiveInvalidTimeout => "{location}: 无效的超时 {value}, 应写作 500ms、10s、2m 等",
+        Msg::DirectiveUnknownSection => "{location}: 脚本中没有分段 [{name}]",
+        Msg::DirectiveUnmatched => "{location}: #!{name} 前面没有对应的开头",
+        Msg::DirectiveUnclosed => "{location}: 缺少对应的 #!{name}",
+        Msg::DirectiveUnknownLabel => "{location}: 从这里跳不到标签 {name} (不存在, 或在循环、条件的里层)",
+        Msg::DirectiveDuplicateLabel => "{location}: 标签 {name} 重复定义",
+        Msg::DirectiveUnknownMacro => "{location}: 没有名为 {name} 的宏",
+        Msg::DirectiveMacroArguments => "{location}: 宏 {name} 有 {expected} 个参数, 调用时给出了 {found} 个",
+        Msg::DirectiveDuplicateMacro => "{location}: 宏 {name} 重复定义",
+        Msg::DirectiveMacroRecursion => "{location}: 宏 {name} 调用了自身或嵌套过深",
+        Msg::DirectiveNestedTooDeep => "{location}: #!include 与 #!call 嵌套超过 {max} 层",
+        Msg::UnknownSection => "找不到分段 [{name}], 文件中的分段: {sections}",
+        Msg::InvalidRange => "无效的行范围: {from}-{to} (文件共 {total} 行)",
+        Msg::CliInvalidPrompt => "无效的提示符正则: {error}",
+        Msg::TranscriptFailed => "会话记录写入失败: {error}",
+        Msg::CaptureFailed => "输出记录写入失败: {error}",
+        Msg::CliCaptureUnsupported => "无法读取目标 {target} 的输出, 不记录输出",
+        Msg::TriggerUnknown => "目标配置中的触发器 {name} 不存在, 请在 [triggers] 中添加",
+        Msg::TriggerInvalidPattern => "触发器 {name} 的正则无效: {error}",
+        Msg::TriggersUnsupported => "无法读取目标 {target} 的输出, 触发器不生效",
+        Msg::TriggerFired => "触发器 {name} 已回复",
+        Msg::TriggerFailed => "触发器 {name} 回复失败: {error}",
+        Msg::CliSendDone => "已发送 {count} 行到 {target}",
+        Msg::CliReportFailed => "写入测试报告 {path} 失败: {error}",
+        Msg::CliSendAborted => "发送已中止, 已发送 {count} 行到 {target}",
+        Msg::CliDryRunSummary => "(试运行) 将发送 {count} 行到 {target}",
+        Msg::CliConfiguredTargets => "配置文件中的目标:",
+        Msg::CliDetectedTargets => "本机检测到的目标:",
+        Msg::CliNoTargets => "  (无)",
+    }
+}
+
+fn en_us(msg: Msg) -> &'static str {
+    match msg {
+        Msg::ButtonOpen => "Open",
+        Msg::ButtonSave => "Save",
+        Msg::ButtonSend => "Send",
+        Msg::ButtonMultiSend => "Multi",
+        Msg::ButtonBind => "Bind",
+        Msg::MenuTheme => "Theme",
+        Msg::MenuThemeDark => "Dark",
+        Msg::MenuThemeLight => "Light",
+        Msg::MenuThemeHighContrast => "High contrast",
+        Msg::MenuThemeCustom => "Custom",
+        Msg::MenuLanguage => "Language",
+        Msg::MenuLanguageAuto => "System default",
+        Msg::MenuSend => "Send",
+        Msg::MenuSendLine => "Send current line",
+        Msg::MenuMultiSend => "Send from cursor",
+        Msg::MenuSendSelection => "Send selected lines",
+        Msg::MenuRunToCursor => "Run to cursor",
+        Msg::MenuSendRange => "Send line range...",
+        Msg::MenuSendCount => "Send N lines from cursor...",
+        Msg::MenuAbort => "Abort sending",
+        Msg::MenuRunSection => "Run selected section",
+        Msg::NoSectionSelected => "Select a section in the list first",
+        Msg::RunningSection => "Running section [{name}]",
+        Msg::MenuStopAt => "Multi-send stops at",
+        Msg::MenuStopBlock => "Next step marker (--- step ---)",
+        Msg::MenuStopBlank => "Blank line",
+        Msg::MenuStopEof => "End of file",
+        Msg::MenuCheckStatus => "Check exit status",
+        Msg::MenuTerminal => "Terminal",
+        Msg::MenuTerminalConnect => "Connect target...",
+        Msg::MenuTerminalDisconnect => "Disconnect",
+        Msg::TerminalConnectLabel => "Target name or rule (e.g. serial:COM3?baud=115200):",
+        Msg::TerminalNoOutput => "The output of {target} cannot be read, so it cannot be shown in the terminal",
+        Msg::TerminalConnected => "Terminal connected to {target}; sent commands now go to this target",
+        Msg::TerminalDisconnected => "Terminal disconnected; sent commands go to the bound window again",
+        Msg::TerminalClosed => "The connection to {target} has ended",
+        Msg::DialogOk => "OK",
+        Msg::DialogCancel => "Cancel",
+        Msg::CreateWindowFailed => "Failed to create control",
+        Msg::SubclassFailed => "Failed to subclass button",
+        Msg::BindSuccess => "Bound to new window: {hwnd}",
+        Msg::WindowDataMissing => "Window data not found",
+        Msg::InvalidWindow => "Invalid window",
+        Msg::SaveConfigFailed => "Failed to save config: {error}",
+        Msg::NoTargetWindow => "No target window bound!",
+        Msg::SenderBusy => "A send is already in progress",
+        Msg::TargetWindowGone => "Target window no longer exists",
+        Msg::SendAborted => "Sending aborted",
+        Msg::NoSelection => "No lines selected",
+        Msg::SendPreview => "Will send lines {from}-{to}, {count} lines in total",
+        Msg::SendRangeLabel => "Line range (e.g. 10-20 or 15):",
+        Msg::SendCountLabel => "Number of lines to send from line {line}:",
+        Msg::InvalidRangeInput => "Please enter valid line numbers",
+        Msg::OpenDialogTitle => "Select File to Open",
+        Msg::SaveDialogTitle => "Save File As",
+        Msg::CreateOpenDialogFailed => "Failed to create file open dialog",
+        Msg::ShowOpenDialogFailed => "Failed to show file open dialog",
+        Msg::CreateSaveDialogFailed => "Failed to create file save dialog",
+        Msg::ShowSaveDialogFailed => "Failed to show file save dialog",
+        Msg::InvalidShortcut => "Invalid shortcut: {key}",
+        Msg::HotkeyRegisterFailed => "Failed to register global hotkey (already in use?): {key}",
+        Msg::TargetInvalidRule => "Invalid target rule: {rule}",
+        Msg::TargetUnknownKind => "Unknown target type: {kind}",
+        Msg::TargetInvalidOption => "Invalid target option: {option}={value}",
+        Msg::TargetUnsupported => "Target type {kind} is not supported on this platform",
+        Msg::TargetNotFound => "Target not found: {target}",
+        Msg::TargetOpenFailed => "Cannot open target {target}: {error}",
+        Msg::SendIoFailed => "Failed to send line {line} to {target}: {error}",
+        Msg::SendPromptTimeout => "Timed out waiting for the prompt from {target} after line {line}",
+        Msg::SendOutputClosed => "Output of {target} ended after line {line} (program exited?)",
+        Msg::SendNoOutput => "The directive on line {line} needs the target's output, but the output of {target} cannot be read",
+        Msg::SendCaptureTimeout => "Line {line}: timed out waiting for matching output from {target}, {name} was not captured",
+        Msg::SendCaptured => "Line {line}: {name} = {value}",
+        Msg::SendStatusTimeout => "Line {line}: timed out waiting for the exit status from {target}",
+        Msg::SendExitStatus => "Line {line} exited with status {status} on {target}, stopped: {text}",
+        Msg::SendErrorHandler => "{error}; running error handler [{section}]",
+        Msg::SendErrorGoto => "{error}; continuing at label {label}",
+        Msg::SendIteration => "Loop at line {line}: iteration {iteration}/{total}",
+        Msg::SendIterationWhile => "Loop at line {line}: iteration {iteration}",
+        Msg::SendExpectFailed => "Line {line}: /{pattern}/ did not appear in the output of {target}",
+        Msg::SendUnexpectedOutput => "Line {line}: /{pattern}/ appeared in the output of {target}: {text}",
+        Msg::SendTooManyJumps => "Line {line}: jumped back to label {label} more than {max} times, stopped to avoid an endless loop",
+        Msg::RunSummary => "Results: {passed} passed, {failed} failed, {skipped} skipped in {seconds}s",
+        Msg::CliUsage => CLI_USAGE_EN_US,
+        Msg::CliUnknownCommand => "Unknown command: {command}",
+        Msg::CliUnknownOption => "Unknown option: {option}",
+        Msg::CliMissingValue => "Option {option} requires a value",
+        Msg::CliInvalidValue => "Invalid value for {option}: {value}",
+        Msg::CliMissingOption => "Missing required option {option}",
+        Msg::CliReadFileFailed => "Failed to read {path}: {error}",
+        Msg::CliConflictingOptions => "{option} cannot be combined with {other}",
+        Msg::InvalidGuardPattern => "Invalid dangerous-command pattern in [guard]: {error}",
+        Msg::GuardHits => "These lines match dangerous-command patterns:",
+        Msg::SendUnconfirmed => "Lines {lines} match dangerous-command patterns and were not confirmed, nothing was sent",
+        Msg::GuardDialogTitle => "Dangerous commands",
+        Msg::GuardConfirm => "These lines match dangerous-command patterns:\n\n{lines}\n\nSend anyway?",
+        Msg::GuardHitLine => "line {line}: {text}",
+        Msg::GuardHitCount => "{count} dangerous commands, see the message pane",
+        Msg::GuardProtectedLabel => "Target {target} is protected. Type its name to confirm:",
+        Msg::GuardCancelled => "Sending cancelled",
+        Msg::CliGuardAsk => "Send anyway? [y/N] ",
+        Msg::CliGuardTypeName => "Target {target} is protected. Type the target name to confirm: ",
+        Msg::CliGuardRefused => "Dangerous commands not confirmed, nothing was sent (use --yes when running unattended)",
+        Msg::CliGuardProtectedRefused => "Target {target} is protected and the dangerous commands were not confirmed, nothing was sent (use --yes-protected when running unattended)",
+        Msg::EditorLine => "line {line}",
+        Msg::IncludeMissingPath => "{location}: #!include needs a file path",
+        Msg::IncludeNoBaseFile => "{location}: save the file first to resolve the relative path {path}",
+        Msg::IncludeReadFailed => "{location}: cannot read {path}: {error}",
+        Msg::IncludeCycle => "{location}: {path} is included recursively",
+        Msg::IncludeTooDeep => "{location}: #!include nested more than {max} levels",
+        Msg::IncludeUnknownSection => "{location}: no section [{name}] in {path}",
+        Msg::DirectiveUnknown => "{location}: unknown directive #!{name}",
+        Msg::DirectiveUsage => "{location}: malformed directive, expected {usage}",
+        Msg::DirectiveInvalidPattern => "{location}: invalid pattern in directive: {error}",
+        Msg::DirectiveInvalidTimeout => "{location}: invalid timeout {value}, use 500ms, 10s, 2m and so on",
+        Msg::DirectiveUnknownSection => "{location}: no section [{name}] in the script",
+        Msg::DirectiveUnmatched => "{location}: #!{name} without a matching start",
+        Msg::DirectiveUnclosed => "{location}: missing #!{name}",
+        Msg::DirectiveUnknownLabel => "{location}: label {name} cannot be reached from here (missing, or inside a loop or condition)",
+        Msg::DirectiveDuplicateLabel => "{location}: label {name} is defined more than once",
+        Msg::DirectiveUnknownMacro => "{location}: no macro named {name}",
+        Msg::DirectiveMacroArguments => "{location}: macro {name} takes {expected} arguments but {found} were given",
+        Msg::DirectiveDuplicateMacro => "{location}: macro {name} is defined more than once",
+        Msg::DirectiveMacroRecursion => "{location}: macro {name} calls itself or is nested too deeply",
+        Msg::DirectiveNestedTooDeep => "{location}: #!include and #!call are nested more than {max} levels deep",
+        Msg::UnknownSection => "No section [{name}] in the file; sections: {sections}",
+        Msg::InvalidRange => "Invalid line range: {from}-{to} (file has {total} lines)",
+        Msg::CliInvalidPrompt => "Invalid prompt regex: {error}",
+        Msg::TranscriptFailed => "Failed to write the session transcript: {error}",
+        Msg::CaptureFailed => "Failed to write the output capture: {error}",
+        Msg::CliCaptureUnsupported => "The output of {target} cannot be read, output capture is off",
+        Msg::TriggerUnknown => "Trigger {name} used by the target is not defined in [triggers]",
+        Msg::TriggerInvalidPattern => "Invalid pattern in trigger {name}: {error}",
+        Msg::TriggersUnsupported => "The output of {target} cannot be read, triggers are off",
+        Msg::TriggerFired => "Trigger {name} responded",
+        Msg::TriggerFailed => "Trigger {name} failed to respond: {error}",
+        Msg::CliSendDone => "Sent {count} lines to {target}",
+        Msg::CliReportFailed => "Failed to write the test report {path}: {error}",
+        Msg::CliSendAborted => "Sending aborted after {count} lines to {target}",
+        Msg::CliDryRunSummary => "(dry run) {count} lines would be sent to {target}",
+        Msg::CliConfiguredTargets => "Configured targets:",
+        Msg::CliDetectedTargets => "Detected on this machine:",
+        Msg::CliNoTargets => "  (none)",
+    }
+}
+
+const CLI_USAGE_ZH_CN: &str = "\
+用法:
+  tty_sender                          启动图形界面 (仅 Windows)
+  tty_sender send --target <目标> --file <文件> [选项]
+  tty_sender list-targets
+
+send 选项:
+  --target <目标>        配置文件中的目标名称, 或目标规则, 如
+                         serial:/dev/ttyUSB0?baud=115200、tcp:host:port、pty:/dev/pts/3、
+                         spawn:<命令> (在伪终端中启动程序, 仅类 Unix 系统)、
+                         tmux:<窗格>、screen:<会话>?window=<窗口>、
+                         qmp:<套接字>、hmp:<套接字> (QEMU 监视器, 发送真实按键)、
+                         vnc:<主机>[:端口]?password=<密码>
+  --file <文件>          命令文件
+  --from-line <N>        从第 N 行开始 (从 1 开始, 默认 1)
+  --to-line <M>          到第 M 行结束 (包含, 默认文件末尾)
+  --count <N>            从起始行开始发送 N 行
+  --section <名称>       只发送 [名称] 段, 不能与行范围同时使用
+  --var KEY=VAL          定义变量, 行中的 ${KEY} 会被替换, 可重复
+  --stop <条件>          停止条件: eof (默认, 发送到末尾)、block (下一个 --- 标记)、blank (空行)
+  --stop-at-blank        同 --stop blank
+  --send-blank-lines     把空行作为回车发送 (默认跳过空行)
+  --check-status         检查每条 shell 命令的退出状态, 非零时停止 (需要能读取输出的目标)
+  --char-delay <毫秒>    字符间隔
+  --line-delay <毫秒>    行间隔
+  --prompt <正则>        每行发送后等待输出匹配提示符 (串口、TCP、伪终端、spawn、tmux、screen 目标)
+  --prompt-timeout <毫秒> 等待提示符的超时时间 (默认 10000)
+  --dry-run              只打印将要发送的行, 不连接目标
+  --yes                  确认发送匹配危险命令规则的行 (无人值守时使用)
+  --yes-protected        同上, 受保护的目标须使用此选项
+  --log-dir <目录>       会话记录目录 (默认为配置中的 [log] dir)
+  --no-transcript        本次不写会话记录
+  --capture <文件>       把目标的输出记录到文件 (串口、TCP、伪终端、spawn、tmux、screen 目标)
+  --capture-timestamps <格式> 输出记录每行的时间戳: none、wall (本地时间)、relative (相对时间)、both
+  --no-capture           本次不记录输出 (配置中 [log] capture = true 时)
+  --junit <文件>         把每一步的结果 (#!expect 检查等) 写为 JUnit XML
+  --report-json <文件>   把每一步的结果写为 JSON
+
+退出码: 0 成功, 1 发送失败, 2 参数错误, 3 目标错误, 4 文件错误, 5 已中止, 6 危险命令未确认";
+
+const CLI_USAGE_EN_US: &str = "\
+Usage:
+  tty_sender                          start the GUI (Windows only)
+  tty_sender send --target <target> --file <file> [options]
+  tty_sender list-targets
+
+send options:
+  --target <target>      a target name from the config file, or a target rule such as
+                         serial:/dev/ttyUSB0?baud=115200, tcp:host:port, pty:/dev/pts/3,
+                         spawn:<command> (run a program under a pty, Unix-like systems only),
+                         tmux:<pane>, screen:<session>?window=<window>,
+                         qmp:<socket>, hmp:<socket> (QEMU monitor, sends real key events),
+                         vnc:<host>[:port]?password=<password>
+  --file <file>          command file
+  --from-line <N>        first line to send (1-based, default 1)
+  --to-line <M>          last line to send (inclusive, default end of file)
+  --count <N>            send N lines starting at the first line
+  --section <name>       send only the [name] section (not combined with a line range)
+  --var KEY=VAL          define a variable substituted for ${KEY}, may be repeated
+  --stop <when>          where to stop: eof (default), block (next --- marker) or blank (first blank line)
+  --stop-at-blank        same as --stop blank
+  --send-blank-lines     send blank lines as a bare Enter (skipped by default)
+  --check-status         check the exit status of each shell command and stop when it is non-zero
+                         (targets whose output can be read)
+  --char-delay <ms>      delay between characters
+  --line-delay <ms>      delay between lines
+  --prompt <regex>       after each line wait for output matching the prompt (serial, tcp, pty, spawn, tmux, screen)
+  --prompt-timeout <ms>  how long to wait for the prompt (default 10000)
+  --dry-run              print the lines that would be sent without opening the target
+  --yes                  confirm lines matching dangerous-command patterns (for unattended runs)
+  --yes-protected        the same for targets marked protected, where --yes is not enough
+  --log-dir <dir>        directory for the session transcript (default: [log] dir in the config)
+  --no-transcript        do not write the session transcript for this run
+  --capture <file>       record the target's output to a file (serial, tcp, pty, spawn, tmux, screen)
+  --capture-timestamps <kind> per-line timestamps in the capture: none, wall, relative or both
+  --no-capture           do not record output for this run (when [log] capture = true)
+  --junit <file>         write the result of each step (#!expect checks and so on) as JUnit XML
+  --report-json <file>   write the result of each step as JSON
+
+Exit codes: 0 success, 1 send failed, 2 usage error, 3 target error, 4 file error, 5 aborted, 6 dangerous commands not confirmed";
+
+pub fn set_language(lang: Lang) {
+    *CURRENT_LANG.lock().unwrap() = lang;
+}
+
+pub fn current_language() -> Lang {
+    *CURRENT_LANG.lock().unwrap()
+}
+
+pub fn tr(msg: Msg) -> &'static str {
+    match current_language() {
+        Lang::ZhCn => zh_cn(msg),
+        Lang::EnUs => en_us(msg),
+    }
+}
+
+/// 翻译并替换文本中的 {name} 占位符
+pub fn tr_fmt(msg: Msg, args: &[(&str, &dyn Display)]) -> String {
+    let mut text = tr(msg).to_string();
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    text
+}
+
+pub fn describe_target_error(error: &TargetError) -> String {
+    match error {
+        TargetError::InvalidRule(rule) => tr_fmt(Msg::TargetInvalidRule, &[("rule", rule)]),
+        TargetError::UnknownKind(kind) => tr_fmt(Msg::TargetUnknownKind, &[("kind", kind)]),
+        TargetError::InvalidOption { option, value } => {
+            tr_fmt(Msg::TargetInvalidOption, &[("option", option), ("value", value)])
+        }
+        TargetError::Unsupported(kind) => tr_fmt(Msg::TargetUnsupported, &[("kind", kind)]),
+        TargetError::NotFound(target) => tr_fmt(Msg::TargetNotFound, &[("target", target)]),
+        TargetError::Open { target, source } => {
+            tr_fmt(Msg::TargetOpenFailed, &[("target", target), ("error", source)])
+        }
+    }
+}
+
+pub fn describe_range_error(error: &RangeError) -> String {
+    tr_fmt(Msg::InvalidRange, &[("from", &error.from), ("to", &error.to), ("total", &error.total)])
+}
+
+pub fn describe_send_error(error: &SendError, target: &str) -> String {
+    match error {
+        SendError::Io { line, source } => {
+            tr_fmt(Msg::SendIoFailed, &[("line", line), ("target", &target), ("error", source)])
+        }
+        SendError::PromptTimeout { line } => {
+            tr_fmt(Msg::SendPromptTimeout, &[("line", line), ("target", &target)])
+        }
+        SendError::OutputClosed { line } => {
+            tr_fmt(Msg::SendOutputClosed, &[("line", line), ("target", &target)])
+        }
+        SendError::NoOutput { line } => tr_fmt(Msg::SendNoOutput, &[("line", line), ("target", &target)]),
+        SendError::CaptureTimeout { line, name } => {
+            tr_fmt(Msg::SendCaptureTimeout, &[("line", line), ("target", &target), ("name", name)])
+        }
+        SendError::StatusTimeout { line } => tr_fmt(Msg::SendStatusTimeout, &[("line", line), ("target", &target)]),
+        SendError::ExitStatus { line, text, status } => {
+            tr_fmt(Msg::SendExitStatus, &[("line", line), ("target", &target), ("status", status), ("text", text)])
+        }
+        SendError::ExpectFailed { line, pattern } => {
+            tr_fmt(Msg::SendExpectFailed, &[("line", line), ("target", &target), ("pattern", pattern)])
+        }
+        SendError::UnexpectedOutput { line, pattern, text } => {
+            tr_fmt(Msg::SendUnexpectedOutput, &[("line", line), ("target", &target), ("pattern", pattern), ("text", text)])
+        }
+        SendError::TooManyJumps { line, label } => {
+            tr_fmt(Msg::SendTooManyJumps, &[("line", line), ("label", label), ("max", &MAX_JUMPS_BACK)])
+        }
+        SendError::Script(e) => describe_script_error(e),
+        SendError::Unconfirmed(hits) => {
+            let lines: Vec<_> = hits.iter().map(|hit| hit.line.number.to_string()).collect();
+            tr_fmt(Msg::SendUnconfirmed, &[("lines", &lines.join(", "))])
+        }
+    }
+}
+
+pub fn describe_results(results: &TestRun) -> String {
+    let seconds = format!("{:.1}", results.duration.as_secs_f64());
+    tr_fmt(
+        Msg::RunSummary,
+        &[("passed", &results.passed()), ("failed", &results.failed()), ("skipped", &results.skipped()), ("seconds", &seconds)],
+    )
+}
+
+pub fn describe_trigger_error(error: &TriggerError) -> String {
+    match error {
+        TriggerError::Unknown(name) => tr_fmt(Msg::TriggerUnknown, &[("name", name)]),
+        TriggerError::Pattern(name, e) => tr_fmt(Msg::TriggerInvalidPattern, &[("name", name), ("error", e)]),
+    }
+}
+
+/// 循环开始新的一次时的进度
+pub fn describe_iteration(line: usize, iteration: usize, total: Option<usize>) -> String {
+    match total {
+        Some(total) => tr_fmt(Msg::SendIteration, &[("line", &line), ("iteration", &iteration), ("total", &total)]),
+        None => tr_fmt(Msg::SendIterationWhile, &[("line", &line), ("iteration", &iteration)]),
+    }
+}
+
+pub fn describe_script_error(error: &ScriptError) -> String {
+    match error {
+        ScriptError::Include(e) => describe_include_error(e),
+        ScriptError::Directive(e) => describe_directive_error(e),
+    }
+}
+
+fn describe_directive_error(error: &DirectiveError) -> String {
+    let location = match &error.file {
+        Some(file) => format!("{}:{}", file.display(), error.line),
+        None => tr_fmt(Msg::EditorLine, &[("line", &error.line)]),
+    };
+    let location: &dyn Display = &location;
+    match &error.kind {
+        DirectiveErrorKind::Unknown(name) => tr_fmt(Msg::DirectiveUnknown, &[("location", location), ("name", name)]),
+        DirectiveErrorKind::Usage(usage) => tr_fmt(Msg::DirectiveUsage, &[("location", location), ("usage", usage)]),
+        DirectiveErrorKind::Pattern(e) => tr_fmt(Msg::DirectiveInvalidPattern, &[("location", location), ("error", e)]),
+        DirectiveErrorKind::Timeout(value) => {
+            tr_fmt(Msg::DirectiveInvalidTimeout, &[("location", location), ("value", value)])
+        }
+        DirectiveErrorKind::Section(name) => tr_fmt(Msg::DirectiveUnknownSection, &[("location", location), ("name", name)]),
+        DirectiveErrorKind::Unmatched(name) => tr_fmt(Msg::DirectiveUnmatched, &[("location", location), ("name", name)]),
+        DirectiveErrorKind::Unclosed(name) => tr_fmt(Msg::DirectiveUnclosed, &[("location", location), ("name", name)]),
+        DirectiveErrorKind::Label(name) => tr_fmt(Msg::DirectiveUnknownLabel, &[("location", location), ("name", name)]),
+        DirectiveErrorKind::DuplicateLabel(name) => {
+            tr_fmt(Msg::DirectiveDuplicateLabel, &[("location", location), ("name", name)])
+        }
+        DirectiveErrorKind::UnknownMacro(name) => tr_fmt(Msg::DirectiveUnknownMacro, &[("location", location), ("name", name)]),
+        DirectiveErrorKind::MacroArguments { name, expected, found } => tr_fmt(
+            Msg::DirectiveMacroArguments,
+            &[("location", location), ("name", name), ("expected", expected), ("found", found)],
+        ),
+        DirectiveErrorKind::DuplicateMacro(name) => {
+            tr_fmt(Msg::DirectiveDuplicateMacro, &[("location", location), ("name", name)])
+        }
+        DirectiveErrorKind::MacroRecursion(name) => {
+            tr_fmt(Msg::DirectiveMacroRecursion, &[("location", location), ("name", name)])
+        }
+        DirectiveErrorKind::NestedTooDeep => {
+            tr_fmt(Msg::DirectiveNestedTooDeep, &[("location", location), ("max", &MAX_CALL_DEPTH)])
+        }
+    }
+}
+
+fn describe_include_error(error: &IncludeError) -> String {
+    let location = match &error.file {
+        Some(file) => format!("{}:{}", file.display(), error.line),
+        None => tr_fmt(Msg::EditorLine, &[("line", &error.line)]),
+    };
+    let location: &dyn Display = &location;
+    match &error.kind {
+        IncludeErrorKind::MissingPath => tr_fmt(Msg::IncludeMissingPath, &[("location", location)]),
+        IncludeErrorKind::NoBaseFile(path) => {
+            tr_fmt(Msg::IncludeNoBaseFile, &[("location", location), ("path", &path.display())])
+        }
+        IncludeErrorKind::Read(path, e) => {
+            tr_fmt(Msg::IncludeReadFailed, &[("location", location), ("path", &path.display()), ("error", e)])
+        }
+        IncludeErrorKind::Cycle(path) => tr_fmt(Msg::IncludeCycle, &[("location", location), ("path", &path.display())]),
+        IncludeErrorKind::TooDeep => tr_fmt(Msg::IncludeTooDeep, &[("location", location), ("max", &MAX_INCLUDE_DEPTH)]),
+        IncludeErrorKind::UnknownSection(path, name) => tr_fmt(
+            Msg::IncludeUnknownSection,
+            &[("location", location), ("path", &path.display()), ("name", name)],
+        ),
+    }
+}
@@ -0,0 +1,355 @@
+//! 输出记录: 把目标的输出持续写入文件, 可在每行前加时间戳、去掉 ANSI 控制序列,
+//! 并在发送命令时插入标记, 便于事后对照每条命令的输出。退出状态检查附加的探测不写入记录
+
+use crate::status::{ends_with_partial_probe, hide_probe};
+use crate::target::OutputBuffer;
+use chrono::Local;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use vte::{Parser, Perform};
+
+/// 每行开头的时间戳
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timestamps {
+    None,
+    /// 本地时间
+    Wall,
+    /// 距开始记录的秒数
+    Relative,
+    Both,
+}
+
+impl Timestamps {
+    pub fn parse(value: &str) -> Option<Timestamps> {
+        match value {
+            "none" => Some(Timestamps::None),
+            "wall" => Some(Timestamps::Wall),
+            "relative" => Some(Timestamps::Relative),
+            "both" => Some(Timestamps::Both),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Timestamps::None => "none",
+            Timestamps::Wall => "wall",
+            Timestamps::Relative => "relative",
+            Timestamps::Both => "both",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CaptureOptions {
+    pub timestamps: Timestamps,
+    /// 去掉颜色、光标移动等控制序列和回车符
+    pub strip_ansi: bool,
+    /// 发送每行前插入 `>>> line N: 命令` 标记
+    pub markers: bool,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self { timestamps: Timestamps::Wall, strip_ansi: true, markers: true }
+    }
+}
+
+/// 正在进行的输出记录, 后台线程随输出到达写入; 克隆后共享同一份记录
+#[derive(Clone)]
+pub struct Capture {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    output: OutputBuffer,
+    options: CaptureOptions,
+    started: Instant,
+    state: Mutex<State>,
+    stopped: AtomicBool,
+}
+
+struct State {
+    writer: Box<dyn Write + Send>,
+    /// 已写入的输出在 OutputBuffer 中的位置
+    position: usize,
+    at_line_start: bool,
+    /// 收到的输出按行写入, 以便去掉探测; 尚未结束的一行暂存在这里
+    pending: String,
+    parser: Parser,
+    /// 第一个写入错误, 结束时返回
+    error: Option<io::Error>,
+}
+
+impl Capture {
+    /// 追加写入 path (目录不存在时创建) 并开始记录
+    pub fn create(path: &Path, target: &str, output: OutputBuffer, options: CaptureOptions) -> io::Result<Capture> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::start(target, output, Box::new(file), options))
+    }
+
+    /// 从 output 当前位置开始记录, 之前的输出不写入
+    pub fn start(target: &str, output: OutputBuffer, writer: Box<dyn Write + Send>, options: CaptureOptions) -> Capture {
+        let state = State {
+            writer,
+            position: output.position(),
+            at_line_start: true,
+            pending: String::new(),
+            parser: Parser::new(),
+            error: None,
+        };
+        let capture = Capture {
+            inner: Arc::new(Inner {
+                output,
+                options,
+                started: Instant::now(),
+                state: Mutex::new(state),
+                stopped: AtomicBool::new(false),
+            }),
+        };
+        capture.write_line(&format!("=== {} capture started {} ===", target, Local::now().format("%Y-%m-%d %H:%M:%S")));
+
+        let worker = capture.clone();
+        thread::spawn(move || worker.run());
+        capture
+    }
+
+    fn run(&self) {
+        // 分段等待, 以便结束后及时退出
+        const SLICE: Duration = Duration::from_millis(200);
+        while !self.inner.stopped.load(Ordering::SeqCst) {
+            let position = self.inner.state.lock().unwrap().position;
+            if self.inner.output.wait_new(position, SLICE) {
+                self.pump(&mut self.inner.state.lock().unwrap());
+                if self.inner.output.is_closed() {
+                    break;
+                }
+            } else {
+                // 暂时没有新输出, 写入未结束的一行 (如提示符);
+                // 末尾可能是探测回显的开头时继续暂存, 等其余部分到达后一并去掉
+                let mut state = self.inner.state.lock().unwrap();
+                if !ends_with_partial_probe(&state.pending) {
+                    self.flush_pending(&mut state);
+                }
+            }
+        }
+    }
+
+    /// 发送某行前调用, 先写入此前收到的输出, 再写标记
+    pub fn mark(&self, line: usize, text: &str) {
+        if self.inner.options.markers {
+            self.write_line(&format!(">>> line {}: {}", line, text));
+        }
+    }
+
+    /// 写入剩余的输出并停止记录, 返回记录过程中的第一个写入错误
+    pub fn finish(&self) -> io::Result<()> {
+        self.inner.stopped.store(true, Ordering::SeqCst);
+        let mut state = self.inner.state.lock().unwrap();
+        self.pump(&mut state);
+        self.flush_pending(&mut state);
+        if !state.at_line_start {
+            self.write(&mut state, "\n");
+        }
+        if let Err(e) = state.writer.flush() {
+            state.error.get_or_insert(e);
+        }
+        state.error.take().map_or(Ok(()), Err)
+    }
+
+    /// 另起一行写入记录自身的文字 (开头说明和发送标记)
+    fn write_line(&self, line: &str) {
+        let mut state = self.inner.state.lock().unwrap();
+        self.pump(&mut state);
+        self.flush_pending(&mut state);
+        if !state.at_line_start {
+            self.write(&mut state, "\n");
+        }
+        self.write(&mut state, &format!("{}\n", line));
+    }
+
+    fn pump(&self, state: &mut State) {
+        let (text, end) = self.inner.output.read_since(state.position);
+        state.position = end;
+        if text.is_empty() {
+            return;
+        }
+        if self.inner.options.strip_ansi {
+            let mut plain = Plain(String::with_capacity(text.len()));
+            state.parser.advance(&mut plain, text.as_bytes());
+            self.write_output(state, &plain.0);
+        } else {
+            self.write_output(state, &text);
+        }
+    }
+
+    /// 写入收到的完整行, 其余暂存
+    fn write_output(&self, state: &mut State, text: &str) {
+        for piece in text.split_inclusive('\n') {
+            state.pending.push_str(piece);
+            if piece.ends_with('\n') {
+                self.flush_pending(state);
+            }
+        }
+    }
+
+    fn flush_pending(&self, state: &mut State) {
+        let pending = std::mem::take(&mut state.pending);
+        if let Some(text) = hide_probe(&pending) {
+            self.write(state, &text);
+        }
+    }
+
+    /// 写入文字, 在每行开头加时间戳
+    fn write(&self, state: &mut State, text: &str) {
+        let mut result = Ok(());
+        for piece in text.split_inclusive('\n') {
+            if state.at_line_start {
+                let prefix = self.prefix();
+                result = result.and_then(|_| state.writer.write_all(prefix.as_bytes()));
+            }
+            result = result.and_then(|_| state.writer.write_all(piece.as_bytes()));
+            state.at_line_start = piece.ends_with('\n');
+        }
+        if let Err(e) = result {
+            state.error.get_or_insert(e);
+        }
+    }
+
+    fn prefix(&self) -> String {
+        let wall = || Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string();
+        let relative = || format!("+{:.3}", self.inner.started.elapsed().as_secs_f64());
+        match self.inner.options.timestamps {
+            Timestamps::None => String::new(),
+            Timestamps::Wall => format!("[{}] ", wall()),
+            Timestamps::Relative => format!("[{}] ", relative()),
+            Timestamps::Both => format!("[{} {}] ", wall(), relative()),
+        }
+    }
+}
+
+/// 去掉一段完整输出中的控制序列和回车符
+pub fn strip_ansi(text: &str) -> String {
+    let mut plain = Plain(String::with_capacity(text.len()));
+    Parser::new().advance(&mut plain, text.as_bytes());
+    plain.0
+}
+
+/// 只保留可打印字符、换行和制表符
+pub(crate) struct Plain(pub(crate) String);
+
+impl Perform for Plain {
+    fn print(&mut self, c: char) {
+        self.0.push(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        if byte == b'\n' || byte == b'\t' {
+            self.0.push(byte as char);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    /// 写入内存, 测试中可随时取出内容检查
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Shared {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn strips_escapes_and_interleaves_markers() {
+        let output = OutputBuffer::new();
+        output.push(b"old output\r\n");
+        let log = Shared::default();
+        let options = CaptureOptions { timestamps: Timestamps::None, ..CaptureOptions::default() };
+        let capture = Capture::start("tcp:board:23", output.clone(), Box::new(log.clone()), options);
+
+        output.push(b"\x1b[1;32mroot\x1b[0m@board:~# \x1b]0;tit");
+        capture.mark(3, "uname -r");
+        output.push(b"le\x07uname -r\r\n6.1.0\r\n# ");
+        capture.finish().unwrap();
+
+        assert_eq!(
+            log.text().lines().skip(1).collect::<Vec<_>>(),
+            vec!["root@board:~# ", ">>> line 3: uname -r", "uname -r", "6.1.0", "# "]
+        );
+        assert!(log.text().starts_with("=== tcp:board:23 capture started "));
+    }
+
+    #[test]
+    fn prefixes_each_line_with_timestamps() {
+        let output = OutputBuffer::new();
+        let log = Shared::default();
+        let options = CaptureOptions { timestamps: Timestamps::Both, strip_ansi: false, markers: false };
+        let capture = Capture::start("serial:COM3@115200", output.clone(), Box::new(log.clone()), options);
+        capture.mark(1, "ignored");
+        output.push(b"a\r\nb");
+        output.close();
+        capture.finish().unwrap();
+
+        let line = Regex::new(r"^\[\d{4}-\d\d-\d\d \d\d:\d\d:\d\d\.\d{3} \+\d+\.\d{3}\] (.*)$").unwrap();
+        let lines: Vec<_> = log.text().split_terminator('\n').map(|text| line.captures(text).unwrap()[1].to_string()).collect();
+        assert_eq!(lines[1..], ["a\r", "b"]);
+    }
+
+    #[test]
+    fn hides_status_probe() {
+        let output = OutputBuffer::new();
+        let log = Shared::default();
+        let options = CaptureOptions { timestamps: Timestamps::None, ..CaptureOptions::default() };
+        let capture = Capture::start("spawn:sh", output.clone(), Box::new(log.clone()), options);
+        capture.mark(1, "false");
+        // 回显可能逐个字符到达
+        for c in "$ false; echo __RC=$?\r\n".chars() {
+            output.push(c.to_string().as_bytes());
+        }
+        output.push(b"__RC=1\r\n$ ");
+        capture.finish().unwrap();
+
+        assert_eq!(log.text().lines().skip(1).collect::<Vec<_>>(), vec![">>> line 1: false", "$ false", "$ "]);
+    }
+
+    #[test]
+    fn hides_probe_split_by_a_pause() {
+        let output = OutputBuffer::new();
+        let log = Shared::default();
+        let options = CaptureOptions { timestamps: Timestamps::None, ..CaptureOptions::default() };
+        let capture = Capture::start("spawn:sh", output.clone(), Box::new(log.clone()), options);
+        capture.mark(1, "false");
+        output.push(b"$ false; echo __R");
+        // 超过记录线程的等待间隔, 未结束的一行本应写入
+        thread::sleep(Duration::from_millis(500));
+        output.push(b"C=$?\r\n__RC=1\r\n$ ");
+        capture.finish().unwrap();
+
+        assert_eq!(log.text().lines().skip(1).collect::<Vec<_>>(), vec![">>> line 1: false", "$ false", "$ "]);
+    }
+}
@@ -0,0 +1,485 @@
+use crate::capture::{CaptureOptions, Timestamps};
+use crate::engine::{SendOptions, StopAt};
+use crate::guard::Guard;
+use crate::script::Syntax;
+use crate::terminal::DEFAULT_SCROLLBACK;
+use crate::transcript::Transcript;
+use crate::trigger::{Trigger, TriggerError};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use chrono::Local;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// auto / zh-CN / en-US
+    pub language: String,
+    pub theme: ThemeConfig,
+    pub layout: LayoutConfig,
+    pub keys: KeysConfig,
+    pub send: SendConfig,
+    pub guard: GuardConfig,
+    pub log: LogConfig,
+    pub terminal: TerminalConfig,
+    /// 命名的输出触发器, 在目标配置的 triggers 中按名称启用
+    pub triggers: BTreeMap<String, TriggerRule>,
+    /// 命名目标, 命令行中可用名称代替目标规则
+    pub targets: BTreeMap<String, TargetProfile>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            language: "auto".to_string(),
+            theme: ThemeConfig::default(),
+            layout: LayoutConfig::default(),
+            keys: KeysConfig::default(),
+            send: SendConfig::default(),
+            guard: GuardConfig::default(),
+            log: LogConfig::default(),
+            terminal: TerminalConfig::default(),
+            triggers: BTreeMap::new(),
+            targets: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    /// dark / light / high-contrast / custom
+    pub name: String,
+    pub custom: CustomColors,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self {
+            name: "dark".to_string(),
+            custom: CustomColors::default(),
+        }
+    }
+}
+
+/// 自定义主题颜色, 格式 "#RRGGBB", 未填写的项沿用深色主题
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CustomColors {
+    pub window_background: Option<String>,
+    pub editor_background: Option<String>,
+    pub editor_text: Option<String>,
+    pub message_background: Option<String>,
+    pub message_info: Option<String>,
+    pub message_error: Option<String>,
+    pub button_background: Option<String>,
+    pub button_pressed: Option<String>,
+    pub button_text: Option<String>,
+    pub button_border: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayoutConfig {
+    /// 消息框高度 (96 DPI 下的像素), 拖动分隔条时更新
+    pub message_height: i32,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self { message_height: 60 }
+    }
+}
+
+/// 快捷键, 格式如 "Ctrl+Enter"、"F5", 留空表示不启用; 修改后重启生效
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeysConfig {
+    pub send_line: String,
+    pub multi_send: String,
+    pub send_selection: String,
+    pub run_to_cursor: String,
+    /// 运行分段列表中选中的段
+    pub run_section: String,
+    pub open: String,
+    pub save: String,
+    pub abort: String,
+    /// 全局热键, 目标窗口在前台时也能发送编辑框当前行
+    pub global_send_line: String,
+}
+
+impl Default for KeysConfig {
+    fn default() -> Self {
+        Self {
+            send_line: "Ctrl+Enter".to_string(),
+            multi_send: "F5".to_string(),
+            send_selection: "Ctrl+Shift+Enter".to_string(),
+            run_to_cursor: "Ctrl+F5".to_string(),
+            run_section: "Ctrl+R".to_string(),
+            open: "Ctrl+O".to_string(),
+            save: "Ctrl+S".to_string(),
+            abort: "Esc".to_string(),
+            global_send_line: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SendConfig {
+    pub char_delay_ms: u64,
+    pub line_delay_ms: u64,
+    /// 提示符正则, 为空时不等待; 仅对能读取输出的目标 (如 spawn) 有效
+    pub prompt: String,
+    pub prompt_timeout_ms: u64,
+    /// 多行发送的停止条件: "block" (下一个 --- 标记)、"blank" (空行) 或 "eof" (文件末尾)
+    pub stop: String,
+    /// 不在空行处停止时是否把空行作为回车发送
+    pub send_blank_lines: bool,
+    /// 检查每条 shell 命令的退出状态, 非零时停止发送
+    pub check_status: bool,
+    /// 注释前缀, 注释行不会发送
+    pub comment_prefixes: Vec<String>,
+}
+
+impl Default for SendConfig {
+    fn default() -> Self {
+        Self {
+            char_delay_ms: 50,
+            line_delay_ms: 200,
+            prompt: String::new(),
+            prompt_timeout_ms: 10_000,
+            stop: StopAt::BlockEnd.name().to_string(),
+            send_blank_lines: false,
+            check_status: false,
+            comment_prefixes: Syntax::default().comment_prefixes,
+        }
+    }
+}
+
+impl SendConfig {
+    pub fn options(&self) -> SendOptions {
+        SendOptions {
+            char_delay: Duration::from_millis(self.char_delay_ms),
+            line_delay: Duration::from_millis(self.line_delay_ms),
+            prompt_timeout: Duration::from_millis(self.prompt_timeout_ms),
+            // 无法识别时按默认值处理
+            stop: StopAt::parse(&self.stop).unwrap_or(StopAt::BlockEnd),
+            send_blank_lines: self.send_blank_lines,
+            check_status: self.check_status,
+            syntax: Syntax { comment_prefixes: self.comment_prefixes.clone() },
+            ..SendOptions::default()
+        }
+    }
+
+    pub fn prompt_regex(&self) -> Result<Option<Regex>, regex::Error> {
+        if self.prompt.is_empty() {
+            return Ok(None);
+        }
+        Regex::new(&self.prompt).map(Some)
+    }
+}
+
+/// 危险命令检查, 规则为不区分大小写的正则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuardConfig {
+    pub enabled: bool,
+    pub patterns: Vec<String>,
+    /// 仅对受保护的目标额外检查的规则
+    pub protected_patterns: Vec<String>,
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        let strings = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect();
+        Self {
+            enabled: true,
+            patterns: strings(&[
+                r"\brm\s+-[a-z]*(rf|fr)",
+                r"\bmkfs",
+                r"\berase\b",
+                r"\breboot\b",
+                r"\bmtd\s+write\b",
+                r"\bdd\b.*\bof=",
+            ]),
+            protected_patterns: strings(&[
+                r"\b(shutdown|poweroff|halt)\b",
+                r"\breset\b",
+                r"\bsaveenv\b",
+                r"\b(fdisk|parted)\b",
+                r"\bflash\b",
+            ]),
+        }
+    }
+}
+
+impl GuardConfig {
+    /// 未启用时返回 None
+    pub fn build(&self) -> Result<Option<Guard>, regex::Error> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        Guard::new(&self.patterns, &self.protected_patterns).map(Some)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// 把发送的每一行记录到会话日志
+    pub transcript: bool,
+    /// 日志目录, 为空时使用配置文件所在目录下的 logs
+    pub dir: String,
+    /// 把能读取输出的目标 (串口、TCP、伪终端等) 的输出记录到日志目录
+    pub capture: bool,
+    /// 输出记录每行开头的时间戳: "none"、"wall" (本地时间)、"relative" (距开始的秒数) 或 "both"
+    pub capture_timestamps: String,
+    /// 去掉输出中的 ANSI 控制序列
+    pub capture_strip_ansi: bool,
+    /// 在输出记录中标出每条命令的发送位置
+    pub capture_markers: bool,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            transcript: true,
+            dir: String::new(),
+            capture: false,
+            capture_timestamps: "wall".to_string(),
+            capture_strip_ansi: true,
+            capture_markers: true,
+        }
+    }
+}
+
+impl LogConfig {
+    pub fn dir(&self) -> PathBuf {
+        if !self.dir.is_empty() {
+            return PathBuf::from(&self.dir);
+        }
+        let path = Config::path();
+        path.parent().map(|dir| dir.join("logs")).unwrap_or_else(|| PathBuf::from("logs"))
+    }
+
+    /// 未启用时返回 None
+    pub fn transcript(&self) -> Option<Transcript> {
+        self.transcript.then(|| Transcript::new(self.dir()))
+    }
+
+    /// 本次运行的输出记录文件
+    pub fn capture_file(&self) -> PathBuf {
+        self.dir().join(format!("capture-{}.log", Local::now().format("%Y-%m-%d-%H%M%S")))
+    }
+
+    pub fn capture_options(&self) -> CaptureOptions {
+        CaptureOptions {
+            timestamps: Timestamps::parse(&self.capture_timestamps).unwrap_or(Timestamps::Wall),
+            strip_ansi: self.capture_strip_ansi,
+            markers: self.capture_markers,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerminalConfig {
+    /// 上次在终端窗格中连接的目标名称或规则
+    pub target: String,
+    /// 保留的滚动历史行数
+    pub scrollback: usize,
+}
+
+impl Default for TerminalConfig {
+    fn default() -> Self {
+        Self { target: String::new(), scrollback: DEFAULT_SCROLLBACK }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TargetProfile {
+    /// 目标规则, 如 "serial:/dev/ttyUSB0?baud=115200"
+    pub target: String,
+    pub description: String,
+    /// 受保护的目标 (如生产设备): 额外检查 protected_patterns, 确认时须输入目标名称
+    pub protected: bool,
+    /// 连接该目标时启用的触发器名称
+    pub triggers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TriggerRule {
+    /// 匹配目标输出的正则 (控制序列去掉后匹配)
+    pub pattern: String,
+    /// 回复的内容, 可含 `{Enter}`、`{Esc}`、`{Down}` 等特殊键
+    pub send: String,
+    /// 回复后再发送回车
+    pub enter: bool,
+    /// 每次连接只触发一次, 否则每次出现都回复
+    pub once: bool,
+}
+
+impl Default for TriggerRule {
+    fn default() -> Self {
+        Self { pattern: String::new(), send: String::new(), enter: true, once: false }
+    }
+}
+
+impl Config {
+    /// 配置文件位置: 环境变量 TTY_SENDER_CONFIG 优先, 否则为用户配置目录下的 tty_sender/config.toml
+    pub fn path() -> PathBuf {
+        if let Some(path) = env::var_os("TTY_SENDER_CONFIG") {
+            return PathBuf::from(path);
+        }
+        let base = env::var_os("APPDATA")
+            .or_else(|| env::var_os("XDG_CONFIG_HOME"))
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .unwrap_or_else(|| PathBuf::from("."));
+        base.join("tty_sender").join(CONFIG_FILE_NAME)
+    }
+
+    /// 读取配置, 文件不存在或格式错误时使用默认值
+    pub fn load() -> Config {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    /// 按名称查找命名目标, 找不到时把参数本身当作目标规则
+    pub fn resolve_target<'a>(&'a self, name_or_rule: &'a str) -> &'a str {
+        self.targets
+            .get(name_or_rule)
+            .map(|profile| profile.target.as_str())
+            .unwrap_or(name_or_rule)
+    }
+
+    /// 命名目标启用的触发器; 目标规则或未启用时为空
+    pub fn triggers_for(&self, name_or_rule: &str) -> Result<Vec<Trigger>, TriggerError> {
+        let names = self.targets.get(name_or_rule).map_or(&[][..], |profile| &profile.triggers[..]);
+        names
+            .iter()
+            .map(|name| {
+                let rule = self.triggers.get(name).ok_or_else(|| TriggerError::Unknown(name.clone()))?;
+                Trigger::new(name, rule).map_err(|e| TriggerError::Pattern(name.clone(), e))
+            })
+            .collect()
+    }
+
+    pub fn is_protected(&self, name_or_rule: &str) -> bool {
+        self.targets.get(name_or_rule).is_some_and(|profile| profile.protected)
+    }
+
+    /// 标题与绑定窗口匹配的受保护 `window:` 目标的名称 (标题包含规则中的文字即匹配)
+    pub fn protected_window(&self, title: &str) -> Option<&str> {
+        self.targets.iter().find_map(|(name, profile)| {
+            let pattern = profile.target.strip_prefix("window:")?;
+            (profile.protected && !pattern.is_empty() && title.contains(pattern)).then_some(name.as_str())
+        })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+            [send]
+            char_delay_ms = 0
+
+            [targets.board]
+            target = "serial:COM3?baud=9600"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.language, "auto");
+        assert_eq!(config.keys.send_line, "Ctrl+Enter");
+        let options = config.send.options();
+        assert!(options.char_delay.is_zero());
+        assert_eq!(options.line_delay, Duration::from_millis(200));
+        assert_eq!(options.stop, StopAt::BlockEnd);
+        assert!(!options.check_status);
+        assert_eq!(options.syntax, Syntax::default());
+        assert_eq!(config.resolve_target("board"), "serial:COM3?baud=9600");
+        assert_eq!(config.resolve_target("tcp:host:23"), "tcp:host:23");
+        assert!(!config.is_protected("board"));
+        assert!(config.guard.build().unwrap().is_some());
+    }
+
+    #[test]
+    fn finds_protected_targets() {
+        let config: Config = toml::from_str(
+            r#"
+            [targets.prod]
+            target = "window:prod-console"
+            protected = true
+
+            [targets.lab]
+            target = "window:lab-console"
+            "#,
+        )
+        .unwrap();
+        assert!(config.is_protected("prod"));
+        assert_eq!(config.protected_window("PuTTY - prod-console"), Some("prod"));
+        assert_eq!(config.protected_window("PuTTY - lab-console"), None);
+    }
+
+    #[test]
+    fn enables_triggers_per_target() {
+        let config: Config = toml::from_str(
+            r#"
+            [triggers.autoboot]
+            pattern = "Hit any key to stop autoboot"
+            send = " "
+            enter = false
+            once = true
+
+            [targets.board]
+            target = "serial:COM3?baud=115200"
+            triggers = ["autoboot"]
+
+            [targets.broken]
+            target = "serial:COM4?baud=115200"
+            triggers = ["login"]
+            "#,
+        )
+        .unwrap();
+        let names: Vec<_> = config.triggers_for("board").unwrap().into_iter().map(|trigger| trigger.name).collect();
+        assert_eq!(names, ["autoboot"]);
+        assert!(config.triggers_for("serial:COM3?baud=115200").unwrap().is_empty());
+        assert!(matches!(config.triggers_for("broken"), Err(TriggerError::Unknown(name)) if name == "login"));
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let mut config = Config::default();
+        config.theme.custom.editor_text = Some("#c0c0c0".to_string());
+        let parsed: Config = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(parsed.theme.custom.editor_text.as_deref(), Some("#c0c0c0"));
+        assert_eq!(parsed.layout.message_height, config.layout.message_height);
+    }
+}
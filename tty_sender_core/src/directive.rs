@@ -0,0 +1,393 @@
+//! 发送过程中由引擎执行的指令
+//!
+//! `#!capture 名称 /正则/ [超时]` 等待上一行发送之后的输出匹配正则, 把第一个分组
+//! (没有分组时为整个匹配) 存为变量, 之后的行可以用 `${名称}` 引用。
+//! 正则用 `/` 括起, 其中的 `/` 写作 `\/`; 超时写作 `500ms`、`10s`、`2m`, 不带单位时为秒。
+//!
+//! `#!expect /正则/ [超时]` 检查上一行命令的输出 (不含回显) 中在超时前出现匹配,
+//! `#!expect-not /正则/ [时长]` 检查没有出现匹配, 给出时长时在这段时间内持续观察,
+//! 省略时等到输出停止一会儿再判断 (最长为提示符的超时)。
+//!
+//! `#!repeat 次数` 与 `#!while /正则/ [超时]` 到 `#!end` 之间的行反复执行, `${ITER}` 为从 1 开始的次数;
+//! `#!while` 在每次执行前检查上一行命令的输出 (不含回显) 是否匹配, 不匹配时结束。
+//! 给出超时时等待到出现匹配或超时, 省略时等到输出停止一会儿再判断。
+//!
+//! `#!if /正则/ [超时]` 在上一行命令的输出 (不含回显) 匹配时执行到 `#!else` 或 `#!endif` 为止的行,
+//! 否则执行 `#!else` 与 `#!endif` 之间的行, 超时的含义与 `#!while` 相同。`#!label 名称` 标记位置, `#!goto 名称` 跳到该位置,
+//! 只能跳到同一层或外层 (跳出循环和条件, 不能跳入)。
+//!
+//! `#!check-status on|off` 打开或关闭退出状态检查; `#!on-error 段名` 指定命令失败时
+//! 执行的 `[段名]`, `#!on-error goto 标签` 改为跳到该标签继续执行, `#!on-error stop` 恢复为直接停止。
+
+use crate::macros::MAX_CALL_DEPTH;
+use crate::script::ScriptLine;
+use regex::Regex;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub enum Directive {
+    Capture {
+        name: String,
+        pattern: Regex,
+        /// 为 None 时使用提示符的超时
+        timeout: Option<Duration>,
+    },
+    Expect {
+        pattern: Regex,
+        /// 为 None 时使用提示符的超时
+        timeout: Option<Duration>,
+    },
+    ExpectNot {
+        pattern: Regex,
+        /// 为 None 时等到输出停止一会儿再判断, 与 `#!while` 相同
+        timeout: Option<Duration>,
+    },
+    CheckStatus(bool),
+    OnError(ErrorAction),
+    Repeat(usize),
+    While {
+        pattern: Regex,
+        /// 为 None 时等到输出停止一会儿再判断
+        timeout: Option<Duration>,
+    },
+    /// 结束 `#!repeat` 或 `#!while`
+    End,
+    If {
+        pattern: Regex,
+        /// 为 None 时等到输出停止一会儿再判断
+        timeout: Option<Duration>,
+    },
+    Else,
+    EndIf,
+    Label(String),
+    Goto(String),
+}
+
+/// 命令以非零状态退出时的处理
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorAction {
+    Stop,
+    /// 执行该名称的段, 然后停止
+    Section(String),
+    /// 跳到该标签继续执行, 执行完后仍按失败结束
+    Goto(String),
+}
+
+#[derive(Debug)]
+pub enum DirectiveErrorKind {
+    /// 不认识的指令名称, 多半是拼错了
+    Unknown(String),
+    /// 参数不符合写法, 内容为正确的写法
+    Usage(&'static str),
+    Pattern(regex::Error),
+    Timeout(String),
+    /// 脚本中没有该名称的段
+    Section(String),
+    /// 块的结尾 (如 `#!end`) 前面没有对应的开头
+    Unmatched(&'static str),
+    /// 块没有结尾, 内容为缺少的指令
+    Unclosed(&'static str),
+    /// 从这里跳不到该标签: 不存在, 或在循环和条件的里层
+    Label(String),
+    DuplicateLabel(String),
+    UnknownMacro(String),
+    /// 调用时给出的值的个数与宏的参数个数不同
+    MacroArguments { name: String, expected: usize, found: usize },
+    DuplicateMacro(String),
+    /// 宏直接或间接调用了自身, 或嵌套过深
+    MacroRecursion(String),
+    /// 宏中的 `#!include` 与 `#!call` 互相嵌套超过 MAX_CALL_DEPTH 层
+    NestedTooDeep,
+}
+
+/// file 与 line 是写有指令的位置, file 为 None 表示未保存的编辑内容
+#[derive(Debug)]
+pub struct DirectiveError {
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub kind: DirectiveErrorKind,
+}
+
+impl DirectiveError {
+    /// line 所在的位置, 被包含的行取其原始文件和行号
+    pub fn at(line: &ScriptLine, source: Option<&Path>, kind: DirectiveErrorKind) -> DirectiveError {
+        match &line.origin {
+            Some(origin) => DirectiveError { file: Some(origin.file.clone()), line: origin.line, kind },
+            None => DirectiveError { file: source.map(Path::to_path_buf), line: line.number, kind },
+        }
+    }
+
+    /// "文件:行号" 形式的位置
+    pub fn location(&self) -> String {
+        match &self.file {
+            Some(file) => format!("{}:{}", file.display(), self.line),
+            None => format!("line {}", self.line),
+        }
+    }
+}
+
+impl fmt::Display for DirectiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.location())?;
+        match &self.kind {
+            DirectiveErrorKind::Unknown(name) => write!(f, "unknown directive #!{}", name),
+            DirectiveErrorKind::Usage(usage) => write!(f, "usage: {}", usage),
+            DirectiveErrorKind::Pattern(e) => write!(f, "invalid pattern: {}", e),
+            DirectiveErrorKind::Timeout(value) => write!(f, "invalid timeout: {}", value),
+            DirectiveErrorKind::Section(name) => write!(f, "no section named [{}]", name),
+            DirectiveErrorKind::Unmatched(name) => write!(f, "#!{} without a matching start", name),
+            DirectiveErrorKind::Unclosed(name) => write!(f, "missing #!{}", name),
+            DirectiveErrorKind::Label(name) => write!(f, "label {} cannot be reached from here", name),
+            DirectiveErrorKind::DuplicateLabel(name) => write!(f, "label {} is defined more than once", name),
+            DirectiveErrorKind::UnknownMacro(name) => write!(f, "no macro named {}", name),
+            DirectiveErrorKind::MacroArguments { name, expected, found } => {
+                write!(f, "macro {} takes {} arguments but {} were given", name, expected, found)
+            }
+            DirectiveErrorKind::DuplicateMacro(name) => write!(f, "macro {} is defined more than once", name),
+            DirectiveErrorKind::MacroRecursion(name) => write!(f, "macro {} calls itself or is nested too deeply", name),
+            DirectiveErrorKind::NestedTooDeep => {
+                write!(f, "#!include and #!call are nested more than {} levels deep", MAX_CALL_DEPTH)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DirectiveError {}
+
+const CAPTURE_USAGE: &str = "#!capture NAME /regex/ [timeout]";
+const EXPECT_USAGE: &str = "#!expect /regex/ [timeout]";
+const EXPECT_NOT_USAGE: &str = "#!expect-not /regex/ [duration]";
+const REPEAT_USAGE: &str = "#!repeat COUNT";
+const WHILE_USAGE: &str = "#!while /regex/ [timeout]";
+const IF_USAGE: &str = "#!if /regex/ [timeout]";
+const LABEL_USAGE: &str = "#!label NAME";
+const GOTO_USAGE: &str = "#!goto LABEL";
+const CHECK_STATUS_USAGE: &str = "#!check-status on|off";
+const ON_ERROR_USAGE: &str = "#!on-error SECTION|goto LABEL|stop";
+
+/// 解析引擎执行的指令; 不是这类指令 (如 `#!include`) 时返回 None
+pub fn parse_directive(name: &str, args: &str) -> Option<Result<Directive, DirectiveErrorKind>> {
+    match name {
+        "capture" => Some(parse_capture(args)),
+        "expect" => Some(
+            parse_check(args, EXPECT_USAGE).map(|(pattern, timeout)| Directive::Expect { pattern, timeout }),
+        ),
+        "expect-not" => Some(
+            parse_check(args, EXPECT_NOT_USAGE).map(|(pattern, timeout)| Directive::ExpectNot { pattern, timeout }),
+        ),
+        "repeat" => Some(args.trim().parse().map(Directive::Repeat).map_err(|_| DirectiveErrorKind::Usage(REPEAT_USAGE))),
+        "while" => Some(parse_check(args, WHILE_USAGE).map(|(pattern, timeout)| Directive::While { pattern, timeout })),
+        "end" => Some(parse_keyword(args, Directive::End, "#!end")),
+        "if" => Some(parse_check(args, IF_USAGE).map(|(pattern, timeout)| Directive::If { pattern, timeout })),
+        "else" => Some(parse_keyword(args, Directive::Else, "#!else")),
+        "endif" => Some(parse_keyword(args, Directive::EndIf, "#!endif")),
+        "label" => Some(parse_label(args, LABEL_USAGE).map(Directive::Label)),
+        "goto" => Some(parse_label(args, GOTO_USAGE).map(Directive::Goto)),
+        "check-status" => Some(match args.trim() {
+            "on" => Ok(Directive::CheckStatus(true)),
+            "off" => Ok(Directive::CheckStatus(false)),
+            _ => Err(DirectiveErrorKind::Usage(CHECK_STATUS_USAGE)),
+        }),
+        "on-error" => Some(match args.trim() {
+            "" => Err(DirectiveErrorKind::Usage(ON_ERROR_USAGE)),
+            "stop" => Ok(Directive::OnError(ErrorAction::Stop)),
+            "goto" => Err(DirectiveErrorKind::Usage(ON_ERROR_USAGE)),
+            args => match args.split_once(char::is_whitespace) {
+                Some(("goto", label)) => {
+                    parse_label(label, ON_ERROR_USAGE).map(|label| Directive::OnError(ErrorAction::Goto(label)))
+                }
+                _ => Ok(Directive::OnError(ErrorAction::Section(args.to_string()))),
+            },
+        }),
+        _ => None,
+    }
+}
+
+/// 没有参数的指令
+fn parse_keyword(args: &str, directive: Directive, usage: &'static str) -> Result<Directive, DirectiveErrorKind> {
+    match args.trim() {
+        "" => Ok(directive),
+        _ => Err(DirectiveErrorKind::Usage(usage)),
+    }
+}
+
+/// 标签名为一个不含空白的词
+fn parse_label(args: &str, usage: &'static str) -> Result<String, DirectiveErrorKind> {
+    match args.trim() {
+        label if label.is_empty() || label.contains(char::is_whitespace) => Err(DirectiveErrorKind::Usage(usage)),
+        label => Ok(label.to_string()),
+    }
+}
+
+fn parse_capture(args: &str) -> Result<Directive, DirectiveErrorKind> {
+    let usage = || DirectiveErrorKind::Usage(CAPTURE_USAGE);
+    let (name, rest) = args.split_once(char::is_whitespace).ok_or_else(usage)?;
+    if !is_variable_name(name) {
+        return Err(usage());
+    }
+    let (pattern, timeout) = parse_check(rest, CAPTURE_USAGE)?;
+    Ok(Directive::Capture { name: name.to_string(), pattern, timeout })
+}
+
+/// `/正则/ [超时]`
+fn parse_check(args: &str, usage: &'static str) -> Result<(Regex, Option<Duration>), DirectiveErrorKind> {
+    let (pattern, rest) = split_pattern(args.trim_start()).ok_or(DirectiveErrorKind::Usage(usage))?;
+    let pattern = pattern.map_err(DirectiveErrorKind::Pattern)?;
+    let timeout = match rest.trim() {
+        "" => None,
+        value => Some(parse_duration(value).ok_or_else(|| DirectiveErrorKind::Timeout(value.to_string()))?),
+    };
+    Ok((pattern, timeout))
+}
+
+/// 变量名只含字母、数字和 `_`, 且不以数字开头
+pub(crate) fn is_variable_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// 取出开头 `/.../` 中的正则并编译, 返回正则和其后的文字
+fn split_pattern(text: &str) -> Option<(Result<Regex, regex::Error>, &str)> {
+    let body = text.strip_prefix('/')?;
+    let mut pattern = String::new();
+    let mut chars = body.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '/' => return Some((Regex::new(&pattern), &body[index + 1..])),
+            '\\' => match chars.next() {
+                Some((_, '/')) => pattern.push('/'),
+                Some((_, next)) => {
+                    pattern.push('\\');
+                    pattern.push(next);
+                }
+                None => return None,
+            },
+            _ => pattern.push(c),
+        }
+    }
+    None
+}
+
+/// `500ms`、`10s`、`2m`, 不带单位时为秒
+fn parse_duration(text: &str) -> Option<Duration> {
+    let (number, unit) = text.split_at(text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len()));
+    let number: f64 = number.parse().ok()?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_capture() {
+        let Some(Ok(Directive::Capture { name, pattern, timeout })) =
+            parse_directive("capture", r"IP /inet (\d+(?:\.\d+){3})\/\d+/ 5s")
+        else {
+            panic!("not a capture");
+        };
+        assert_eq!(name, "IP");
+        assert_eq!(&pattern.captures("inet 10.0.0.2/24 brd").unwrap()[1], "10.0.0.2");
+        assert_eq!(timeout, Some(Duration::from_secs(5)));
+
+        assert!(matches!(parse_directive("capture", "MAC /ether (\\S+)/"), Some(Ok(Directive::Capture { timeout: None, .. }))));
+        assert!(matches!(parse_directive("capture", "IP inet"), Some(Err(DirectiveErrorKind::Usage(_)))));
+        assert!(matches!(parse_directive("capture", "1X /a/"), Some(Err(DirectiveErrorKind::Usage(_)))));
+        assert!(matches!(parse_directive("capture", "IP /(/"), Some(Err(DirectiveErrorKind::Pattern(_)))));
+        assert!(matches!(parse_directive("capture", "IP /a/ soon"), Some(Err(DirectiveErrorKind::Timeout(_)))));
+        assert!(parse_directive("include", "common.txt").is_none());
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2m"), Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn parses_expect() {
+        assert!(matches!(parse_directive("expect", "/ok/ 2s"), Some(Ok(Directive::Expect { timeout: Some(_), .. }))));
+        assert!(matches!(parse_directive("expect", "/ok/"), Some(Ok(Directive::Expect { timeout: None, .. }))));
+        assert!(matches!(parse_directive("expect", "ok"), Some(Err(DirectiveErrorKind::Usage(_)))));
+    }
+
+    #[test]
+    fn parses_expect_not() {
+        assert!(matches!(parse_directive("expect-not", "/error/ 1s"), Some(Ok(Directive::ExpectNot { timeout: Some(_), .. }))));
+        assert!(matches!(parse_directive("expect-not", "/error/i"), Some(Err(DirectiveErrorKind::Timeout(_)))));
+        assert!(matches!(parse_directive("expect-not", "error"), Some(Err(DirectiveErrorKind::Usage(EXPECT_NOT_USAGE)))));
+    }
+
+    #[test]
+    fn parses_repeat() {
+        assert!(matches!(parse_directive("repeat", " 500"), Some(Ok(Directive::Repeat(500)))));
+        assert!(matches!(parse_directive("repeat", "-1"), Some(Err(DirectiveErrorKind::Usage(REPEAT_USAGE)))));
+        assert!(matches!(parse_directive("repeat", ""), Some(Err(DirectiveErrorKind::Usage(REPEAT_USAGE)))));
+        assert!(matches!(parse_directive("end", ""), Some(Ok(Directive::End))));
+        assert!(matches!(parse_directive("end", "repeat"), Some(Err(DirectiveErrorKind::Usage("#!end")))));
+    }
+
+    #[test]
+    fn parses_while() {
+        assert!(matches!(
+            parse_directive("while", "/busy/ 3"),
+            Some(Ok(Directive::While { timeout: Some(timeout), .. })) if timeout == Duration::from_secs(3)
+        ));
+        assert!(matches!(parse_directive("while", " /busy/"), Some(Ok(Directive::While { timeout: None, .. }))));
+        assert!(matches!(parse_directive("while", "busy"), Some(Err(DirectiveErrorKind::Usage(WHILE_USAGE)))));
+        assert!(matches!(parse_directive("while", "/busy/ later"), Some(Err(DirectiveErrorKind::Timeout(_)))));
+    }
+
+    #[test]
+    fn parses_if() {
+        assert!(matches!(parse_directive("if", "/U-Boot/"), Some(Ok(Directive::If { timeout: None, .. }))));
+        assert!(matches!(
+            parse_directive("if", "/=> $/ 500ms"),
+            Some(Ok(Directive::If { timeout: Some(timeout), .. })) if timeout == Duration::from_millis(500)
+        ));
+        assert!(matches!(parse_directive("if", "U-Boot"), Some(Err(DirectiveErrorKind::Usage(IF_USAGE)))));
+        assert!(matches!(parse_directive("if", "/(/"), Some(Err(DirectiveErrorKind::Pattern(_)))));
+    }
+
+    #[test]
+    fn parses_else() {
+        assert!(matches!(parse_directive("else", ""), Some(Ok(Directive::Else))));
+        assert!(matches!(parse_directive("else", "x"), Some(Err(DirectiveErrorKind::Usage("#!else")))));
+        assert!(matches!(parse_directive("endif", " "), Some(Ok(Directive::EndIf))));
+        assert!(matches!(parse_directive("endif", "x"), Some(Err(DirectiveErrorKind::Usage("#!endif")))));
+    }
+
+    #[test]
+    fn parses_label_and_goto() {
+        assert!(matches!(parse_directive("label", " done "), Some(Ok(Directive::Label(label))) if label == "done"));
+        assert!(matches!(parse_directive("label", "a b"), Some(Err(DirectiveErrorKind::Usage(LABEL_USAGE)))));
+        assert!(matches!(parse_directive("goto", "done"), Some(Ok(Directive::Goto(label))) if label == "done"));
+        assert!(matches!(parse_directive("goto", ""), Some(Err(DirectiveErrorKind::Usage(GOTO_USAGE)))));
+    }
+
+    #[test]
+    fn parses_check_status() {
+        assert!(matches!(parse_directive("check-status", "on"), Some(Ok(Directive::CheckStatus(true)))));
+        assert!(matches!(parse_directive("check-status", "off"), Some(Ok(Directive::CheckStatus(false)))));
+        assert!(matches!(parse_directive("check-status", "yes"), Some(Err(DirectiveErrorKind::Usage(_)))));
+    }
+
+    #[test]
+    fn parses_on_error() {
+        assert!(matches!(
+            parse_directive("on-error", " cleanup "),
+            Some(Ok(Directive::OnError(ErrorAction::Section(name)))) if name == "cleanup"
+        ));
+        assert!(matches!(parse_directive("on-error", "stop"), Some(Ok(Directive::OnError(ErrorAction::Stop)))));
+        assert!(matches!(
+            parse_directive("on-error", "goto  cleanup"),
+            Some(Ok(Directive::OnError(ErrorAction::Goto(label)))) if label == "cleanup"
+        ));
+        assert!(matches!(parse_directive("on-error", "goto a b"), Some(Err(DirectiveErrorKind::Usage(ON_ERROR_USAGE)))));
+        assert!(matches!(parse_directive("on-error", ""), Some(Err(DirectiveErrorKind::Usage(ON_ERROR_USAGE)))));
+    }
+}
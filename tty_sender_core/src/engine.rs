@@ -0,0 +1,1708 @@
+use crate::capture::{strip_ansi, Capture};
+use crate::directive::{parse_directive, Directive, DirectiveError, DirectiveErrorKind, ErrorAction};
+use crate::keys::key_units;
+use crate::guard::{Guard, GuardHit};
+use crate::include::{expand_include, IncludeError};
+use crate::macros::{Macros, MAX_CALL_DEPTH};
+use crate::results::{StepResult, StepStatus, TestRun};
+use crate::script::{section_lines, LineKind, ScriptLine, Syntax};
+use crate::status::{status_pattern, Prober};
+use crate::target::{OutputBuffer, Target};
+use crate::transcript::{Outcome, Record, Transcript};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 多行发送在何处停止; 注释行和分段标记本身从不发送
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopAt {
+    /// 遇到空行时停止
+    Blank,
+    /// 遇到下一个 `--- 步骤 ---` 标记或 `[名称]` 段开头时停止, 开头的标记会被跳过
+    BlockEnd,
+    /// 发送到最后一行
+    EndOfInput,
+}
+
+impl StopAt {
+    pub fn parse(value: &str) -> Option<StopAt> {
+        match value {
+            "blank" => Some(StopAt::Blank),
+            "block" => Some(StopAt::BlockEnd),
+            "eof" => Some(StopAt::EndOfInput),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StopAt::Blank => "blank",
+            StopAt::BlockEnd => "block",
+            StopAt::EndOfInput => "eof",
+        }
+    }
+}
+
+/// 发送节奏与停止条件, GUI 与命令行共用
+#[derive(Debug, Clone)]
+pub struct SendOptions {
+    /// 每个字符之间的间隔, 为 0 时整行一次写入
+    pub char_delay: Duration,
+    /// 每行发送后的间隔
+    pub line_delay: Duration,
+    pub stop: StopAt,
+    /// 不在空行处停止时, 把空行作为单独的回车发送 (如 heredoc 中的空行); 否则跳过
+    pub send_blank_lines: bool,
+    pub syntax: Syntax,
+    /// 每行发送后等待目标输出匹配该提示符再继续, 仅对能读取输出的目标有效
+    pub prompt: Option<Regex>,
+    pub prompt_timeout: Duration,
+    /// 在每条 shell 命令后附加探测, 检查退出状态, 非零时停止; 仅对能读取输出的目标有效
+    pub check_status: bool,
+}
+
+impl Default for SendOptions {
+    fn default() -> Self {
+        Self {
+            char_delay: Duration::from_millis(50),
+            line_delay: Duration::from_millis(200),
+            stop: StopAt::BlockEnd,
+            send_blank_lines: false,
+            syntax: Syntax::default(),
+            prompt: None,
+            prompt_timeout: Duration::from_secs(10),
+            check_status: false,
+        }
+    }
+}
+
+pub enum SendEvent<'a> {
+    /// 即将发送某行, text 为变量替换后的内容
+    Sending { line: &'a ScriptLine, text: &'a str },
+    Sent { line: &'a ScriptLine },
+    /// `#!capture` 从输出中取得了变量的值
+    Captured { line: &'a ScriptLine, name: &'a str, value: &'a str },
+    /// 命令或检查失败, 即将执行 `#!on-error` 指定的段
+    ErrorHandler { line: &'a ScriptLine, error: &'a SendError, section: &'a str },
+    /// 命令或检查失败, 即将按 `#!on-error goto` 跳到该标签
+    ErrorGoto { line: &'a ScriptLine, error: &'a SendError, label: &'a str },
+    /// 循环开始第 iteration 次 (从 1 开始), total 为 `#!repeat` 的次数, `#!while` 为 None
+    Iteration { line: &'a ScriptLine, iteration: usize, total: Option<usize> },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SendReport {
+    pub sent: usize,
+    pub aborted: bool,
+    /// 写会话记录时遇到的第一个错误
+    pub transcript_error: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum SendError {
+    Io { line: usize, source: io::Error },
+    PromptTimeout { line: usize },
+    /// 等待提示符时目标的输出已结束, 例如启动的程序已退出
+    OutputClosed { line: usize },
+    /// 目标不能读取输出, 无法执行该行的指令
+    NoOutput { line: usize },
+    /// `#!capture` 超时仍未在输出中找到匹配
+    CaptureTimeout { line: usize, name: String },
+    /// 检查退出状态时超时仍未读到状态
+    StatusTimeout { line: usize },
+    /// 命令以非零状态退出; 有出错处理段时它已执行完
+    ExitStatus { line: usize, text: String, status: i32 },
+    /// `#!expect` 超时仍未在输出中找到匹配
+    ExpectFailed { line: usize, pattern: String },
+    /// `#!expect-not` 在输出中找到了匹配, text 为匹配的文字
+    UnexpectedOutput { line: usize, pattern: String, text: String },
+    /// 向回跳到标签的次数超过 MAX_JUMPS_BACK, line 为标签所在的行
+    TooManyJumps { line: usize, label: String },
+    /// 展开 `#!include` 或解析指令失败, 此时还没有发送任何内容
+    Script(ScriptError),
+    /// 有命中危险命令规则的行而未经确认, 此时还没有发送任何内容;
+    /// 由 `#!capture` 取得的值引起时, 之前的行已经发送
+    Unconfirmed(Vec<GuardHit>),
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Io { line, source } => write!(f, "line {}: {}", line, source),
+            SendError::PromptTimeout { line } => write!(f, "line {}: timed out waiting for prompt", line),
+            SendError::OutputClosed { line } => write!(f, "line {}: target output closed", line),
+            SendError::NoOutput { line } => write!(f, "line {}: the target's output cannot be read", line),
+            SendError::CaptureTimeout { line, name } => write!(f, "line {}: timed out capturing {}", line, name),
+            SendError::StatusTimeout { line } => write!(f, "line {}: timed out waiting for exit status", line),
+            SendError::ExitStatus { line, text, status } => {
+                write!(f, "line {}: exited with status {}: {}", line, status, text)
+            }
+            SendError::ExpectFailed { line, pattern } => write!(f, "line {}: expected output /{}/ not found", line, pattern),
+            SendError::UnexpectedOutput { line, pattern, text } => {
+                write!(f, "line {}: unexpected output /{}/: {}", line, pattern, text)
+            }
+            SendError::TooManyJumps { line, label } => {
+                write!(f, "line {}: jumped back to label {} more than {} times", line, label, MAX_JUMPS_BACK)
+            }
+            SendError::Script(e) => write!(f, "{}", e),
+            SendError::Unconfirmed(hits) => {
+                let lines: Vec<_> = hits.iter().map(|hit| hit.line.number.to_string()).collect();
+                write!(f, "dangerous commands on lines {} need confirmation", lines.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+impl SendError {
+    /// 目标的回应不符合要求 (命令失败或检查不通过), 而不是发送本身出错;
+    /// 只有这类错误会执行 `#!on-error` 指定的段
+    pub fn is_failure(&self) -> bool {
+        matches!(self, SendError::ExitStatus { .. } | SendError::ExpectFailed { .. } | SendError::UnexpectedOutput { .. })
+    }
+
+    /// 出错的行号
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            SendError::Io { line, .. }
+            | SendError::PromptTimeout { line }
+            | SendError::OutputClosed { line }
+            | SendError::NoOutput { line }
+            | SendError::CaptureTimeout { line, .. }
+            | SendError::StatusTimeout { line }
+            | SendError::ExitStatus { line, .. }
+            | SendError::ExpectFailed { line, .. }
+            | SendError::UnexpectedOutput { line, .. }
+            | SendError::TooManyJumps { line, .. } => Some(*line),
+            SendError::Script(_) | SendError::Unconfirmed(_) => None,
+        }
+    }
+}
+
+/// 发送前展开和检查脚本时的错误
+#[derive(Debug)]
+pub enum ScriptError {
+    Include(IncludeError),
+    Directive(DirectiveError),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Include(e) => write!(f, "{}", e),
+            ScriptError::Directive(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<IncludeError> for ScriptError {
+    fn from(error: IncludeError) -> Self {
+        ScriptError::Include(error)
+    }
+}
+
+impl From<DirectiveError> for ScriptError {
+    fn from(error: DirectiveError) -> Self {
+        ScriptError::Directive(error)
+    }
+}
+
+/// 循环中的次数变量
+const ITER: &str = "ITER";
+
+/// 一次发送中向回跳转的最多次数, 超过时认为陷入了死循环
+pub const MAX_JUMPS_BACK: usize = 10_000;
+
+/// 条件没有给出超时时, 输出停止这么久之后才判断
+const SETTLE: Duration = Duration::from_millis(500);
+
+/// 实际执行的一步; 发送的行在发送时才替换变量, 以便使用之前捕获的值
+#[derive(Debug, Clone)]
+enum Step {
+    Send(ScriptLine),
+    Run(ScriptLine, Directive),
+    /// `#!repeat` 或 `#!while` 所在的行、该指令和循环体
+    Loop(ScriptLine, Directive, Vec<Step>),
+    /// `#!if` 所在的行、条件、等待的超时、匹配时和不匹配时执行的步骤
+    If(ScriptLine, Regex, Option<Duration>, Vec<Step>, Vec<Step>),
+}
+
+impl Step {
+    fn line(&self) -> &ScriptLine {
+        match self {
+            Step::Send(line) | Step::Run(line, _) | Step::Loop(line, ..) | Step::If(line, ..) => line,
+        }
+    }
+
+    /// 需要读取目标输出的步骤所在的行
+    fn reads_output(&self, check_status: bool) -> Option<&ScriptLine> {
+        match self {
+            Step::Send(line) if check_status => Some(line),
+            Step::Run(
+                line,
+                Directive::Capture { .. } | Directive::Expect { .. } | Directive::ExpectNot { .. } | Directive::CheckStatus(true),
+            )
+            | Step::Loop(line, Directive::While { .. }, _)
+            | Step::If(line, ..) => Some(line),
+            Step::Loop(_, _, body) => body.iter().find_map(|step| step.reads_output(check_status)),
+            _ => None,
+        }
+    }
+
+    /// 记入测试结果时的种类, 其余指令不记录
+    fn kind(&self) -> Option<&'static str> {
+        match self {
+            Step::Send(_) => Some("send"),
+            Step::Run(_, Directive::Capture { .. }) => Some("capture"),
+            Step::Run(_, Directive::Expect { .. }) => Some("expect"),
+            Step::Run(_, Directive::ExpectNot { .. }) => Some("expect-not"),
+            Step::Run(..) | Step::Loop(..) | Step::If(..) => None,
+        }
+    }
+}
+
+/// 构建步骤时尚未结束的循环或条件
+struct OpenBlock {
+    line: ScriptLine,
+    directive: Directive,
+    body: Vec<Step>,
+    /// 遇到 `#!else` 后, `#!if` 匹配时执行的步骤
+    then: Option<Vec<Step>>,
+}
+
+/// 执行一组步骤的结果
+enum Flow {
+    Completed,
+    Aborted,
+    /// 跳到该标签; 标签不在这一层时交给外层
+    Goto(String),
+}
+
+/// 一次发送过程中随执行变化的状态
+struct Session<'a> {
+    target: &'a mut dyn Target,
+    output: Option<OutputBuffer>,
+    target_name: String,
+    /// 查找出错处理段的完整脚本
+    document: &'a [ScriptLine],
+    variables: HashMap<String, String>,
+    /// 上一行发送前的输出位置, `#!capture` 从这里开始查找
+    last_mark: Option<usize>,
+    /// 上一行实际发送的内容, `#!expect` 查找时跳过其回显
+    last_sent: String,
+    check_status: bool,
+    prober: Prober,
+    on_error: ErrorAction,
+    /// 按 `#!on-error goto` 跳转前的失败, 执行完后仍以它结束
+    failure: Option<SendError>,
+    /// 已经向回跳转的次数
+    jumps_back: usize,
+    report: SendReport,
+    on_event: &'a mut dyn FnMut(SendEvent),
+}
+
+/// 等待输出匹配的结果
+enum Waited {
+    Found(String),
+    TimedOut,
+    Aborted,
+}
+
+pub struct Engine {
+    pub options: SendOptions,
+    pub variables: HashMap<String, String>,
+    /// 被发送的行所在的文件, `#!include` 的相对路径以它为准
+    pub source: Option<PathBuf>,
+    /// 危险命令检查, 为 None 时不检查
+    pub guard: Option<Guard>,
+    /// 目标是否受保护
+    pub protected: bool,
+    /// 用户已确认发送危险命令
+    pub confirmed: bool,
+    /// 完整的脚本, `#!on-error` 在其中查找出错处理段; 为空时在发送的行中查找
+    pub document: Vec<ScriptLine>,
+    /// 会话记录, 为 None 时不记录
+    pub transcript: Option<Transcript>,
+    /// 目标输出记录, 发送每行前在其中插入标记
+    pub capture: Option<Capture>,
+    /// 最近一次发送中每一步的结果
+    results: Mutex<TestRun>,
+    abort_flag: Arc<AtomicBool>,
+}
+
+impl Engine {
+    pub fn new(options: SendOptions, abort_flag: Arc<AtomicBool>) -> Self {
+        Self {
+            options,
+            variables: HashMap::new(),
+            source: None,
+            guard: None,
+            protected: false,
+            confirmed: false,
+            document: Vec::new(),
+            transcript: None,
+            capture: None,
+            results: Mutex::new(TestRun::default()),
+            abort_flag,
+        }
+    }
+
+    /// 替换 ${NAME}; 未定义的变量原样保留, 以免破坏 shell 自身的变量引用
+    pub fn expand(&self, text: &str) -> String {
+        expand_variables(text, &self.variables)
+    }
+
+    /// 按停止条件选出实际会发送的行及替换后的文本, 用于预览和 --dry-run;
+    /// `#!capture` 等运行时才有值的变量保留 `${名称}` 原样
+    pub fn plan(&self, lines: &[ScriptLine]) -> Result<Vec<(ScriptLine, String)>, ScriptError> {
+        Ok(self.sent_lines(&self.steps(lines, self.options.stop, self.document(lines))?))
+    }
+
+    fn document<'a>(&'a self, lines: &'a [ScriptLine]) -> &'a [ScriptLine] {
+        if self.document.is_empty() {
+            lines
+        } else {
+            &self.document
+        }
+    }
+
+    /// 循环体只列出一次, `${ITER}` 保留原样; 条件的两个分支都列出
+    fn sent_lines(&self, steps: &[Step]) -> Vec<(ScriptLine, String)> {
+        steps
+            .iter()
+            .flat_map(|step| match step {
+                Step::Send(line) => vec![(line.clone(), self.expand(&line.text))],
+                Step::Loop(_, _, body) => self.sent_lines(body),
+                Step::If(_, _, _, then, otherwise) => [self.sent_lines(then), self.sent_lines(otherwise)].concat(),
+                Step::Run(..) => Vec::new(),
+            })
+            .collect()
+    }
+
+    /// 按停止条件选出要执行的行和指令; `#!include` 和 `#!call` 在这里展开,
+    /// 展开的内容整体插入, 其中的空行和标记不会结束发送; 循环和条件中间也不会结束
+    fn steps(&self, lines: &[ScriptLine], stop: StopAt, document: &[ScriptLine]) -> Result<Vec<Step>, ScriptError> {
+        let syntax = &self.options.syntax;
+        // 宏可以定义在发送范围之外, 定义从完整的脚本中取出; 发送的行按在完整脚本中的位置去掉定义,
+        // 因此发送范围从定义中间开始或结束时也不会出错
+        let (mut macros, outside) = Macros::split(document, syntax, self.source.as_deref())?;
+        let outside: HashSet<_> = outside.iter().map(|line| (line.number, &line.origin)).collect();
+        let defined: HashSet<_> =
+            document.iter().map(|line| (line.number, &line.origin)).filter(|position| !outside.contains(position)).collect();
+        let mut steps = Vec::new();
+        let mut blocks = Vec::new();
+        for line in lines.iter().filter(|line| !defined.contains(&(line.number, &line.origin))) {
+            match syntax.classify(&line.text) {
+                LineKind::Blank if stop == StopAt::Blank && blocks.is_empty() => break,
+                // 已发送过内容才结束, 因此从上一段末尾继续发送时会进入下一段
+                LineKind::Marker(_) | LineKind::Section(_)
+                    if stop == StopAt::BlockEnd && blocks.is_empty() && !steps.is_empty() =>
+                {
+                    break
+                }
+                _ => self.add_line(&mut steps, &mut blocks, &mut macros, line, document, 0)?,
+            }
+        }
+        if let Some(open) = blocks.pop() {
+            let end = if matches!(open.directive, Directive::If { .. }) { "endif" } else { "end" };
+            return Err(DirectiveError::at(&open.line, self.source.as_deref(), DirectiveErrorKind::Unclosed(end)).into());
+        }
+        self.check_labels(&steps, &mut HashSet::new(), &mut Vec::new())?;
+        Ok(steps)
+    }
+
+    /// 展开 `#!include` 和 `#!call` 后逐行加入; 被包含的文件中的宏定义在这里取出,
+    /// depth 为宏中的 `#!include` 再调用宏的层数
+    fn add_line(
+        &self,
+        steps: &mut Vec<Step>,
+        blocks: &mut Vec<OpenBlock>,
+        macros: &mut Macros,
+        line: &ScriptLine,
+        document: &[ScriptLine],
+        depth: usize,
+    ) -> Result<(), ScriptError> {
+        let syntax = &self.options.syntax;
+        let source = self.source.as_deref();
+        let expanded = match syntax.classify(&line.text) {
+            LineKind::Directive { name: "include", args } => {
+                let included = expand_include(&self.expand(args), line, source, syntax)?;
+                let (defined, included) = Macros::split(&included, syntax, source)?;
+                macros.extend(defined, source)?;
+                included
+            }
+            LineKind::Directive { name: "call", args } => macros.expand_call(args, line, syntax, source)?,
+            _ => return Ok(self.add_step(steps, blocks, line, document)?),
+        };
+        if depth > MAX_CALL_DEPTH {
+            return Err(DirectiveError::at(line, source, DirectiveErrorKind::NestedTooDeep).into());
+        }
+        for expanded in &expanded {
+            self.add_line(steps, blocks, macros, expanded, document, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// 把一行加入最内层的循环、条件或顶层; 空行按 send_blank_lines 发送或跳过, 注释和标记跳过
+    fn add_step(
+        &self,
+        steps: &mut Vec<Step>,
+        blocks: &mut Vec<OpenBlock>,
+        line: &ScriptLine,
+        document: &[ScriptLine],
+    ) -> Result<(), DirectiveError> {
+        let unmatched = |line: &ScriptLine, name| DirectiveError::at(line, self.source.as_deref(), DirectiveErrorKind::Unmatched(name));
+        let step = match self.options.syntax.classify(&line.text) {
+            LineKind::Command => Step::Send(line.clone()),
+            LineKind::Blank if self.options.send_blank_lines => Step::Send(line.clone()),
+            LineKind::Directive { name, args } => match self.directive_step(line, name, args, document)? {
+                Step::Run(line, directive @ (Directive::Repeat(_) | Directive::While { .. } | Directive::If { .. })) => {
+                    blocks.push(OpenBlock { line, directive, body: Vec::new(), then: None });
+                    return Ok(());
+                }
+                Step::Run(line, Directive::Else) => match blocks.last_mut() {
+                    Some(open) if matches!(open.directive, Directive::If { .. }) && open.then.is_none() => {
+                        open.then = Some(std::mem::take(&mut open.body));
+                        return Ok(());
+                    }
+                    _ => return Err(unmatched(&line, "else")),
+                },
+                Step::Run(end, Directive::End) => match blocks.pop() {
+                    Some(OpenBlock { line, directive: directive @ (Directive::Repeat(_) | Directive::While { .. }), body, .. }) => {
+                        Step::Loop(line, directive, body)
+                    }
+                    _ => return Err(unmatched(&end, "end")),
+                },
+                Step::Run(end, Directive::EndIf) => match blocks.pop() {
+                    Some(OpenBlock { line, directive: Directive::If { pattern, timeout }, body, then: Some(then) }) => {
+                        Step::If(line, pattern, timeout, then, body)
+                    }
+                    Some(OpenBlock { line, directive: Directive::If { pattern, timeout }, body, then: None }) => {
+                        Step::If(line, pattern, timeout, body, Vec::new())
+                    }
+                    _ => return Err(unmatched(&end, "endif")),
+                },
+                step => step,
+            },
+            _ => return Ok(()),
+        };
+        match blocks.last_mut() {
+            Some(open) => open.body.push(step),
+            None => steps.push(step),
+        }
+        Ok(())
+    }
+
+    /// 检查标签没有重复, 且 `#!goto` 和 `#!on-error goto` 都能跳到目标:
+    /// visible 为这一层及外层的标签, 只能跳到其中之一
+    fn check_labels<'a>(
+        &self,
+        steps: &'a [Step],
+        seen: &mut HashSet<&'a str>,
+        visible: &mut Vec<&'a str>,
+    ) -> Result<(), DirectiveError> {
+        let error = |line, kind| DirectiveError::at(line, self.source.as_deref(), kind);
+        let outer = visible.len();
+        for step in steps {
+            if let Step::Run(line, Directive::Label(label)) = step {
+                if !seen.insert(label) {
+                    return Err(error(line, DirectiveErrorKind::DuplicateLabel(label.clone())));
+                }
+                visible.push(label);
+            }
+        }
+        for step in steps {
+            match step {
+                Step::Run(line, Directive::Goto(label) | Directive::OnError(ErrorAction::Goto(label)))
+                    if !visible.contains(&label.as_str()) =>
+                {
+                    return Err(error(line, DirectiveErrorKind::Label(label.clone())));
+                }
+                Step::Loop(_, _, body) => self.check_labels(body, seen, visible)?,
+                Step::If(_, _, _, then, otherwise) => {
+                    self.check_labels(then, seen, visible)?;
+                    self.check_labels(otherwise, seen, visible)?;
+                }
+                _ => {}
+            }
+        }
+        visible.truncate(outer);
+        Ok(())
+    }
+
+    /// 引擎执行的指令; 不认识的指令 (如拼错的 `#!expct`) 报错, 以免检查被悄悄跳过。
+    /// 参数中的变量用发送前已有的值替换, 出错处理段在这里检查是否存在
+    fn directive_step(
+        &self,
+        line: &ScriptLine,
+        name: &str,
+        args: &str,
+        document: &[ScriptLine],
+    ) -> Result<Step, DirectiveError> {
+        let error = |kind| DirectiveError::at(line, self.source.as_deref(), kind);
+        match parse_directive(name, &self.expand(args)) {
+            Some(Ok(Directive::OnError(ErrorAction::Section(name))))
+                if section_lines(document, &self.options.syntax, &name).is_none() =>
+            {
+                Err(error(DirectiveErrorKind::Section(name)))
+            }
+            Some(Ok(directive)) => Ok(Step::Run(line.clone(), directive)),
+            Some(Err(kind)) => Err(error(kind)),
+            None => Err(error(DirectiveErrorKind::Unknown(name.to_string()))),
+        }
+    }
+
+    /// 将要发送的行中需要确认的危险命令
+    pub fn dangerous_lines(&self, lines: &[ScriptLine]) -> Result<Vec<GuardHit>, ScriptError> {
+        Ok(self.check_guard(&self.plan(lines)?))
+    }
+
+    fn check_guard(&self, planned: &[(ScriptLine, String)]) -> Vec<GuardHit> {
+        match &self.guard {
+            Some(guard) => guard.check(planned, self.protected),
+            None => Vec::new(),
+        }
+    }
+
+    /// 最近一次 run 的测试结果, 出错时也包括出错之前的各步
+    pub fn results(&self) -> TestRun {
+        self.results.lock().unwrap().clone()
+    }
+
+    pub fn run(
+        &self,
+        target: &mut dyn Target,
+        lines: &[ScriptLine],
+        on_event: &mut dyn FnMut(SendEvent),
+    ) -> Result<SendReport, SendError> {
+        *self.results.lock().unwrap() = TestRun::default();
+        let started = Instant::now();
+        let result = self.run_script(target, lines, on_event);
+        self.results.lock().unwrap().duration = started.elapsed();
+        result
+    }
+
+    fn run_script(
+        &self,
+        target: &mut dyn Target,
+        lines: &[ScriptLine],
+        on_event: &mut dyn FnMut(SendEvent),
+    ) -> Result<SendReport, SendError> {
+        let document = self.document(lines);
+        let steps = self.steps(lines, self.options.stop, document).map_err(SendError::Script)?;
+        let planned = self.sent_lines(&steps);
+        if !self.confirmed {
+            let hits = self.check_guard(&planned);
+            if !hits.is_empty() {
+                return Err(SendError::Unconfirmed(hits));
+            }
+        }
+        let output = target.output();
+        if output.is_none() {
+            if let Some(line) = steps.iter().find_map(|step| step.reads_output(self.options.check_status)) {
+                return Err(SendError::NoOutput { line: line.number });
+            }
+        }
+        if !steps.is_empty() {
+            target.prepare();
+        }
+        // 先等到第一个提示符 (如 shell 启动或设备开机完成), 否则它可能被误当作第一行的提示符
+        if let (Some(prompt), Some(output), Some((first, _))) = (&self.options.prompt, &output, planned.first()) {
+            if !self.wait_for_prompt(output, prompt, 0, first.number)? {
+                return Ok(SendReport { aborted: true, ..SendReport::default() });
+            }
+        }
+        let mut session = Session {
+            target_name: target.describe(),
+            target,
+            last_mark: output.as_ref().map(OutputBuffer::position),
+            last_sent: String::new(),
+            output,
+            document,
+            variables: self.variables.clone(),
+            check_status: self.options.check_status,
+            prober: Prober::default(),
+            on_error: ErrorAction::Stop,
+            failure: None,
+            jumps_back: 0,
+            report: SendReport::default(),
+            on_event,
+        };
+        let flow = self.execute(&mut session, &steps)?;
+        if let Some(error) = session.failure.take() {
+            return Err(error);
+        }
+        session.report.aborted = matches!(flow, Flow::Aborted);
+        Ok(session.report)
+    }
+
+    /// 依次执行各步; 失败或中止后余下的步骤记为跳过, 跳转时越过的步骤也记为跳过。
+    /// 向回跳转超过 MAX_JUMPS_BACK 次时报错, 以免没有出口的 `#!goto` 一直运行下去
+    fn execute(&self, session: &mut Session, steps: &[Step]) -> Result<Flow, SendError> {
+        let mut index = 0;
+        while let Some(step) = steps.get(index) {
+            if self.aborted() {
+                self.skip(session, &steps[index..]);
+                return Ok(Flow::Aborted);
+            }
+            let flow = match step {
+                Step::Send(line) => self.checked(session, step, |session| self.send_step(session, line)),
+                Step::Run(_, Directive::Goto(label)) => Ok(Flow::Goto(label.clone())),
+                Step::Run(line, directive) => self.checked(session, step, |session| self.run_directive(session, line, directive)),
+                // 循环和条件中的各步自行记录结果, 失败也已经处理过
+                Step::Loop(line, directive, body) => self.run_loop(session, line, directive, body),
+                Step::If(_, pattern, timeout, then, otherwise) => self.run_if(session, pattern, *timeout, then, otherwise),
+            };
+            match flow {
+                Ok(Flow::Completed) => index += 1,
+                Ok(Flow::Goto(label)) => {
+                    let target = steps.iter().position(|step| matches!(step, Step::Run(_, Directive::Label(name)) if *name == label));
+                    match target {
+                        Some(target) => {
+                            if target <= index {
+                                session.jumps_back += 1;
+                                if session.jumps_back > MAX_JUMPS_BACK {
+                                    self.skip(session, &steps[index + 1..]);
+                                    return Err(SendError::TooManyJumps { line: steps[target].line().number, label });
+                                }
+                            }
+                            self.skip(session, steps.get(index + 1..target).unwrap_or_default());
+                            index = target;
+                        }
+                        None => {
+                            self.skip(session, &steps[index + 1..]);
+                            return Ok(Flow::Goto(label));
+                        }
+                    }
+                }
+                other => {
+                    self.skip(session, &steps[index + 1..]);
+                    return other;
+                }
+            }
+        }
+        Ok(Flow::Completed)
+    }
+
+    /// 执行发送的行或指令并记录结果, 失败时按 `#!on-error` 处理
+    fn checked(
+        &self,
+        session: &mut Session,
+        step: &Step,
+        run: impl FnOnce(&mut Session) -> Result<bool, SendError>,
+    ) -> Result<Flow, SendError> {
+        let started = Instant::now();
+        let result = run(session);
+        let status = match &result {
+            Ok(true) => StepStatus::Passed,
+            Ok(false) => StepStatus::Skipped,
+            Err(e) => StepStatus::Failed(e.to_string()),
+        };
+        self.record(session, step, status, started.elapsed());
+        match result {
+            Ok(true) => Ok(Flow::Completed),
+            Ok(false) => Ok(Flow::Aborted),
+            Err(error) => self.handle_failure(session, step.line(), error),
+        }
+    }
+
+    fn run_directive(&self, session: &mut Session, line: &ScriptLine, directive: &Directive) -> Result<bool, SendError> {
+        match directive {
+            Directive::Capture { name, pattern, timeout } => {
+                // 没有输出的目标在开始前已经报错
+                let (output, mark) = session.output.as_ref().zip(session.last_mark).unwrap();
+                let timeout = timeout.unwrap_or(self.options.prompt_timeout);
+                let value = match self.wait_match(output, pattern, mark, Some(&session.last_sent), timeout) {
+                    Waited::Found(value) => value,
+                    Waited::TimedOut if output.is_closed() => return Err(SendError::OutputClosed { line: line.number }),
+                    Waited::TimedOut => return Err(SendError::CaptureTimeout { line: line.number, name: name.clone() }),
+                    Waited::Aborted => return Ok(false),
+                };
+                (session.on_event)(SendEvent::Captured { line, name, value: &value });
+                session.variables.insert(name.clone(), value);
+            }
+            Directive::Expect { pattern, timeout } => {
+                let (output, mark) = session.output.as_ref().zip(session.last_mark).unwrap();
+                let timeout = timeout.unwrap_or(self.options.prompt_timeout);
+                match self.wait_match(output, pattern, mark, Some(&session.last_sent), timeout) {
+                    Waited::Found(_) => {}
+                    Waited::TimedOut if output.is_closed() => return Err(SendError::OutputClosed { line: line.number }),
+                    Waited::TimedOut => {
+                        return Err(SendError::ExpectFailed { line: line.number, pattern: pattern.to_string() })
+                    }
+                    Waited::Aborted => return Ok(false),
+                }
+            }
+            Directive::ExpectNot { pattern, timeout } => {
+                // 省略时长时也要等输出停止, 晚到的输出同样要检查
+                match self.wait_condition(session, pattern, *timeout) {
+                    Waited::Found(text) => {
+                        return Err(SendError::UnexpectedOutput { line: line.number, pattern: pattern.to_string(), text })
+                    }
+                    Waited::TimedOut => {}
+                    Waited::Aborted => return Ok(false),
+                }
+            }
+            Directive::CheckStatus(on) => session.check_status = *on,
+            Directive::OnError(action) => session.on_error = action.clone(),
+            // 循环和条件在构建步骤时已经展开, 跳转在 execute 中处理
+            Directive::Repeat(_)
+            | Directive::While { .. }
+            | Directive::End
+            | Directive::If { .. }
+            | Directive::Else
+            | Directive::EndIf
+            | Directive::Label(_)
+            | Directive::Goto(_) => {}
+        }
+        Ok(true)
+    }
+
+    /// 反复执行循环体, 其中任何一步失败或被中止时结束整个循环; 结束后恢复外层循环的 `${ITER}`
+    fn run_loop(&self, session: &mut Session, line: &ScriptLine, directive: &Directive, body: &[Step]) -> Result<Flow, SendError> {
+        let outer = session.variables.get(ITER).cloned();
+        let total = match directive {
+            Directive::Repeat(count) => Some(*count),
+            _ => None,
+        };
+        let mut iteration = 0;
+        let completed = loop {
+            if total == Some(iteration) {
+                break Ok(Flow::Completed);
+            }
+            if let Directive::While { pattern, timeout } = directive {
+                match self.wait_condition(session, pattern, *timeout) {
+                    Waited::Found(_) => {}
+                    Waited::TimedOut => break Ok(Flow::Completed),
+                    Waited::Aborted => break Ok(Flow::Aborted),
+                }
+            }
+            iteration += 1;
+            session.variables.insert(ITER.to_string(), iteration.to_string());
+            (session.on_event)(SendEvent::Iteration { line, iteration, total });
+            match self.execute(session, body) {
+                Ok(Flow::Completed) => {}
+                other => break other,
+            }
+        };
+        match outer {
+            Some(value) => session.variables.insert(ITER.to_string(), value),
+            None => session.variables.remove(ITER),
+        };
+        completed
+    }
+
+    /// 按上一行命令的输出选择分支, 没有执行的分支不记录
+    fn run_if(
+        &self,
+        session: &mut Session,
+        pattern: &Regex,
+        timeout: Option<Duration>,
+        then: &[Step],
+        otherwise: &[Step],
+    ) -> Result<Flow, SendError> {
+        let branch = match self.wait_condition(session, pattern, timeout) {
+            Waited::Found(_) => then,
+            Waited::TimedOut => otherwise,
+            Waited::Aborted => return Ok(Flow::Aborted),
+        };
+        self.execute(session, branch)
+    }
+
+    /// 上一行命令的输出 (不含回显) 是否匹配: 给出超时时等到匹配或超时,
+    /// 否则等到输出停止 SETTLE 后判断 (最长为提示符的超时), 以免命令的输出还没有到达
+    fn wait_condition(&self, session: &Session, pattern: &Regex, timeout: Option<Duration>) -> Waited {
+        // 没有输出的目标在开始前已经报错
+        let (output, mark) = session.output.as_ref().zip(session.last_mark).unwrap();
+        let echo = Some(session.last_sent.as_str());
+        if let Some(timeout) = timeout {
+            return self.wait_match(output, pattern, mark, echo, timeout);
+        }
+        let deadline = Instant::now() + self.options.prompt_timeout;
+        loop {
+            let position = output.position();
+            let slice = SETTLE.min(deadline.saturating_duration_since(Instant::now()));
+            match self.wait_match(output, pattern, mark, echo, slice) {
+                Waited::TimedOut if output.position() != position && Instant::now() < deadline => {}
+                waited => return waited,
+            }
+        }
+    }
+
+    /// 发送一行, 记录结果并等待退出状态和提示符; 被中止时返回 false
+    fn send_step(&self, session: &mut Session, line: &ScriptLine) -> Result<bool, SendError> {
+        let text = expand_variables(&line.text, &session.variables);
+        // 捕获的值可能构成危险命令, 发送前再检查一次
+        if !self.confirmed {
+            let hits = self.check_guard(&[(line.clone(), text.clone())]);
+            if !hits.is_empty() {
+                return Err(SendError::Unconfirmed(hits));
+            }
+        }
+        // 即使不检查也要看到每一行, 以便之后打开检查时知道是否处在复合命令中间
+        let probed = session.prober.probe(&text).filter(|_| session.check_status);
+        session.last_mark = session.output.as_ref().map(OutputBuffer::position);
+        (session.on_event)(SendEvent::Sending { line, text: &text });
+        if let Some(capture) = &self.capture {
+            capture.mark(line.number, &text);
+        }
+        session.last_sent = probed.clone().unwrap_or_else(|| text.clone());
+        let completed = match self.send_line(session.target, &session.last_sent) {
+            Ok(completed) => completed,
+            Err(source) => {
+                self.log(session, line, &text, Outcome::Failed(source.to_string()));
+                return Err(SendError::Io { line: line.number, source });
+            }
+        };
+        if !completed {
+            self.log(session, line, &text, Outcome::Aborted);
+            return Ok(false);
+        }
+        session.report.sent += 1;
+        (session.on_event)(SendEvent::Sent { line });
+
+        // 先读退出状态, 提示符在它之后输出
+        let mut status = None;
+        let mut waited = Ok(true);
+        if let (Some(_), Some(output), Some(mark)) = (&probed, &session.output, session.last_mark) {
+            let timeout = self.options.prompt_timeout;
+            waited = match self.wait_match(output, status_pattern(), mark, None, timeout) {
+                Waited::Found(value) => {
+                    status = value.parse().ok();
+                    Ok(true)
+                }
+                Waited::TimedOut if output.is_closed() => Err(SendError::OutputClosed { line: line.number }),
+                Waited::TimedOut => Err(SendError::StatusTimeout { line: line.number }),
+                Waited::Aborted => Ok(false),
+            };
+        }
+        if let (Ok(true), Some(prompt), Some(output), Some(mark)) =
+            (&waited, &self.options.prompt, &session.output, session.last_mark)
+        {
+            waited = self.wait_for_prompt(output, prompt, mark, line.number);
+        }
+        let failed = status.filter(|&status| status != 0);
+        let outcome = match &waited {
+            Err(SendError::PromptTimeout { .. }) => Outcome::PromptTimeout,
+            Err(SendError::OutputClosed { .. }) => Outcome::OutputClosed,
+            Err(e) => Outcome::Failed(e.to_string()),
+            Ok(_) => failed.map_or(Outcome::Sent, Outcome::ExitStatus),
+        };
+        self.log(session, line, &text, outcome);
+        if !waited? {
+            return Ok(false);
+        }
+        if let Some(status) = failed {
+            return Err(SendError::ExitStatus { line: line.number, text, status });
+        }
+        thread::sleep(self.options.line_delay);
+        Ok(true)
+    }
+
+    /// 命令或检查失败时先执行 `#!on-error` 指定的段 (其中不再检查退出状态), 然后结束发送,
+    /// 或跳到 `#!on-error goto` 指定的标签继续; 其他错误直接结束。
+    /// 跳转后即使执行完也不算成功, 整个发送仍以这次失败结束
+    fn handle_failure(&self, session: &mut Session, line: &ScriptLine, error: SendError) -> Result<Flow, SendError> {
+        if !error.is_failure() {
+            return Err(error);
+        }
+        match std::mem::replace(&mut session.on_error, ErrorAction::Stop) {
+            ErrorAction::Section(name) => {
+                (session.on_event)(SendEvent::ErrorHandler { line, error: &error, section: &name });
+                // 段是否存在在开始前已经检查
+                let lines = section_lines(session.document, &self.options.syntax, &name).unwrap_or_default();
+                let steps = self.steps(&lines, StopAt::EndOfInput, session.document).map_err(SendError::Script)?;
+                session.check_status = false;
+                thread::sleep(self.options.line_delay);
+                if let Flow::Aborted = self.execute(session, &steps)? {
+                    return Ok(Flow::Aborted);
+                }
+            }
+            ErrorAction::Goto(label) => {
+                (session.on_event)(SendEvent::ErrorGoto { line, error: &error, label: &label });
+                session.check_status = false;
+                session.failure = Some(error);
+                return Ok(Flow::Goto(label));
+            }
+            ErrorAction::Stop => {}
+        }
+        Err(error)
+    }
+
+    /// 等待 mark 之后的输出 (去掉控制序列后) 匹配 pattern, 取第一个分组或整个匹配;
+    /// 给出 echo 时跳过输出中该命令的回显所在的行
+    fn wait_match(&self, output: &OutputBuffer, pattern: &Regex, mark: usize, echo: Option<&str>, timeout: Duration) -> Waited {
+        // 分段等待, 以便及时响应中止
+        const SLICE: Duration = Duration::from_millis(100);
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.aborted() {
+                return Waited::Aborted;
+            }
+            let (text, end) = output.read_since(mark);
+            let text = strip_ansi(&text);
+            let text = match echo.filter(|echo| !echo.is_empty()) {
+                Some(echo) => skip_echo(&text, echo),
+                None => &text,
+            };
+            if let Some(captures) = pattern.captures(text) {
+                let found = captures.get(1).or_else(|| captures.get(0)).unwrap();
+                return Waited::Found(found.as_str().to_string());
+            }
+            let now = Instant::now();
+            if output.is_closed() || now >= deadline {
+                return Waited::TimedOut;
+            }
+            output.wait_new(end, SLICE.min(deadline - now));
+        }
+    }
+
+    /// 发送一行并回车, 中途被中止时返回 false (不发送回车)
+    fn send_line(&self, target: &mut dyn Target, text: &str) -> io::Result<bool> {
+        if self.options.char_delay.is_zero() {
+            target.send_str(text)?;
+        } else {
+            for unit in key_units(text) {
+                if self.aborted() {
+                    return Ok(false);
+                }
+                target.send_str(unit)?;
+                thread::sleep(self.options.char_delay);
+            }
+        }
+        target.send_enter()?;
+        Ok(true)
+    }
+
+    /// 等待 mark 之后的输出出现提示符, 被中止时返回 false
+    fn wait_for_prompt(
+        &self,
+        output: &OutputBuffer,
+        prompt: &Regex,
+        mark: usize,
+        line: usize,
+    ) -> Result<bool, SendError> {
+        // 分段等待, 以便及时响应中止
+        const SLICE: Duration = Duration::from_millis(100);
+        let mut remaining = self.options.prompt_timeout;
+        loop {
+            if self.aborted() {
+                return Ok(false);
+            }
+            let slice = remaining.min(SLICE);
+            if output.wait_for(prompt, mark, slice).is_some() {
+                return Ok(true);
+            }
+            if output.is_closed() {
+                return Err(SendError::OutputClosed { line });
+            }
+            remaining -= slice;
+            if remaining.is_zero() {
+                return Err(SendError::PromptTimeout { line });
+            }
+        }
+    }
+
+    /// 写入会话记录; 记录失败不影响发送
+    fn log(&self, session: &mut Session, line: &ScriptLine, text: &str, outcome: Outcome) {
+        let transcript = match &self.transcript {
+            Some(transcript) => transcript,
+            None => return,
+        };
+        let (file, number) = self.location(line);
+        let record = Record { target: &session.target_name, file, line: number, text, outcome: &outcome };
+        if let Err(e) = transcript.record(&record) {
+            session.report.transcript_error.get_or_insert_with(|| e.to_string());
+        }
+    }
+
+    /// 记入测试结果, 不属于测试步骤的指令不记录
+    fn record(&self, session: &Session, step: &Step, status: StepStatus, duration: Duration) {
+        let Some(kind) = step.kind() else { return };
+        let line = step.line();
+        let text = match step {
+            Step::Send(_) => expand_variables(&line.text, &session.variables),
+            _ => line.text.trim().to_string(),
+        };
+        let (file, number) = self.location(line);
+        let result = StepResult { file: file.map(Path::to_path_buf), line: number, kind, text, status, duration };
+        self.results.lock().unwrap().steps.push(result);
+    }
+
+    /// 跳过的循环体记录一次
+    fn skip(&self, session: &Session, steps: &[Step]) {
+        for step in steps {
+            match step {
+                Step::Loop(_, _, body) => self.skip(session, body),
+                Step::If(_, _, _, then, otherwise) => {
+                    self.skip(session, then);
+                    self.skip(session, otherwise);
+                }
+                _ => self.record(session, step, StepStatus::Skipped, Duration::ZERO),
+            }
+        }
+    }
+
+    /// 该行真正所在的文件和行号, 被包含的行取其原始位置
+    fn location<'a>(&'a self, line: &'a ScriptLine) -> (Option<&'a Path>, usize) {
+        match &line.origin {
+            Some(origin) => (Some(origin.file.as_path()), origin.line),
+            None => (self.source.as_deref(), line.number),
+        }
+    }
+
+    fn aborted(&self) -> bool {
+        self.abort_flag.load(Ordering::SeqCst)
+    }
+}
+
+/// 去掉输出中命令的回显及其之前的部分; 回显还没有收完 (最后一行是它的开头) 时返回空,
+/// 以免把回显中的文字当作命令的输出。没有回显的目标原样返回
+fn skip_echo<'a>(text: &'a str, echo: &str) -> &'a str {
+    if let Some(start) = text.find(echo) {
+        return text[start..].split_once('\n').map_or("", |(_, rest)| rest);
+    }
+    let last = text.rsplit('\n').next().unwrap_or_default().trim_start_matches('\r');
+    if !last.is_empty() && echo.starts_with(last) {
+        return "";
+    }
+    text
+}
+
+
+pub(crate) fn expand_variables(text: &str, variables: &HashMap<String, String>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        let end = match after.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        result.push_str(&rest[..start]);
+        match variables.get(&after[..end]) {
+            Some(value) => result.push_str(value),
+            None => result.push_str(&rest[start..start + end + 3]),
+        }
+        rest = &after[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::lines_from_text;
+
+    /// 记录收到的内容, 回车记为 '\n'
+    #[derive(Default)]
+    struct Recorder {
+        output: String,
+        prepared: bool,
+    }
+
+    impl Target for Recorder {
+        fn describe(&self) -> String {
+            "recorder".to_string()
+        }
+
+        fn prepare(&mut self) {
+            self.prepared = true;
+        }
+
+        fn send_str(&mut self, text: &str) -> io::Result<()> {
+            self.output.push_str(text);
+            Ok(())
+        }
+
+        fn send_enter(&mut self) -> io::Result<()> {
+            self.output.push('\n');
+            Ok(())
+        }
+    }
+
+    fn engine(stop: StopAt) -> Engine {
+        let options = SendOptions {
+            char_delay: Duration::ZERO,
+            line_delay: Duration::ZERO,
+            stop,
+            ..SendOptions::default()
+        };
+        Engine::new(options, Arc::new(AtomicBool::new(false)))
+    }
+
+    #[test]
+    fn expands_known_variables_only() {
+        let mut engine = engine(StopAt::Blank);
+        engine.variables.insert("HOST".to_string(), "board".to_string());
+        assert_eq!(engine.expand("ssh ${HOST} echo ${HOME} ${"), "ssh board echo ${HOME} ${");
+    }
+
+    #[test]
+    fn stops_or_skips_at_blank_line() {
+        let lines = lines_from_text("a\n  \nb");
+        assert_eq!(engine(StopAt::Blank).plan(&lines).unwrap().len(), 1);
+        let planned = engine(StopAt::EndOfInput).plan(&lines).unwrap();
+        assert_eq!(planned.iter().map(|(line, _)| line.number).collect::<Vec<_>>(), vec![1, 3]);
+    }
+
+    #[test]
+    fn skips_comments_and_stops_at_block_end() {
+        let lines = lines_from_text("--- one ---\n# note\na\n\nb\n[two]\nc");
+        let numbers = |engine: &Engine, lines: &[ScriptLine]| {
+            engine.plan(lines).unwrap().iter().map(|(line, _)| line.number).collect::<Vec<_>>()
+        };
+        assert_eq!(numbers(&engine(StopAt::BlockEnd), &lines), vec![3, 5]);
+        // 从第一段末尾的空行继续时发送下一段
+        assert_eq!(numbers(&engine(StopAt::BlockEnd), &lines[3..]), vec![5]);
+        assert_eq!(numbers(&engine(StopAt::BlockEnd), &lines[5..]), vec![7]);
+        assert_eq!(numbers(&engine(StopAt::EndOfInput), &lines), vec![3, 5, 7]);
+
+        let mut keep_blank = engine(StopAt::BlockEnd);
+        keep_blank.options.send_blank_lines = true;
+        let planned = keep_blank.plan(&lines).unwrap();
+        assert_eq!(planned.iter().map(|(_, text)| text.as_str()).collect::<Vec<_>>(), vec!["a", "", "b"]);
+    }
+
+    #[test]
+    fn sends_lines_with_enter() {
+        let mut target = Recorder::default();
+        let mut sent = Vec::new();
+        let report = engine(StopAt::EndOfInput)
+            .run(&mut target, &lines_from_text("echo 1\n\necho 2"), &mut |event| {
+                if let SendEvent::Sent { line } = event {
+                    sent.push(line.number);
+                }
+            })
+            .unwrap();
+        assert_eq!(report, SendReport { sent: 2, aborted: false, transcript_error: None });
+        assert_eq!(sent, vec![1, 3]);
+        assert_eq!(target.output, "echo 1\necho 2\n");
+        assert!(target.prepared);
+    }
+
+    #[test]
+    fn refuses_unconfirmed_dangerous_lines() {
+        let mut engine = engine(StopAt::EndOfInput);
+        engine.guard = Some(Guard::new(&[r"\breboot\b".to_string()], &[]).unwrap());
+        engine.variables.insert("CMD".to_string(), "reboot".to_string());
+        let lines = lines_from_text("ls\n${CMD}");
+        let mut target = Recorder::default();
+        match engine.run(&mut target, &lines, &mut |_| {}) {
+            Err(SendError::Unconfirmed(hits)) => assert_eq!(hits[0].text, "reboot"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(target.output, "");
+
+        engine.confirmed = true;
+        assert_eq!(engine.run(&mut target, &lines, &mut |_| {}).unwrap().sent, 2);
+    }
+
+    #[test]
+    fn abort_stops_before_next_line() {
+        let engine = engine(StopAt::EndOfInput);
+        let abort_flag = engine.abort_flag.clone();
+        let mut target = Recorder::default();
+        let report = engine
+            .run(&mut target, &lines_from_text("a\nb\nc"), &mut |event| {
+                if let SendEvent::Sent { .. } = event {
+                    abort_flag.store(true, Ordering::SeqCst);
+                }
+            })
+            .unwrap();
+        assert_eq!(report, SendReport { sent: 1, aborted: true, transcript_error: None });
+        assert_eq!(target.output, "a\n");
+    }
+
+    /// 把收到的行回显到输出, 遇到 "ok" 才输出提示符; 执行 echo, 回答退出状态探测, 只有 "false" 失败。
+    /// 给出 delay 时输出在这段时间之后才到达, 如同串口或网络上的目标
+    #[derive(Default)]
+    struct FakeShell {
+        line: String,
+        output: OutputBuffer,
+        delay: Duration,
+    }
+
+    impl Target for FakeShell {
+        fn describe(&self) -> String {
+            "fake".to_string()
+        }
+
+        fn send_str(&mut self, text: &str) -> io::Result<()> {
+            self.line.push_str(text);
+            Ok(())
+        }
+
+        fn send_enter(&mut self) -> io::Result<()> {
+            let line = std::mem::take(&mut self.line);
+            let mut reply = format!("{}\r\n", line);
+            if let Some(text) = line.strip_prefix("echo ") {
+                reply += &format!("{}\r\n", text);
+            }
+            if line == "ok" {
+                reply += "> ";
+            }
+            if let Some(command) = line.strip_suffix("; echo __RC=$?") {
+                reply += &format!("__RC={}\r\n", i32::from(command == "false"));
+            }
+            if self.delay.is_zero() {
+                self.output.push(reply.as_bytes());
+            } else {
+                let (output, delay) = (self.output.clone(), self.delay);
+                thread::spawn(move || {
+                    thread::sleep(delay);
+                    output.push(reply.as_bytes());
+                });
+            }
+            Ok(())
+        }
+
+        fn output(&self) -> Option<OutputBuffer> {
+            Some(self.output.clone())
+        }
+    }
+
+    #[test]
+    fn waits_for_prompt_after_each_line() {
+        let mut engine = engine(StopAt::EndOfInput);
+        engine.options.prompt = Some(Regex::new("> $").unwrap());
+        engine.options.prompt_timeout = Duration::from_millis(200);
+        let mut target = FakeShell::default();
+        target.output.push(b"> ");
+
+        let report = engine.run(&mut target, &lines_from_text("ok\nok"), &mut |_| {}).unwrap();
+        assert_eq!(report.sent, 2);
+
+        let result = engine.run(&mut target, &lines_from_text("ok\nhang\nok"), &mut |_| {});
+        assert!(matches!(result, Err(SendError::PromptTimeout { line: 2 })));
+    }
+
+    #[test]
+    fn records_each_line_in_transcript() {
+        let dir = std::env::temp_dir().join(format!("tty_sender_engine_transcript_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut engine = engine(StopAt::EndOfInput);
+        engine.options.prompt = Some(Regex::new("> $").unwrap());
+        engine.options.prompt_timeout = Duration::from_millis(200);
+        engine.source = Some(PathBuf::from("main.txt"));
+        engine.transcript = Some(Transcript::new(&dir));
+        let mut target = FakeShell::default();
+        target.output.push(b"> ");
+
+        let result = engine.run(&mut target, &lines_from_text("ok\n\nhang"), &mut |_| {});
+        assert!(matches!(result, Err(SendError::PromptTimeout { line: 3 })));
+
+        let entries: Vec<serde_json::Value> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+            .flat_map(|path| std::fs::read_to_string(path).unwrap().lines().map(str::to_string).collect::<Vec<_>>())
+            .map(|line| serde_json::from_str(&line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["target"], "fake");
+        assert_eq!(entries[0]["file"], "main.txt");
+        assert_eq!(entries[0]["outcome"], "sent");
+        assert_eq!(entries[1]["line"], 3);
+        assert_eq!(entries[1]["outcome"], "prompt-timeout");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn captures_output_into_variables() {
+        let engine = engine(StopAt::EndOfInput);
+        let mut target = FakeShell::default();
+        // 匹配前去掉控制序列
+        let lines = lines_from_text("echo \x1b[1minet 10.0.0.2/24\n#!capture IP /inet ([\\d.]+)/ 1s\nping ${IP}");
+        let mut captured = Vec::new();
+        engine
+            .run(&mut target, &lines, &mut |event| {
+                if let SendEvent::Captured { name, value, .. } = event {
+                    captured.push(format!("{}={}", name, value));
+                }
+            })
+            .unwrap();
+        assert_eq!(captured, ["IP=10.0.0.2"]);
+        assert!(target.output.text_since(0).ends_with("ping 10.0.0.2\r\n"));
+        // 预览时还没有值
+        assert_eq!(engine.plan(&lines).unwrap()[1].1, "ping ${IP}");
+
+        let timeout = lines_from_text("echo\n#!capture MAC /ether (\\S+)/ 50ms");
+        assert!(matches!(
+            engine.run(&mut FakeShell::default(), &timeout, &mut |_| {}),
+            Err(SendError::CaptureTimeout { line: 2, name }) if name == "MAC"
+        ));
+        // 不能读取输出的目标在发送前报错
+        let mut recorder = Recorder::default();
+        assert!(matches!(engine.run(&mut recorder, &timeout, &mut |_| {}), Err(SendError::NoOutput { line: 2 })));
+        assert_eq!(recorder.output, "");
+    }
+
+    #[test]
+    fn capture_skips_command_echo() {
+        let engine = engine(StopAt::EndOfInput);
+        let lines = lines_from_text("echo inet 10.0.0.2/24\n#!capture FIRST /(?m)^(\\S+)/ 1s");
+        let mut captured = Vec::new();
+        engine
+            .run(&mut FakeShell::default(), &lines, &mut |event| {
+                if let SendEvent::Captured { value, .. } = event {
+                    captured.push(value.to_string());
+                }
+            })
+            .unwrap();
+        // 回显 "echo inet ..." 同样匹配, 但只取命令的输出
+        assert_eq!(captured, ["inet"]);
+    }
+
+    #[test]
+    fn rejects_unknown_directives() {
+        assert!(matches!(invalid("echo ok\n#!expct /ok/"), (2, DirectiveErrorKind::Unknown(name)) if name == "expct"));
+        assert!(matches!(invalid("#!repeat 2\n#!expect-nto /panic/\n#!end"), (2, DirectiveErrorKind::Unknown(_))));
+        // `#!/bin/sh` 仍是注释
+        assert!(engine(StopAt::EndOfInput).plan(&lines_from_text("#!/bin/sh\nls")).is_ok());
+    }
+
+    #[test]
+    fn stops_on_failed_command() {
+        let mut engine = engine(StopAt::BlockEnd);
+        engine.options.check_status = true;
+        engine.options.prompt_timeout = Duration::from_millis(500);
+        let lines = lines_from_text("#!on-error cleanup\ntrue\nfalse\necho never\n[cleanup]\necho cleanup");
+        let mut target = FakeShell::default();
+        let mut handled = Vec::new();
+        let result = engine.run(&mut target, &lines, &mut |event| {
+            if let SendEvent::ErrorHandler { line, error, section } = event {
+                handled.push((line.number, error.to_string(), section.to_string()));
+            }
+        });
+        assert!(matches!(result, Err(SendError::ExitStatus { line: 3, status: 1, .. })));
+        assert_eq!(handled, [(3, "line 3: exited with status 1: false".to_string(), "cleanup".to_string())]);
+        let output = target.output.text_since(0);
+        // 出错处理段中不再附加探测
+        assert!(output.ends_with("false; echo __RC=$?\r\n__RC=1\r\necho cleanup\r\ncleanup\r\n"));
+        assert!(!output.contains("never"));
+
+        let missing = lines_from_text("#!on-error nowhere\ntrue");
+        assert!(matches!(
+            engine.plan(&missing),
+            Err(ScriptError::Directive(DirectiveError { kind: DirectiveErrorKind::Section(_), .. }))
+        ));
+    }
+
+    #[test]
+    fn records_expectation_results() {
+        let engine = engine(StopAt::EndOfInput);
+        let lines = lines_from_text(
+            "echo kernel 6.1\n#!expect /6\\.1/ 1s\n#!expect-not /panic/\necho panic\n#!expect-not /panic/\necho never\n#!expect /never/",
+        );
+        let result = engine.run(&mut FakeShell::default(), &lines, &mut |_| {});
+        assert!(matches!(result, Err(SendError::UnexpectedOutput { line: 5, .. })));
+        let results = engine.results();
+        let statuses: Vec<_> = results.steps.iter().map(|step| (step.line, step.status.name())).collect();
+        assert_eq!(
+            statuses,
+            [(1, "passed"), (2, "passed"), (3, "passed"), (4, "passed"), (5, "failed"), (6, "skipped"), (7, "skipped")]
+        );
+        assert_eq!(results.steps[1].kind, "expect");
+        assert!(results.has_assertions());
+    }
+
+    #[test]
+    fn expect_fails_after_timeout() {
+        let engine = engine(StopAt::EndOfInput);
+        let missing = lines_from_text("echo 1\n#!expect /2/ 50ms");
+        assert!(matches!(
+            engine.run(&mut FakeShell::default(), &missing, &mut |_| {}),
+            Err(SendError::ExpectFailed { line: 2, .. })
+        ));
+        let statuses: Vec<_> = engine.results().steps.iter().map(|step| step.status.name()).collect();
+        assert_eq!(statuses, ["passed", "failed"]);
+
+        // 输出已关闭时不会再出现匹配, 报告为连接断开
+        let mut target = FakeShell::default();
+        target.output.close();
+        assert!(matches!(
+            engine.run(&mut target, &missing, &mut |_| {}),
+            Err(SendError::OutputClosed { line: 2 })
+        ));
+    }
+
+    #[test]
+    fn expect_not_waits_for_late_output() {
+        let engine = engine(StopAt::EndOfInput);
+        let mut target = FakeShell { delay: Duration::from_millis(200), ..FakeShell::default() };
+        let lines = lines_from_text("echo BOOM\n#!expect-not /BOOM/");
+        assert!(matches!(
+            engine.run(&mut target, &lines, &mut |_| {}),
+            Err(SendError::UnexpectedOutput { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn expect_not_ignores_command_echo() {
+        let engine = engine(StopAt::EndOfInput);
+        let echoed = lines_from_text("grep -c panic /var/log/messages\n#!expect-not /panic/");
+        assert!(engine.run(&mut FakeShell::default(), &echoed, &mut |_| {}).is_ok());
+
+        // 回显只收到一部分时也不算输出
+        let pattern = Regex::new("panic").unwrap();
+        let echo = "grep -c panic /var/log/messages";
+        let output = OutputBuffer::new();
+        output.push(b"grep -c panic /va");
+        assert!(matches!(engine.wait_match(&output, &pattern, 0, Some(echo), Duration::ZERO), Waited::TimedOut));
+        output.push(b"r/log/messages\r\n0\r\n");
+        assert!(matches!(engine.wait_match(&output, &pattern, 0, Some(echo), Duration::ZERO), Waited::TimedOut));
+        output.push(b"kernel panic\r\n");
+        assert!(matches!(engine.wait_match(&output, &pattern, 0, Some(echo), Duration::ZERO), Waited::Found(_)));
+        // 不回显的目标照常匹配
+        let silent = OutputBuffer::new();
+        silent.push(b"kernel panic\r\n");
+        assert!(matches!(engine.wait_match(&silent, &pattern, 0, Some(echo), Duration::ZERO), Waited::Found(_)));
+    }
+
+    #[test]
+    fn repeats_loop_body() {
+        let engine = engine(StopAt::Blank);
+        let lines = lines_from_text("#!repeat 3\n\necho run\n#!expect /run/\n#!end\necho after");
+        let mut iterations = Vec::new();
+        let report = engine
+            .run(&mut FakeShell::default(), &lines, &mut |event| {
+                if let SendEvent::Iteration { line, iteration, total } = event {
+                    iterations.push((line.number, iteration, total));
+                }
+            })
+            .unwrap();
+        // 循环中的空行不结束发送
+        assert_eq!(report.sent, 4);
+        assert_eq!(iterations, [(1, 1, Some(3)), (1, 2, Some(3)), (1, 3, Some(3))]);
+        // 预览中循环体只列出一次
+        assert_eq!(engine.plan(&lines).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn numbers_iterations() {
+        let engine = engine(StopAt::EndOfInput);
+        let lines = lines_from_text("#!repeat 3\necho run ${ITER}\n#!end\necho ${ITER}");
+        let mut target = FakeShell::default();
+        engine.run(&mut target, &lines, &mut |_| {}).unwrap();
+        let output = target.output.text_since(0);
+        assert!(output.starts_with("echo run 1\r\nrun 1\r\necho run 2\r\nrun 2\r\necho run 3\r\nrun 3\r\n"));
+        // 循环结束后 ${ITER} 不再有值
+        assert!(output.ends_with("echo ${ITER}\r\n${ITER}\r\n"));
+    }
+
+    #[test]
+    fn nests_loops() {
+        let engine = engine(StopAt::EndOfInput);
+        let lines = lines_from_text("#!repeat 2\n#!repeat 2\necho inner ${ITER}\n#!end\necho outer ${ITER}\n#!end");
+        let mut target = FakeShell::default();
+        let mut iterations = Vec::new();
+        engine
+            .run(&mut target, &lines, &mut |event| {
+                if let SendEvent::Iteration { line, iteration, .. } = event {
+                    iterations.push((line.number, iteration));
+                }
+            })
+            .unwrap();
+        assert_eq!(iterations, [(1, 1), (2, 1), (2, 2), (1, 2), (2, 1), (2, 2)]);
+        let sent: Vec<_> = target.output.text_since(0).lines().filter(|line| !line.starts_with("echo")).map(str::to_string).collect();
+        // 里层循环结束后恢复外层的 ${ITER}
+        assert_eq!(sent, ["inner 1", "inner 2", "outer 1", "inner 1", "inner 2", "outer 2"]);
+    }
+
+    #[test]
+    fn stops_loop_on_failure() {
+        let engine = engine(StopAt::EndOfInput);
+        let failing = lines_from_text("#!repeat 5\necho ${ITER}\n#!expect /(?m)^[12]\\r?$/ 50ms\n#!end\necho after");
+        let result = engine.run(&mut FakeShell::default(), &failing, &mut |_| {});
+        assert!(matches!(result, Err(SendError::ExpectFailed { line: 3, .. })));
+        let statuses: Vec<_> = engine.results().steps.iter().map(|step| step.status.name()).collect();
+        assert_eq!(statuses, ["passed", "passed", "passed", "passed", "passed", "failed", "skipped"]);
+    }
+
+    #[test]
+    fn while_waits_for_late_output() {
+        let engine = engine(StopAt::EndOfInput);
+        let mut target = FakeShell { delay: Duration::from_millis(200), ..FakeShell::default() };
+        let polling = lines_from_text("echo busy\n#!while /busy/\necho done\n#!end\necho busy\n#!while /busy/ 2s\necho done\n#!end");
+        let mut iterations = Vec::new();
+        engine
+            .run(&mut target, &polling, &mut |event| {
+                if let SendEvent::Iteration { line, total: None, .. } = event {
+                    iterations.push(line.number);
+                }
+            })
+            .unwrap();
+        // 输出晚于检查到达时也能看到, 循环体各执行一次
+        assert_eq!(iterations, [2, 6]);
+    }
+
+    #[test]
+    fn reports_unclosed_loops() {
+        let engine = engine(StopAt::EndOfInput);
+        assert!(matches!(
+            engine.plan(&lines_from_text("#!repeat 2\nls")),
+            Err(ScriptError::Directive(DirectiveError { line: 1, kind: DirectiveErrorKind::Unclosed("end"), .. }))
+        ));
+        assert!(matches!(
+            engine.plan(&lines_from_text("ls\n#!end")),
+            Err(ScriptError::Directive(DirectiveError { line: 2, kind: DirectiveErrorKind::Unmatched("end"), .. }))
+        ));
+    }
+
+    #[test]
+    fn branches_and_jumps_to_labels() {
+        let engine = engine(StopAt::EndOfInput);
+        let lines = lines_from_text(
+            "echo U-Boot 2024.01\n#!if /U-Boot/\necho in bootloader\n#!else\necho in linux\n#!endif\n#!goto done\necho skipped\n#!label done\necho end",
+        );
+        let mut target = FakeShell::default();
+        engine.run(&mut target, &lines, &mut |_| {}).unwrap();
+        let output = target.output.text_since(0);
+        assert!(output.contains("in bootloader\r\n"));
+        assert!(!output.contains("in linux") && !output.contains("skipped"));
+        let statuses: Vec<_> = engine.results().steps.iter().map(|step| (step.line, step.status.name())).collect();
+        assert_eq!(statuses, [(1, "passed"), (3, "passed"), (8, "skipped"), (10, "passed")]);
+    }
+
+    #[test]
+    fn if_waits_for_late_output() {
+        let engine = engine(StopAt::EndOfInput);
+        let lines = lines_from_text(
+            "echo U-Boot\n#!if /U-Boot/\necho bootloader\n#!endif\necho Linux\n#!if /U-Boot/ 300ms\necho wrong\n#!else\necho linux\n#!endif",
+        );
+        let mut target = FakeShell { delay: Duration::from_millis(200), ..FakeShell::default() };
+        engine.run(&mut target, &lines, &mut |_| {}).unwrap();
+        let sent: Vec<_> = engine.results().steps.iter().map(|step| step.line).collect();
+        assert_eq!(sent, [1, 3, 5, 9]);
+    }
+
+    #[test]
+    fn on_error_goto_still_fails() {
+        let mut engine = engine(StopAt::EndOfInput);
+        engine.options.check_status = true;
+        engine.options.prompt_timeout = Duration::from_millis(500);
+        let lines = lines_from_text("#!on-error goto cleanup\n#!repeat 3\nfalse\n#!end\necho never\n#!label cleanup\necho cleanup");
+        let mut target = FakeShell::default();
+        let mut jumps = Vec::new();
+        let result = engine.run(&mut target, &lines, &mut |event| {
+            if let SendEvent::ErrorGoto { line, label, .. } = event {
+                jumps.push((line.number, label.to_string()));
+            }
+        });
+        // 跳转后执行完, 仍按失败结束
+        assert!(matches!(result, Err(SendError::ExitStatus { line: 3, .. })));
+        assert_eq!(jumps, [(3, "cleanup".to_string())]);
+        let output = target.output.text_since(0);
+        assert!(output.ends_with("__RC=1\r\necho cleanup\r\ncleanup\r\n"));
+        assert!(!output.contains("never"));
+        let statuses: Vec<_> = engine.results().steps.iter().map(|step| (step.line, step.status.name())).collect();
+        assert_eq!(statuses, [(3, "failed"), (5, "skipped"), (7, "passed")]);
+    }
+
+    #[test]
+    fn limits_jumps_back() {
+        let engine = engine(StopAt::EndOfInput);
+        let lines = lines_from_text("echo start\n#!label top\necho again\n#!goto top\necho never");
+        let mut target = Recorder::default();
+        let result = engine.run(&mut target, &lines, &mut |_| {});
+        assert!(matches!(result, Err(SendError::TooManyJumps { line: 2, label }) if label == "top"));
+        assert_eq!(target.output.matches("echo again\n").count(), MAX_JUMPS_BACK + 1);
+        assert!(!target.output.contains("never"));
+    }
+
+    /// 开始发送前报告的错误
+    fn invalid(text: &str) -> (usize, DirectiveErrorKind) {
+        match engine(StopAt::EndOfInput).plan(&lines_from_text(text)) {
+            Err(ScriptError::Directive(DirectiveError { line, kind, .. })) => (line, kind),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_labels() {
+        assert!(matches!(invalid("ls\n#!goto nowhere"), (2, DirectiveErrorKind::Label(_))));
+        assert!(matches!(invalid("#!on-error goto nowhere"), (1, DirectiveErrorKind::Label(_))));
+        assert!(matches!(invalid("#!label a\n#!if /x/\n#!label a\n#!endif"), (3, DirectiveErrorKind::DuplicateLabel(_))));
+    }
+
+    #[test]
+    fn rejects_jumps_into_blocks() {
+        assert!(matches!(invalid("#!goto inner\n#!repeat 2\n#!label inner\n#!end"), (1, DirectiveErrorKind::Label(_))));
+        assert!(matches!(invalid("#!goto then\n#!if /x/\n#!label then\n#!endif"), (1, DirectiveErrorKind::Label(_))));
+        // 可以从里层跳出
+        let engine = engine(StopAt::EndOfInput);
+        assert!(engine.plan(&lines_from_text("#!repeat 2\n#!if /x/\n#!goto out\n#!endif\n#!end\n#!label out")).is_ok());
+    }
+
+    #[test]
+    fn reports_unmatched_branches() {
+        assert!(matches!(invalid("#!repeat 2\n#!else\n#!end"), (2, DirectiveErrorKind::Unmatched("else"))));
+        assert!(matches!(invalid("#!if /x/\n#!end"), (2, DirectiveErrorKind::Unmatched("end"))));
+        assert!(matches!(invalid("#!if /x/\nls"), (1, DirectiveErrorKind::Unclosed("endif"))));
+    }
+
+    #[test]
+    fn expands_macro_calls_before_sending() {
+        let mut engine = engine(StopAt::BlockEnd);
+        engine.variables.insert("GREETING".to_string(), "hello".to_string());
+        // 宏定义在发送的段之外
+        let lines = lines_from_text(
+            "#!call greet(world)\n#!call greet(\"a, b\")\n\n[macros]\n#!macro greet(name)\necho ${GREETING} ${name}\n#!expect /${name}/ 1s\n#!endmacro",
+        );
+        let planned: Vec<_> = engine.plan(&lines).unwrap().into_iter().map(|(line, text)| (line.number, text)).collect();
+        assert_eq!(planned, [(1, "echo hello world".to_string()), (2, "echo hello a, b".to_string())]);
+
+        let mut target = FakeShell::default();
+        assert_eq!(engine.run(&mut target, &lines, &mut |_| {}).unwrap().sent, 2);
+        assert!(target.output.text_since(0).ends_with("echo hello a, b\r\nhello a, b\r\n"));
+        let kinds: Vec<_> = engine.results().steps.iter().map(|step| (step.line, step.kind, step.status.name())).collect();
+        assert_eq!(kinds, [(1, "send", "passed"), (1, "expect", "passed"), (2, "send", "passed"), (2, "expect", "passed")]);
+
+        assert!(matches!(
+            engine.plan(&lines_from_text("#!call greet(a)")),
+            Err(ScriptError::Directive(DirectiveError { line: 1, kind: DirectiveErrorKind::UnknownMacro(_), .. }))
+        ));
+    }
+
+    #[test]
+    fn sends_ranges_cutting_through_macros() {
+        let mut engine = engine(StopAt::EndOfInput);
+        let document = lines_from_text("#!call greet(a)\n#!macro greet(name)\necho ${name}\n#!endmacro\necho after");
+        engine.document = document.clone();
+        let texts = |lines: &[ScriptLine]| -> Vec<String> {
+            engine.plan(lines).unwrap().into_iter().map(|(_, text)| text).collect()
+        };
+        // 范围在定义中间结束或开始时, 定义中的行按位置去掉
+        assert_eq!(texts(&document[..3]), ["echo a"]);
+        assert_eq!(texts(&document[2..]), ["echo after"]);
+        assert!(texts(&document[1..3]).is_empty());
+    }
+
+    #[test]
+    fn limits_include_and_call_nesting() {
+        let dir = std::env::temp_dir().join(format!("tty_sender_engine_nesting_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("again.txt"), "#!call again").unwrap();
+        let mut engine = engine(StopAt::EndOfInput);
+        engine.source = Some(dir.join("main.txt"));
+        let lines = lines_from_text("#!call again\n#!macro again()\n#!include again.txt\n#!endmacro");
+        let error = match engine.plan(&lines) {
+            Err(ScriptError::Directive(error)) => error,
+            other => panic!("unexpected result: {:?}", other),
+        };
+        assert!(matches!(error.kind, DirectiveErrorKind::NestedTooDeep));
+        assert!(error.to_string().contains(&format!("more than {} levels", MAX_CALL_DEPTH)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
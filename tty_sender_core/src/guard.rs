@@ -0,0 +1,70 @@
+//! 危险命令检查: 发送前把要发送的每一行与配置的正则比对, 命中时须先由用户确认
+//!
+//! 受保护的目标额外检查 protected_patterns, 界面和命令行也会要求更明确的确认。
+
+use crate::script::ScriptLine;
+use regex::{Regex, RegexBuilder};
+
+pub struct Guard {
+    patterns: Vec<Regex>,
+    protected_patterns: Vec<Regex>,
+}
+
+/// 命中危险命令规则的一行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuardHit {
+    pub line: ScriptLine,
+    /// 变量替换后实际要发送的内容
+    pub text: String,
+    pub pattern: String,
+}
+
+impl Guard {
+    /// 规则不区分大小写
+    pub fn new(patterns: &[String], protected_patterns: &[String]) -> Result<Guard, regex::Error> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| RegexBuilder::new(pattern).case_insensitive(true).build())
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Guard { patterns: compile(patterns)?, protected_patterns: compile(protected_patterns)? })
+    }
+
+    /// 检查将要发送的行, protected 为 true 时同时使用受保护目标的规则
+    pub fn check(&self, planned: &[(ScriptLine, String)], protected: bool) -> Vec<GuardHit> {
+        let extra: &[Regex] = if protected { &self.protected_patterns } else { &[] };
+        planned
+            .iter()
+            .filter_map(|(line, text)| {
+                let pattern = self.patterns.iter().chain(extra).find(|pattern| pattern.is_match(text))?;
+                Some(GuardHit { line: line.clone(), text: text.clone(), pattern: pattern.as_str().to_string() })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::lines_from_text;
+
+    #[test]
+    fn flags_dangerous_lines() {
+        let guard = Guard::new(&[r"\brm\s+-rf\b".to_string(), r"\berase\b".to_string()], &[r"\breset\b".to_string()])
+            .unwrap();
+        let planned: Vec<_> = lines_from_text("ls\nFLASH ERASE 0\nrm -rf /tmp/x\nreset")
+            .into_iter()
+            .map(|line| {
+                let text = line.text.clone();
+                (line, text)
+            })
+            .collect();
+
+        let hits = guard.check(&planned, false);
+        assert_eq!(hits.iter().map(|hit| hit.line.number).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(hits[0].pattern, r"\berase\b");
+        assert_eq!(guard.check(&planned, true).len(), 3);
+        assert!(Guard::new(&["(".to_string()], &[]).is_err());
+    }
+}
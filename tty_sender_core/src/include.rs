@@ -0,0 +1,238 @@
+//! `#!include 路径 [分段]` 的展开
+//!
+//! 相对路径相对于写有该指令的文件; 被包含的文件中可以继续包含其他文件,
+//! 循环包含和过深的嵌套会报错, 错误信息指出出错的文件和行号。
+
+use crate::script::{lines_from_text, section_lines, LineKind, Origin, ScriptLine, Syntax};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 最多嵌套的层数
+pub const MAX_INCLUDE_DEPTH: usize = 16;
+
+#[derive(Debug)]
+pub enum IncludeErrorKind {
+    MissingPath,
+    /// 编辑内容尚未保存为文件, 无法解析相对路径
+    NoBaseFile(PathBuf),
+    Read(PathBuf, io::Error),
+    Cycle(PathBuf),
+    TooDeep,
+    UnknownSection(PathBuf, String),
+}
+
+/// file 与 line 是写有 `#!include` 的位置, file 为 None 表示未保存的编辑内容
+#[derive(Debug)]
+pub struct IncludeError {
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub kind: IncludeErrorKind,
+}
+
+impl IncludeError {
+    /// "文件:行号" 形式的位置
+    pub fn location(&self) -> String {
+        match &self.file {
+            Some(file) => format!("{}:{}", file.display(), self.line),
+            None => format!("line {}", self.line),
+        }
+    }
+}
+
+impl fmt::Display for IncludeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.location())?;
+        match &self.kind {
+            IncludeErrorKind::MissingPath => write!(f, "#!include needs a path"),
+            IncludeErrorKind::NoBaseFile(path) => {
+                write!(f, "cannot resolve {} before the file is saved", path.display())
+            }
+            IncludeErrorKind::Read(path, e) => write!(f, "cannot read {}: {}", path.display(), e),
+            IncludeErrorKind::Cycle(path) => write!(f, "{} includes itself", path.display()),
+            IncludeErrorKind::TooDeep => write!(f, "includes nested more than {} levels", MAX_INCLUDE_DEPTH),
+            IncludeErrorKind::UnknownSection(path, name) => {
+                write!(f, "no section [{}] in {}", name, path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for IncludeError {}
+
+/// 展开 line 上的 `#!include` (args 为指令参数), source 是 line 所在的文件;
+/// 返回的行都带有 origin, 行号沿用 line 的行号
+pub fn expand_include(
+    args: &str,
+    line: &ScriptLine,
+    source: Option<&Path>,
+    syntax: &Syntax,
+) -> Result<Vec<ScriptLine>, IncludeError> {
+    let mut stack: Vec<PathBuf> = source.and_then(|path| path.canonicalize().ok()).into_iter().collect();
+    let (file, at_line) = match &line.origin {
+        Some(origin) => (Some(origin.file.as_path()), origin.line),
+        None => (source, line.number),
+    };
+    let mut expanded = Vec::new();
+    include_into(&mut expanded, args, file, at_line, line.number, &mut stack, 1, syntax)?;
+    Ok(expanded)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn include_into(
+    expanded: &mut Vec<ScriptLine>,
+    args: &str,
+    file: Option<&Path>,
+    at_line: usize,
+    number: usize,
+    stack: &mut Vec<PathBuf>,
+    depth: usize,
+    syntax: &Syntax,
+) -> Result<(), IncludeError> {
+    let error = |kind| IncludeError { file: file.map(Path::to_path_buf), line: at_line, kind };
+    let (path, section) = split_args(args).ok_or_else(|| error(IncludeErrorKind::MissingPath))?;
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(error(IncludeErrorKind::TooDeep));
+    }
+    let path = PathBuf::from(path);
+    let path = if path.is_absolute() {
+        path
+    } else {
+        match file.and_then(Path::parent) {
+            Some(dir) => dir.join(path),
+            None => return Err(error(IncludeErrorKind::NoBaseFile(path))),
+        }
+    };
+    let read = |path: &Path| path.canonicalize().and_then(|canonical| Ok((fs::read_to_string(&canonical)?, canonical)));
+    let (text, canonical) = read(&path).map_err(|e| error(IncludeErrorKind::Read(path.clone(), e)))?;
+    if stack.contains(&canonical) {
+        return Err(error(IncludeErrorKind::Cycle(canonical)));
+    }
+
+    let lines = lines_from_text(&text);
+    let lines = match section {
+        Some(name) => section_lines(&lines, syntax, name)
+            .ok_or_else(|| error(IncludeErrorKind::UnknownSection(canonical.clone(), name.to_string())))?,
+        None => lines,
+    };
+    stack.push(canonical.clone());
+    for line in lines {
+        if let LineKind::Directive { name: "include", args } = syntax.classify(&line.text) {
+            include_into(expanded, args, Some(&canonical), line.number, number, stack, depth + 1, syntax)?;
+        } else {
+            expanded.push(ScriptLine {
+                number,
+                text: line.text,
+                origin: Some(Origin { file: canonical.clone(), line: line.number }),
+            });
+        }
+    }
+    stack.pop();
+    Ok(())
+}
+
+/// 拆出路径和可选的分段名, 含空格的路径可以用双引号括起
+fn split_args(args: &str) -> Option<(&str, Option<&str>)> {
+    let args = args.trim();
+    let (path, rest) = match args.strip_prefix('"') {
+        Some(quoted) => quoted.split_once('"')?,
+        None => args.split_once(char::is_whitespace).unwrap_or((args, "")),
+    };
+    if path.is_empty() {
+        return None;
+    }
+    let section = rest.trim();
+    Some((path, if section.is_empty() { None } else { Some(section) }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// 在临时目录中写入一组文件, 返回目录
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("tty_sender_include_{}_{}", name, std::process::id()));
+        for (path, text) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    fn expand(dir: &Path, text: &str) -> Result<Vec<ScriptLine>, IncludeError> {
+        let source = dir.join("main.txt");
+        fs::write(&source, text).unwrap();
+        let line = &lines_from_text(text)[0];
+        let args = match Syntax::default().classify(&line.text) {
+            LineKind::Directive { args, .. } => args,
+            other => panic!("not a directive: {:?}", other),
+        };
+        expand_include(args, line, Some(&source), &Syntax::default())
+    }
+
+    #[test]
+    fn expands_nested_includes_and_sections() {
+        let dir = write_files(
+            "nested",
+            &[
+                ("common/login.txt", "root\n#!include net.txt dhcp\n"),
+                ("common/net.txt", "[static]\nifconfig eth0 10.0.0.2\n[dhcp]\nudhcpc\n"),
+            ],
+        );
+        let lines = expand(&dir, "#!include common/login.txt").unwrap();
+        let texts: Vec<_> = lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(texts, vec!["root", "udhcpc"]);
+        assert!(lines.iter().all(|line| line.number == 1));
+        let origin = lines[1].origin.as_ref().unwrap();
+        assert!(origin.file.ends_with("common/net.txt"));
+        assert_eq!(origin.line, 4);
+    }
+
+    #[test]
+    fn reports_cycles_with_location() {
+        let dir = write_files("cycle", &[("a.txt", "echo a\n#!include b.txt\n"), ("b.txt", "\n\n#!include a.txt\n")]);
+        let error = expand(&dir, "#!include a.txt").unwrap_err();
+        assert!(matches!(error.kind, IncludeErrorKind::Cycle(_)));
+        assert!(error.file.as_ref().unwrap().ends_with("b.txt"));
+        assert_eq!(error.line, 3);
+
+        let error = expand(&dir, "#!include main.txt").unwrap_err();
+        assert!(matches!(error.kind, IncludeErrorKind::Cycle(_)));
+    }
+
+    #[test]
+    fn reports_missing_files_and_sections() {
+        let dir = write_files("missing", &[("a.txt", "[one]\nls\n")]);
+        let error = expand(&dir, "#!include nothing.txt").unwrap_err();
+        assert!(matches!(error.kind, IncludeErrorKind::Read(..)));
+        assert!(error.to_string().contains("main.txt:1"));
+        let error = expand(&dir, "#!include a.txt two").unwrap_err();
+        assert!(matches!(error.kind, IncludeErrorKind::UnknownSection(_, ref name) if name == "two"));
+
+        let line = &lines_from_text("#!include a.txt")[0];
+        let error = expand_include("a.txt", line, None, &Syntax::default()).unwrap_err();
+        assert!(matches!(error.kind, IncludeErrorKind::NoBaseFile(_)));
+    }
+
+    #[test]
+    fn limits_depth() {
+        let files: Vec<(String, String)> = (0..=MAX_INCLUDE_DEPTH)
+            .map(|i| (format!("{}.txt", i), format!("#!include {}.txt", i + 1)))
+            .collect();
+        let refs: Vec<(&str, &str)> = files.iter().map(|(path, text)| (path.as_str(), text.as_str())).collect();
+        let dir = write_files("deep", &refs);
+        fs::write(dir.join(format!("{}.txt", MAX_INCLUDE_DEPTH + 1)), "ls").unwrap();
+        let error = expand(&dir, "#!include 0.txt").unwrap_err();
+        assert!(matches!(error.kind, IncludeErrorKind::TooDeep));
+    }
+
+    #[test]
+    fn splits_quoted_paths() {
+        assert_eq!(split_args("\"my files/a.txt\" boot"), Some(("my files/a.txt", Some("boot"))));
+        assert_eq!(split_args("a.txt"), Some(("a.txt", None)));
+        assert_eq!(split_args("  "), None);
+    }
+}
@@ -0,0 +1,205 @@
+//! 按键事件类目标 (QEMU、VNC) 使用的按键表示与美式键盘布局, 以及字节流目标使用的终端按键序列
+//!
+//! 行中的 `{F2}`、`{Esc}`、`{Up}` 等写法表示特殊键, 不认识的 `{...}` 按原文发送;
+//! 制表符、退格、ESC 等控制字符也会转成对应的按键。
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialKey {
+    Enter,
+    Tab,
+    Backspace,
+    Escape,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Up,
+    Down,
+    Left,
+    Right,
+    /// F1 ~ F12
+    F(u8),
+}
+
+impl SpecialKey {
+    /// 大括号中的名称, 不区分大小写
+    pub fn from_name(name: &str) -> Option<SpecialKey> {
+        let key = match name.to_ascii_lowercase().as_str() {
+            "enter" | "return" => SpecialKey::Enter,
+            "tab" => SpecialKey::Tab,
+            "backspace" | "bs" => SpecialKey::Backspace,
+            "esc" | "escape" => SpecialKey::Escape,
+            "ins" | "insert" => SpecialKey::Insert,
+            "del" | "delete" => SpecialKey::Delete,
+            "home" => SpecialKey::Home,
+            "end" => SpecialKey::End,
+            "pgup" | "pageup" => SpecialKey::PageUp,
+            "pgdn" | "pagedown" => SpecialKey::PageDown,
+            "up" => SpecialKey::Up,
+            "down" => SpecialKey::Down,
+            "left" => SpecialKey::Left,
+            "right" => SpecialKey::Right,
+            other => {
+                let number: u8 = other.strip_prefix('f')?.parse().ok()?;
+                if !(1..=12).contains(&number) {
+                    return None;
+                }
+                SpecialKey::F(number)
+            }
+        };
+        Some(key)
+    }
+
+    fn from_control(c: char) -> Option<SpecialKey> {
+        match c {
+            '\r' | '\n' => Some(SpecialKey::Enter),
+            '\t' => Some(SpecialKey::Tab),
+            '\u{8}' => Some(SpecialKey::Backspace),
+            '\u{1b}' => Some(SpecialKey::Escape),
+            '\u{7f}' => Some(SpecialKey::Delete),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Special(SpecialKey),
+}
+
+/// 把文本拆成发送单位: 每个字符, 或一个完整的 `{名称}` 特殊键;
+/// 引擎按单位控制字符间隔, 以免把特殊键拆开
+pub fn key_units(text: &str) -> Vec<&str> {
+    let mut units = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = match special_token(rest) {
+            Some((_, len)) => len,
+            None => c.len_utf8(),
+        };
+        units.push(&rest[..len]);
+        rest = &rest[len..];
+    }
+    units
+}
+
+pub fn parse_keys(text: &str) -> Vec<Key> {
+    key_units(text)
+        .into_iter()
+        .map(|unit| match special_token(unit) {
+            Some((key, _)) => Key::Special(key),
+            None => {
+                let c = unit.chars().next().unwrap();
+                SpecialKey::from_control(c).map_or(Key::Char(c), Key::Special)
+            }
+        })
+        .collect()
+}
+
+/// 终端中按下特殊键时发出的字节序列; app_cursor 为应用光标模式 (方向键改用 `ESC O`)
+pub fn terminal_bytes(key: SpecialKey, app_cursor: bool) -> Vec<u8> {
+    let cursor = |c: char| {
+        let prefix = if app_cursor { "\x1bO" } else { "\x1b[" };
+        format!("{}{}", prefix, c).into_bytes()
+    };
+    match key {
+        SpecialKey::Enter => b"\r".to_vec(),
+        SpecialKey::Tab => b"\t".to_vec(),
+        SpecialKey::Backspace => b"\x7f".to_vec(),
+        SpecialKey::Escape => b"\x1b".to_vec(),
+        SpecialKey::Up => cursor('A'),
+        SpecialKey::Down => cursor('B'),
+        SpecialKey::Right => cursor('C'),
+        SpecialKey::Left => cursor('D'),
+        SpecialKey::Home => cursor('H'),
+        SpecialKey::End => cursor('F'),
+        SpecialKey::Insert => b"\x1b[2~".to_vec(),
+        SpecialKey::Delete => b"\x1b[3~".to_vec(),
+        SpecialKey::PageUp => b"\x1b[5~".to_vec(),
+        SpecialKey::PageDown => b"\x1b[6~".to_vec(),
+        SpecialKey::F(n @ 1..=4) => format!("\x1bO{}", (b'P' + n - 1) as char).into_bytes(),
+        SpecialKey::F(n) => {
+            let code = match n {
+                5 => 15,
+                6 => 17,
+                7 => 18,
+                8 => 19,
+                9 => 20,
+                10 => 21,
+                11 => 23,
+                _ => 24,
+            };
+            format!("\x1b[{}~", code).into_bytes()
+        }
+    }
+}
+
+/// 把文本中的 `{名称}` 特殊键换成终端字节序列, 供串口、伪终端等字节流目标发送
+pub fn terminal_text(text: &str) -> String {
+    key_units(text)
+        .into_iter()
+        .map(|unit| match special_token(unit) {
+            Some((key, _)) => String::from_utf8_lossy(&terminal_bytes(key, false)).into_owned(),
+            None => unit.to_string(),
+        })
+        .collect()
+}
+
+/// 文本开头若是 `{名称}` 特殊键, 返回该键及其长度
+fn special_token(text: &str) -> Option<(SpecialKey, usize)> {
+    let inner = text.strip_prefix('{')?;
+    let end = inner.find('}')?;
+    SpecialKey::from_name(&inner[..end]).map(|key| (key, end + 2))
+}
+
+/// 美式键盘上输入该字符所按的键 (以不按 Shift 时的字符表示) 以及是否需要 Shift
+pub fn us_layout(c: char) -> Option<(char, bool)> {
+    const SHIFTED: &[(char, char)] = &[
+        ('!', '1'), ('@', '2'), ('#', '3'), ('$', '4'), ('%', '5'),
+        ('^', '6'), ('&', '7'), ('*', '8'), ('(', '9'), (')', '0'),
+        ('_', '-'), ('+', '='), ('{', '['), ('}', ']'), ('|', '\\'),
+        (':', ';'), ('"', '\''), ('~', '`'), ('<', ','), ('>', '.'), ('?', '/'),
+    ];
+    match c {
+        'a'..='z' | '0'..='9' | ' ' | '-' | '=' | '[' | ']' | '\\' | ';' | '\'' | '`' | ',' | '.' | '/' => {
+            Some((c, false))
+        }
+        'A'..='Z' => Some((c.to_ascii_lowercase(), true)),
+        _ => SHIFTED.iter().find(|(shifted, _)| *shifted == c).map(|&(_, base)| (base, true)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_special_keys_together() {
+        assert_eq!(key_units("a{F2}{x}"), vec!["a", "{F2}", "{", "x", "}"]);
+        assert_eq!(
+            parse_keys("{esc}\tZ{F13}"),
+            vec![
+                Key::Special(SpecialKey::Escape),
+                Key::Special(SpecialKey::Tab),
+                Key::Char('Z'),
+                Key::Char('{'),
+                Key::Char('F'),
+                Key::Char('1'),
+                Key::Char('3'),
+                Key::Char('}'),
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_us_layout() {
+        assert_eq!(us_layout('q'), Some(('q', false)));
+        assert_eq!(us_layout('Q'), Some(('q', true)));
+        assert_eq!(us_layout('?'), Some(('/', true)));
+        assert_eq!(us_layout('~'), Some(('`', true)));
+        assert_eq!(us_layout('é'), None);
+    }
+}
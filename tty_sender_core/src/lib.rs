@@ -0,0 +1,23 @@
+//! tty_sender 的核心部分: 命令脚本解析、发送引擎、发送目标与配置文件,
+//! 不依赖任何界面, 可嵌入其他工具使用
+
+pub mod capture;
+pub mod config;
+pub mod directive;
+pub mod engine;
+pub mod guard;
+pub mod include;
+pub mod keys;
+pub mod macros;
+pub mod results;
+pub mod script;
+pub mod status;
+pub mod target;
+pub mod terminal;
+pub mod transcript;
+pub mod trigger;
+
+pub use config::Config;
+pub use engine::{Engine, SendError, SendEvent, SendOptions, SendReport, StopAt};
+pub use script::{lines_from_text, ScriptLine, Syntax};
+pub use target::{Target, TargetError, TargetSpec};
@@ -0,0 +1,260 @@
+//! 宏: `#!macro 名称(参数, ...)` 到 `#!endmacro` 之间的行为宏的内容,
+//! `#!call 名称(值, ...)` 在发送前展开为这些行, 其中的 `${参数}` 换成对应的值。
+//!
+//! 宏可以定义在脚本的任何位置 (包括发送范围之外), 被包含的文件中的宏在 `#!include` 之后可用;
+//! 宏中可以调用其他宏, 递归调用和过深的嵌套会报错。值中含有逗号时用双引号括起。
+
+use crate::directive::{is_variable_name, DirectiveError, DirectiveErrorKind};
+use crate::engine::expand_variables;
+use crate::script::{LineKind, Origin, ScriptLine, Syntax};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// 宏调用最多嵌套的层数
+pub const MAX_CALL_DEPTH: usize = 16;
+
+const MACRO_USAGE: &str = "#!macro NAME(PARAM, ...)";
+const CALL_USAGE: &str = "#!call NAME(VALUE, ...)";
+
+#[derive(Debug, Clone)]
+struct Macro {
+    /// `#!macro` 所在的行
+    line: ScriptLine,
+    params: Vec<String>,
+    body: Vec<ScriptLine>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Macros {
+    defined: HashMap<String, Macro>,
+}
+
+impl Macros {
+    /// 取出 lines 中的宏定义, 返回定义以外的行; source 是 lines 所在的文件
+    pub fn split(lines: &[ScriptLine], syntax: &Syntax, source: Option<&Path>) -> Result<(Macros, Vec<ScriptLine>), DirectiveError> {
+        let mut macros = Macros::default();
+        let mut rest = Vec::new();
+        let mut open: Option<(String, Macro)> = None;
+        for line in lines {
+            let error = |kind| DirectiveError::at(line, source, kind);
+            match (syntax.classify(&line.text), &mut open) {
+                (LineKind::Directive { name: "macro", args }, None) => {
+                    let (name, params) = parse_signature(args).ok_or_else(|| error(DirectiveErrorKind::Usage(MACRO_USAGE)))?;
+                    let valid = params.iter().enumerate().all(|(index, param)| {
+                        is_variable_name(param) && !params[..index].contains(param)
+                    });
+                    if !valid {
+                        return Err(error(DirectiveErrorKind::Usage(MACRO_USAGE)));
+                    }
+                    open = Some((name, Macro { line: line.clone(), params, body: Vec::new() }));
+                }
+                (LineKind::Directive { name: "endmacro", .. }, Some(_)) => {
+                    let (name, definition) = open.take().unwrap();
+                    macros.insert(name, definition, source)?;
+                }
+                (LineKind::Directive { name: "endmacro", .. }, None) => {
+                    return Err(error(DirectiveErrorKind::Unmatched("endmacro")));
+                }
+                // 宏中不能再定义宏
+                (LineKind::Directive { name: "macro", .. }, Some((_, unclosed))) => {
+                    return Err(DirectiveError::at(&unclosed.line, source, DirectiveErrorKind::Unclosed("endmacro")));
+                }
+                (_, Some((_, open))) => open.body.push(line.clone()),
+                (_, None) => rest.push(line.clone()),
+            }
+        }
+        if let Some((_, unclosed)) = open {
+            return Err(DirectiveError::at(&unclosed.line, source, DirectiveErrorKind::Unclosed("endmacro")));
+        }
+        Ok((macros, rest))
+    }
+
+    /// 加入另一组定义, 如被包含的文件中的宏; 同一处定义再次加入 (同一文件被包含多次) 时忽略
+    pub fn extend(&mut self, other: Macros, source: Option<&Path>) -> Result<(), DirectiveError> {
+        for (name, definition) in other.defined {
+            self.insert(name, definition, source)?;
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, name: String, definition: Macro, source: Option<&Path>) -> Result<(), DirectiveError> {
+        match self.defined.get(&name) {
+            Some(existing) if existing.line.origin == definition.line.origin && existing.line.number == definition.line.number => {}
+            Some(_) => return Err(DirectiveError::at(&definition.line, source, DirectiveErrorKind::DuplicateMacro(name))),
+            None => {
+                self.defined.insert(name, definition);
+            }
+        }
+        Ok(())
+    }
+
+    /// 展开 line 上的 `#!call` (args 为指令参数), 宏中的调用一并展开;
+    /// 返回的行号沿用 line 的行号, origin 指向宏定义中的行
+    pub fn expand_call(
+        &self,
+        args: &str,
+        line: &ScriptLine,
+        syntax: &Syntax,
+        source: Option<&Path>,
+    ) -> Result<Vec<ScriptLine>, DirectiveError> {
+        let mut expanded = Vec::new();
+        self.call_into(&mut expanded, args, line, syntax, source, &mut Vec::new())?;
+        Ok(expanded)
+    }
+
+    fn call_into(
+        &self,
+        expanded: &mut Vec<ScriptLine>,
+        args: &str,
+        line: &ScriptLine,
+        syntax: &Syntax,
+        source: Option<&Path>,
+        stack: &mut Vec<String>,
+    ) -> Result<(), DirectiveError> {
+        let error = |kind| DirectiveError::at(line, source, kind);
+        let (name, values) = parse_signature(args).ok_or_else(|| error(DirectiveErrorKind::Usage(CALL_USAGE)))?;
+        let definition = self.defined.get(&name).ok_or_else(|| error(DirectiveErrorKind::UnknownMacro(name.clone())))?;
+        if values.len() != definition.params.len() {
+            let (expected, found) = (definition.params.len(), values.len());
+            return Err(error(DirectiveErrorKind::MacroArguments { name, expected, found }));
+        }
+        if stack.contains(&name) || stack.len() >= MAX_CALL_DEPTH {
+            return Err(error(DirectiveErrorKind::MacroRecursion(name)));
+        }
+
+        let values: HashMap<String, String> = definition.params.iter().cloned().zip(values).collect();
+        stack.push(name);
+        for body in &definition.body {
+            let origin = body.origin.clone().or_else(|| source.map(|file| Origin { file: file.to_path_buf(), line: body.number }));
+            let called = ScriptLine { number: line.number, text: expand_variables(&body.text, &values), origin };
+            match syntax.classify(&called.text) {
+                LineKind::Directive { name: "call", args } => self.call_into(expanded, args, &called, syntax, source, stack)?,
+                _ => expanded.push(called),
+            }
+        }
+        stack.pop();
+        Ok(())
+    }
+}
+
+/// 拆出 `名称(a, b, ...)` 中的名称和各项; 没有括号时没有参数
+fn parse_signature(text: &str) -> Option<(String, Vec<String>)> {
+    let text = text.trim();
+    let (name, items) = match text.split_once('(') {
+        Some((name, rest)) => (name.trim(), split_items(rest.trim_end().strip_suffix(')')?)?),
+        None => (text, Vec::new()),
+    };
+    is_variable_name(name).then(|| (name.to_string(), items))
+}
+
+/// 按逗号拆分, 双引号中的逗号不拆分, 每项去掉两端的空白和引号
+fn split_items(text: &str) -> Option<Vec<String>> {
+    if text.trim().is_empty() {
+        return Some(Vec::new());
+    }
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                item.push(c);
+            }
+            ',' if !quoted => items.push(std::mem::take(&mut item)),
+            _ => item.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    items.push(item);
+    Some(
+        items
+            .iter()
+            .map(|item| {
+                let item = item.trim();
+                item.strip_prefix('"').and_then(|item| item.strip_suffix('"')).unwrap_or(item).to_string()
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::lines_from_text;
+
+    const FLASH: &str = "#!macro flash_part(name, offset, size)\necho ${name}\nnand write ${ADDR} ${offset} ${size}\n#!endmacro\n\
+                         #!macro all()\n#!call flash_part(kernel, 0x200000, 0x800000)\n#!call flash_part(\"a, b\", 0, 1)\n#!endmacro\n\
+                         #!call all";
+
+    fn split(text: &str) -> Result<(Macros, Vec<ScriptLine>), DirectiveError> {
+        Macros::split(&lines_from_text(text), &Syntax::default(), Some(Path::new("flash.txt")))
+    }
+
+    #[test]
+    fn splits_definitions_from_other_lines() {
+        let (macros, rest) = split(FLASH).unwrap();
+        assert_eq!(macros.defined.len(), 2);
+        assert_eq!(rest.iter().map(|line| line.number).collect::<Vec<_>>(), [9]);
+    }
+
+    #[test]
+    fn expands_nested_calls_with_arguments() {
+        let (macros, rest) = split(FLASH).unwrap();
+        let expanded = macros.expand_call("all", &rest[0], &Syntax::default(), Some(Path::new("flash.txt"))).unwrap();
+        let texts: Vec<_> = expanded.iter().map(|line| line.text.as_str()).collect();
+        // 值中的逗号在引号中; 不是参数的变量保留原样
+        assert_eq!(
+            texts,
+            ["echo kernel", "nand write ${ADDR} 0x200000 0x800000", "echo a, b", "nand write ${ADDR} 0 1"]
+        );
+    }
+
+    #[test]
+    fn keeps_call_line_and_definition_origin() {
+        let (macros, rest) = split(FLASH).unwrap();
+        let expanded = macros.expand_call("all", &rest[0], &Syntax::default(), Some(Path::new("flash.txt"))).unwrap();
+        assert!(expanded.iter().all(|line| line.number == 9));
+        assert_eq!(expanded[1].origin, Some(Origin { file: "flash.txt".into(), line: 3 }));
+    }
+
+    #[test]
+    fn reports_bad_calls() {
+        let (macros, rest) = split(FLASH).unwrap();
+        let call = |args: &str| macros.expand_call(args, &rest[0], &Syntax::default(), None).map_err(|e| e.kind);
+        assert!(matches!(call("flash_part(a, b)"), Err(DirectiveErrorKind::MacroArguments { expected: 3, found: 2, .. })));
+        assert!(matches!(call("missing()"), Err(DirectiveErrorKind::UnknownMacro(_))));
+        assert!(matches!(call("all(\"x)"), Err(DirectiveErrorKind::Usage(CALL_USAGE))));
+    }
+
+    #[test]
+    fn rejects_recursion() {
+        let (macros, rest) = split("#!macro a()\n#!call b()\n#!endmacro\n#!macro b()\n#!call a()\n#!endmacro\n#!call a").unwrap();
+        assert!(matches!(
+            macros.expand_call("a()", &rest[0], &Syntax::default(), None).map_err(|e| e.kind),
+            Err(DirectiveErrorKind::MacroRecursion(_))
+        ));
+    }
+
+    #[test]
+    fn reports_bad_definitions_with_location() {
+        for (text, line) in [("#!macro a(x, x)\n#!endmacro", 1), ("ls\n#!macro a()\nls", 2), ("#!endmacro", 1), ("#!macro a()\n#!macro b()", 1)] {
+            let error = split(text).unwrap_err();
+            assert_eq!((error.file.as_deref(), error.line), (Some(Path::new("flash.txt")), line));
+        }
+    }
+
+    #[test]
+    fn rejects_duplicate_macros() {
+        assert!(matches!(
+            split("#!macro a()\n#!endmacro\n#!macro a()\n#!endmacro").map_err(|e| (e.line, e.kind)),
+            Err((3, DirectiveErrorKind::DuplicateMacro(_)))
+        ));
+        // 同一处定义再次加入时忽略
+        let (mut macros, _) = split("#!macro a()\n#!endmacro").unwrap();
+        let (again, _) = split("#!macro a()\n#!endmacro").unwrap();
+        assert!(macros.extend(again, None).is_ok());
+    }
+}
@@ -0,0 +1,210 @@
+//! 测试结果: 记录多行发送中每一步 (发送的行、`#!capture` 和 `#!expect` 检查) 的结果与用时,
+//! 汇总为通过/失败/跳过的步数, 并可导出为 JUnit XML 或 JSON 供 CI 展示
+
+use chrono::{DateTime, Local};
+use serde_json::{json, Value};
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepStatus {
+    Passed,
+    /// 内容为失败原因
+    Failed(String),
+    /// 之前的步骤失败或发送被中止, 没有执行
+    Skipped,
+}
+
+impl StepStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StepStatus::Passed => "passed",
+            StepStatus::Failed(_) => "failed",
+            StepStatus::Skipped => "skipped",
+        }
+    }
+}
+
+/// file 和 line 指向该步真正所在的位置, 被包含的行指向被包含的文件
+#[derive(Debug, Clone)]
+pub struct StepResult {
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    /// "send"、"capture"、"expect" 或 "expect-not"
+    pub kind: &'static str,
+    /// 发送的内容 (变量替换后), 指令为其原文
+    pub text: String,
+    pub status: StepStatus,
+    pub duration: Duration,
+}
+
+impl StepResult {
+    /// "文件:行号 内容" 形式的名称
+    pub fn name(&self) -> String {
+        match &self.file {
+            Some(file) => format!("{}:{} {}", file.display(), self.line, self.text),
+            None => format!("line {} {}", self.line, self.text),
+        }
+    }
+}
+
+/// 一次多行发送的结果
+#[derive(Debug, Clone)]
+pub struct TestRun {
+    pub started: DateTime<Local>,
+    pub steps: Vec<StepResult>,
+    /// 从开始到结束的总用时, 包括行间隔
+    pub duration: Duration,
+}
+
+impl Default for TestRun {
+    fn default() -> Self {
+        Self { started: Local::now(), steps: Vec::new(), duration: Duration::ZERO }
+    }
+}
+
+impl TestRun {
+    pub fn passed(&self) -> usize {
+        self.count(|status| *status == StepStatus::Passed)
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|status| matches!(status, StepStatus::Failed(_)))
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|status| *status == StepStatus::Skipped)
+    }
+
+    fn count(&self, filter: impl Fn(&StepStatus) -> bool) -> usize {
+        self.steps.iter().filter(|step| filter(&step.status)).count()
+    }
+
+    /// 是否含有 `#!expect` 检查, 有时才需要显示汇总
+    pub fn has_assertions(&self) -> bool {
+        self.steps.iter().any(|step| step.kind.starts_with("expect"))
+    }
+
+    /// JUnit XML, 整个脚本为一个 testsuite, 每一步为一个 testcase
+    pub fn to_junit(&self, name: &str) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let counts = format!(
+            "tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{:.3}\"",
+            self.steps.len(),
+            self.failed(),
+            self.skipped(),
+            self.duration.as_secs_f64()
+        );
+        let _ = writeln!(xml, "<testsuites name=\"tty_sender\" {}>", counts);
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" {} timestamp=\"{}\">",
+            escape_xml(name),
+            counts,
+            self.started.format("%Y-%m-%dT%H:%M:%S")
+        );
+        for step in &self.steps {
+            let classname = step.file.as_ref().map_or_else(|| name.to_string(), |file| file.display().to_string());
+            let _ = write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                escape_xml(&step.name()),
+                escape_xml(&classname),
+                step.duration.as_secs_f64()
+            );
+            match &step.status {
+                StepStatus::Passed => xml.push_str("/>\n"),
+                StepStatus::Failed(message) => {
+                    let _ = writeln!(xml, ">\n      <failure message=\"{}\"/>\n    </testcase>", escape_xml(message));
+                }
+                StepStatus::Skipped => xml.push_str(">\n      <skipped/>\n    </testcase>\n"),
+            }
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+
+    pub fn to_json(&self, name: &str) -> Value {
+        let steps: Vec<_> = self
+            .steps
+            .iter()
+            .map(|step| {
+                let mut entry = json!({
+                    "file": step.file,
+                    "line": step.line,
+                    "kind": step.kind,
+                    "text": step.text,
+                    "status": step.status.name(),
+                    "duration": step.duration.as_secs_f64(),
+                });
+                if let StepStatus::Failed(message) = &step.status {
+                    entry["error"] = json!(message);
+                }
+                entry
+            })
+            .collect();
+        json!({
+            "name": name,
+            "started": self.started.to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            "duration": self.duration.as_secs_f64(),
+            "passed": self.passed(),
+            "failed": self.failed(),
+            "skipped": self.skipped(),
+            "steps": steps,
+        })
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // XML 1.0 不允许除制表符和换行外的控制字符
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(line: usize, text: &str, status: StepStatus) -> StepResult {
+        let file = Some(PathBuf::from("bringup.txt"));
+        StepResult { file, line, kind: "send", text: text.to_string(), status, duration: Duration::from_millis(250) }
+    }
+
+    #[test]
+    fn exports_junit_and_json() {
+        let run = TestRun {
+            steps: vec![
+                step(1, "uname -r", StepStatus::Passed),
+                step(2, "#!expect /6\\.1/", StepStatus::Failed("expected <6.1> & more".to_string())),
+                step(3, "reboot", StepStatus::Skipped),
+            ],
+            duration: Duration::from_secs(2),
+            ..TestRun::default()
+        };
+        assert_eq!((run.passed(), run.failed(), run.skipped()), (1, 1, 1));
+
+        let xml = run.to_junit("bringup.txt");
+        assert!(xml.contains("<testsuite name=\"bringup.txt\" tests=\"3\" failures=\"1\" errors=\"0\" skipped=\"1\" time=\"2.000\""));
+        assert!(xml.contains("<testcase name=\"bringup.txt:1 uname -r\" classname=\"bringup.txt\" time=\"0.250\"/>"));
+        assert!(xml.contains("<failure message=\"expected &lt;6.1&gt; &amp; more\"/>"));
+        assert!(xml.contains("<skipped/>"));
+
+        let json = run.to_json("bringup.txt");
+        assert_eq!(json["failed"], 1);
+        assert_eq!(json["steps"][1]["status"], "failed");
+        assert_eq!(json["steps"][1]["error"], "expected <6.1> & more");
+        assert_eq!(json["steps"][2]["duration"], 0.25);
+    }
+}
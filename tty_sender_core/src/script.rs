@@ -0,0 +1,222 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// 命令文件中的一行, number 从 1 开始
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptLine {
+    pub number: usize,
+    pub text: String,
+    /// 由 `#!include` 展开的行记录其所在文件和行号, 此时 number 是顶层 `#!include` 所在的行
+    pub origin: Option<Origin>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Origin {
+    pub file: PathBuf,
+    pub line: usize,
+}
+
+pub fn lines_from_text(text: &str) -> Vec<ScriptLine> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| ScriptLine { number: i + 1, text: line.to_string(), origin: None })
+        .collect()
+}
+
+/// 行的种类, 由 Syntax::classify 判定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind<'a> {
+    Command,
+    Blank,
+    Comment,
+    /// `--- 步骤名 ---` 分段标记, 内容为去掉两侧短横线后的名称
+    Marker(&'a str),
+    /// `[名称]` 命名段的开头, 名称只含字母、数字和 `-_.`, 以免与 `[ -f x ]` 之类的命令混淆
+    Section(&'a str),
+    /// `#!名称 参数` 形式的指令, 不受注释前缀设置影响; 名称只含小写字母和 `-`, 因此 `#!/bin/sh` 不是指令
+    Directive { name: &'a str, args: &'a str },
+}
+
+/// 命令文件中的命名段: 从 `[名称]` 的下一行到下一个段开头之前
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    /// `[名称]` 所在行号
+    pub line: usize,
+    /// 段内最后一行的行号, 空段时等于 line
+    pub end: usize,
+}
+
+/// 注释与分段标记的写法
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Syntax {
+    /// 以这些前缀开头 (允许前导空白) 的行是注释, 不会发送; 为空时没有注释
+    pub comment_prefixes: Vec<String>,
+}
+
+impl Default for Syntax {
+    fn default() -> Self {
+        Self { comment_prefixes: vec!["#".to_string(), "//".to_string()] }
+    }
+}
+
+impl Syntax {
+    pub fn classify<'a>(&self, text: &'a str) -> LineKind<'a> {
+        let trimmed = text.trim();
+        if trimmed.is_empty() {
+            return LineKind::Blank;
+        }
+        if let Some(inner) = trimmed.strip_prefix("---").and_then(|rest| rest.strip_suffix("---")) {
+            return LineKind::Marker(inner.trim_matches('-').trim());
+        }
+        if let Some(rest) = trimmed.strip_prefix("#!") {
+            let end = rest.find(|c: char| !(c.is_ascii_lowercase() || c == '-')).unwrap_or(rest.len());
+            let (name, args) = rest.split_at(end);
+            if !name.is_empty() && (args.is_empty() || args.starts_with(char::is_whitespace)) {
+                return LineKind::Directive { name, args: args.trim() };
+            }
+        }
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || "-_.".contains(c)) {
+                return LineKind::Section(name);
+            }
+        }
+        if self.comment_prefixes.iter().any(|prefix| !prefix.is_empty() && trimmed.starts_with(prefix.as_str())) {
+            return LineKind::Comment;
+        }
+        LineKind::Command
+    }
+}
+
+/// 按出现顺序列出所有命名段; 第一个段之前的行不属于任何段
+pub fn sections(lines: &[ScriptLine], syntax: &Syntax) -> Vec<Section> {
+    let mut sections: Vec<Section> = Vec::new();
+    for line in lines {
+        if let LineKind::Section(name) = syntax.classify(&line.text) {
+            sections.push(Section { name: name.to_string(), line: line.number, end: line.number });
+        } else if let Some(current) = sections.last_mut() {
+            current.end = line.number;
+        }
+    }
+    sections
+}
+
+/// 取名为 name 的段中的行 (不含 `[名称]` 本身); 同名段取第一个
+pub fn section_lines(lines: &[ScriptLine], syntax: &Syntax, name: &str) -> Option<Vec<ScriptLine>> {
+    let section = sections(lines, syntax).into_iter().find(|section| section.name == name)?;
+    Some(
+        lines
+            .iter()
+            .filter(|line| line.number > section.line && line.number <= section.end)
+            .cloned()
+            .collect(),
+    )
+}
+
+/// 超出文件的行号范围
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeError {
+    pub from: usize,
+    pub to: usize,
+    pub total: usize,
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid line range {}-{} (file has {} lines)", self.from, self.to, self.total)
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+/// 取第 from 到 to 行 (从 1 开始, 包含两端)
+pub fn select_range(lines: &[ScriptLine], from: usize, to: usize) -> Result<Vec<ScriptLine>, RangeError> {
+    let total = lines.len();
+    if from == 0 || from > to || to > total {
+        return Err(RangeError { from, to, total });
+    }
+    Ok(lines[from - 1..to].to_vec())
+}
+
+/// 解析 "N-M" 或单个 "N" 形式的行范围
+pub fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (from, to) = match text.split_once('-') {
+        Some((from, to)) => (from.trim().parse().ok()?, to.trim().parse().ok()?),
+        None => {
+            let line = text.trim().parse().ok()?;
+            (line, line)
+        }
+    };
+    Some((from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_lines_from_one() {
+        let lines = lines_from_text("ls\r\n\npwd");
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], ScriptLine { number: 1, text: "ls".to_string(), origin: None });
+        assert_eq!(lines[1].text, "");
+        assert_eq!(lines[2].number, 3);
+    }
+
+    #[test]
+    fn selects_inclusive_ranges() {
+        let lines = lines_from_text("a\nb\nc\nd");
+        let texts: Vec<_> = select_range(&lines, 2, 3).unwrap().into_iter().map(|line| line.text).collect();
+        assert_eq!(texts, vec!["b", "c"]);
+        assert_eq!(select_range(&lines, 3, 5), Err(RangeError { from: 3, to: 5, total: 4 }));
+        assert!(select_range(&lines, 0, 1).is_err());
+        assert!(select_range(&lines, 3, 2).is_err());
+    }
+
+    #[test]
+    fn classifies_comments_and_markers() {
+        let syntax = Syntax::default();
+        assert_eq!(syntax.classify("  # note"), LineKind::Comment);
+        assert_eq!(syntax.classify("// note"), LineKind::Comment);
+        assert_eq!(syntax.classify("--- flash u-boot ---"), LineKind::Marker("flash u-boot"));
+        assert_eq!(syntax.classify("------"), LineKind::Marker(""));
+        assert_eq!(syntax.classify("---"), LineKind::Command);
+        assert_eq!(syntax.classify("echo a # b"), LineKind::Command);
+        assert_eq!(
+            syntax.classify("#!include  common/login.txt root "),
+            LineKind::Directive { name: "include", args: "common/login.txt root" }
+        );
+        assert_eq!(syntax.classify("#!/bin/sh"), LineKind::Comment);
+        assert_eq!(syntax.classify(" \t"), LineKind::Blank);
+        let no_comments = Syntax { comment_prefixes: Vec::new() };
+        assert_eq!(no_comments.classify("# root prompt"), LineKind::Command);
+    }
+
+    #[test]
+    fn finds_named_sections() {
+        let syntax = Syntax::default();
+        assert_eq!(syntax.classify(" [flash-uboot] "), LineKind::Section("flash-uboot"));
+        assert_eq!(syntax.classify("[ -f /etc/issue ]"), LineKind::Command);
+        assert_eq!(syntax.classify("[]"), LineKind::Command);
+
+        let lines = lines_from_text("setup\n[flash]\nfatload\nboot\n\n[net]\n[dump]\nmd 0");
+        let found = sections(&lines, &syntax);
+        let summary: Vec<_> = found.iter().map(|s| (s.name.as_str(), s.line, s.end)).collect();
+        assert_eq!(summary, vec![("flash", 2, 5), ("net", 6, 6), ("dump", 7, 8)]);
+
+        let texts = |name| {
+            section_lines(&lines, &syntax, name).map(|lines| lines.into_iter().map(|l| l.text).collect::<Vec<_>>())
+        };
+        assert_eq!(texts("flash"), Some(vec!["fatload".to_string(), "boot".to_string(), String::new()]));
+        assert_eq!(texts("net"), Some(vec![]));
+        assert_eq!(texts("missing"), None);
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range(" 3 - 7 "), Some((3, 7)));
+        assert_eq!(parse_range("12"), Some((12, 12)));
+        assert_eq!(parse_range("3-"), None);
+        assert_eq!(parse_range("x"), None);
+    }
+}
@@ -0,0 +1,248 @@
+//! 退出状态检查: 在 shell 命令后附加 `; echo __RC=$?`, 从目标的输出中读出命令的退出状态。
+//! 续行、引号未结束的行、heredoc 和 if/for/while 等复合命令中间的行不附加,
+//! 复合命令在结束的那一行 (如 `done`) 附加; heredoc 的命令无法附加, 不检查
+
+use regex::Regex;
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+/// 附加在命令后的探测
+const PROBE: &str = "; echo __RC=$?";
+
+/// 输出中的退出状态, 命令的输出不以换行结尾时出现在同一行末尾
+pub(crate) fn status_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?m)__RC=(\d+)\r?$").unwrap())
+}
+
+/// 从去掉控制序列的输出中取出退出状态; 回显的 `echo __RC=$?` 不会匹配
+pub fn parse_status(text: &str) -> Option<i32> {
+    status_pattern().captures(text)?[1].parse().ok()
+}
+
+/// 从输出记录的一行中去掉探测; 整行只有退出状态时返回 None
+pub(crate) fn hide_probe(line: &str) -> Option<Cow<'_, str>> {
+    let body = line.trim_end_matches(['\r', '\n']);
+    if let Some(found) = status_pattern().find(body).filter(|found| found.end() == body.len()) {
+        if found.start() == 0 {
+            return None;
+        }
+        return Some(Cow::Owned(format!("{}{}", &body[..found.start()], &line[body.len()..])));
+    }
+    if line.contains(PROBE) {
+        return Some(Cow::Owned(line.replace(PROBE, "")));
+    }
+    Some(Cow::Borrowed(line))
+}
+
+/// text 末尾是否可能是分几次到达的探测回显的开头部分
+pub(crate) fn ends_with_partial_probe(text: &str) -> bool {
+    (1..PROBE.len()).any(|len| text.ends_with(&PROBE[..len]))
+}
+
+/// 逐行判断能否在行尾附加探测, 需要按发送的顺序看到每一行
+#[derive(Debug, Default)]
+pub struct Prober {
+    /// 上一行结束时未闭合的引号
+    quote: Option<char>,
+    /// 正在发送的 heredoc 的结束标记, 以及是否允许以 tab 缩进 (`<<-`)
+    heredoc: Option<(String, bool)>,
+    /// 未结束的复合命令层数
+    depth: usize,
+}
+
+impl Prober {
+    /// 返回附加了探测的命令; 该行不是一条完整命令的结尾时返回 None
+    pub fn probe(&mut self, line: &str) -> Option<String> {
+        if let Some((end, tabs)) = &self.heredoc {
+            let body = if *tabs { line.trim_start_matches('\t') } else { line };
+            if body.trim_end() == end {
+                self.heredoc = None;
+            }
+            return None;
+        }
+
+        let scan = self.scan(line);
+        let mut command_start = true;
+        for token in &scan.tokens {
+            match token.as_str() {
+                ";" | ";;" | "&" | "|" | "&&" | "||" | "(" | ")" => command_start = true,
+                _ if !command_start => {}
+                "if" | "while" | "until" | "{" => self.depth += 1,
+                "for" | "case" | "select" => {
+                    self.depth += 1;
+                    command_start = false;
+                }
+                "fi" | "done" | "esac" | "}" => {
+                    self.depth = self.depth.saturating_sub(1);
+                    command_start = false;
+                }
+                "then" | "do" | "else" | "elif" | "!" | "time" => {}
+                _ => command_start = false,
+            }
+        }
+        if let Some(heredoc) = scan.heredoc {
+            self.heredoc = Some(heredoc);
+            return None;
+        }
+        if scan.continued || self.quote.is_some() || self.depth > 0 {
+            return None;
+        }
+        match scan.tokens.last().map(String::as_str) {
+            None | Some("|" | "&&" | "||" | "&" | "(") => return None,
+            _ => {}
+        }
+
+        let code = line[..scan.comment.unwrap_or(line.len())].trim_end();
+        let code = code.strip_suffix(';').filter(|code| !code.ends_with(';')).unwrap_or(code);
+        Some(match scan.comment {
+            Some(comment) => format!("{}{} {}", code, PROBE, &line[comment..]),
+            None => format!("{}{}", code, PROBE),
+        })
+    }
+
+    /// 把一行粗略地分成单词和操作符, 同时记录引号、注释、续行和 heredoc
+    fn scan(&mut self, line: &str) -> Scan {
+        let mut scan = Scan::default();
+        let mut word = String::new();
+        let mut chars = line.char_indices().peekable();
+        let finish = |word: &mut String, tokens: &mut Vec<String>| {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(word));
+            }
+        };
+        while let Some((index, c)) = chars.next() {
+            if let Some(quote) = self.quote {
+                match c {
+                    '\\' if quote == '"' => {
+                        chars.next();
+                    }
+                    _ if c == quote => self.quote = None,
+                    _ => {}
+                }
+                word.push(c);
+                continue;
+            }
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, next)) => {
+                        word.push(c);
+                        word.push(next);
+                    }
+                    None => scan.continued = true,
+                },
+                '\'' | '"' => {
+                    self.quote = Some(c);
+                    word.push(c);
+                }
+                '#' if word.is_empty() => {
+                    scan.comment = Some(index);
+                    break;
+                }
+                ' ' | '\t' => finish(&mut word, &mut scan.tokens),
+                ';' | '&' | '|' | '(' | ')' => {
+                    finish(&mut word, &mut scan.tokens);
+                    let mut token = c.to_string();
+                    if matches!(c, ';' | '&' | '|') && chars.peek().is_some_and(|(_, next)| *next == c) {
+                        chars.next();
+                        token.push(c);
+                    }
+                    scan.tokens.push(token);
+                }
+                '<' if chars.peek().is_some_and(|(_, next)| *next == '<') && !word.ends_with('<') => {
+                    chars.next();
+                    if chars.peek().is_some_and(|(_, next)| *next == '<') {
+                        // `<<<` 是 here-string
+                        chars.next();
+                        word.push_str("<<<");
+                        continue;
+                    }
+                    let tabs = chars.next_if(|(_, next)| *next == '-').is_some();
+                    while chars.next_if(|(_, next)| *next == ' ' || *next == '\t').is_some() {}
+                    let mut end = String::new();
+                    while let Some((_, next)) = chars.next_if(|(_, next)| !next.is_whitespace() && !";&|<>()".contains(*next)) {
+                        if next != '\'' && next != '"' && next != '\\' {
+                            end.push(next);
+                        }
+                    }
+                    if !end.is_empty() {
+                        scan.heredoc = Some((end, tabs));
+                    }
+                    word.push_str("<<");
+                }
+                _ => word.push(c),
+            }
+        }
+        finish(&mut word, &mut scan.tokens);
+        scan
+    }
+}
+
+#[derive(Debug, Default)]
+struct Scan {
+    tokens: Vec<String>,
+    /// 注释开始的位置
+    comment: Option<usize>,
+    /// 以 `\` 结尾, 命令在下一行继续
+    continued: bool,
+    heredoc: Option<(String, bool)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe_all(lines: &[&str]) -> Vec<Option<String>> {
+        let mut prober = Prober::default();
+        lines.iter().map(|line| prober.probe(line)).collect()
+    }
+
+    #[test]
+    fn probes_complete_commands_only() {
+        assert_eq!(
+            probe_all(&["ls -l", "cd /tmp;", "make # build", "echo '#' \"a;b\""]),
+            [
+                Some("ls -l; echo __RC=$?".to_string()),
+                Some("cd /tmp; echo __RC=$?".to_string()),
+                Some("make; echo __RC=$? # build".to_string()),
+                Some("echo '#' \"a;b\"; echo __RC=$?".to_string()),
+            ]
+        );
+        let probed = probe_all(&[
+            "for i in 1 2; do",
+            "  echo $i",
+            "done",
+            "tar czf a.tgz \\",
+            "  dir",
+            "dmesg |",
+            "  tail",
+            "sleep 10 &",
+            "echo 'one",
+            "two'",
+            "cat <<-'EOF' > f",
+            "\techo done",
+            "\tEOF",
+            "cat <<< done",
+            "if true; then echo ok; fi",
+        ]);
+        let probed: Vec<_> = probed.iter().map(Option::is_some).collect();
+        assert_eq!(
+            probed,
+            [false, false, true, false, true, false, true, false, false, true, false, false, false, true, true]
+        );
+    }
+
+    #[test]
+    fn parses_and_hides_status() {
+        assert_eq!(parse_status("$ ls; echo __RC=$?\r\nfile\r\n__RC=2\r\n$ "), Some(2));
+        assert_eq!(parse_status("$ printf x; echo __RC=$?\r\nx__RC=0\r\n"), Some(0));
+        assert_eq!(parse_status("$ ls; echo __RC=$?\r\n"), None);
+
+        assert_eq!(hide_probe("$ ls; echo __RC=$?\r\n").unwrap(), "$ ls\r\n");
+        assert!(hide_probe("__RC=0\r\n").is_none());
+        assert!(ends_with_partial_probe("$ false; echo __R"));
+        assert!(!ends_with_partial_probe("$ "));
+        assert_eq!(hide_probe("x__RC=0\n").unwrap(), "x\n");
+        assert_eq!(hide_probe("plain\n").unwrap(), "plain\n");
+    }
+}
@@ -0,0 +1,421 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+mod multiplexer;
+mod output;
+#[cfg(unix)]
+mod pty;
+mod qemu;
+mod stream;
+mod vnc;
+#[cfg(windows)]
+mod window;
+
+pub use multiplexer::{MultiplexerTarget, Pane};
+pub use output::OutputBuffer;
+#[cfg(unix)]
+pub use pty::PtyTarget;
+pub use qemu::{KeyMethod, Monitor, QemuTarget};
+pub use stream::StreamTarget;
+pub use vnc::VncTarget;
+#[cfg(windows)]
+pub use window::WindowTarget;
+
+/// 发送目标: 绑定的窗口、串口、TCP 连接等
+pub trait Target: Send {
+    /// 用于消息和日志的目标描述
+    fn describe(&self) -> String;
+    /// 开始发送前调用, 例如将目标窗口切到前台
+    fn prepare(&mut self) {}
+    fn send_str(&mut self, text: &str) -> io::Result<()>;
+    fn send_enter(&mut self) -> io::Result<()>;
+    /// 能读取输出的目标返回其输出缓冲, 用于等待提示符
+    fn output(&self) -> Option<OutputBuffer> {
+        None
+    }
+}
+
+/// 多个线程共用的目标, 每次发送时加锁, 例如终端窗格的按键与多行发送交替写入同一个连接
+#[derive(Clone)]
+pub struct SharedTarget {
+    name: String,
+    output: Option<OutputBuffer>,
+    inner: Arc<Mutex<Box<dyn Target>>>,
+}
+
+impl SharedTarget {
+    pub fn new(target: Box<dyn Target>) -> Self {
+        Self { name: target.describe(), output: target.output(), inner: Arc::new(Mutex::new(target)) }
+    }
+}
+
+impl Target for SharedTarget {
+    fn describe(&self) -> String {
+        self.name.clone()
+    }
+
+    fn prepare(&mut self) {
+        self.inner.lock().unwrap().prepare();
+    }
+
+    fn send_str(&mut self, text: &str) -> io::Result<()> {
+        self.inner.lock().unwrap().send_str(text)
+    }
+
+    fn send_enter(&mut self) -> io::Result<()> {
+        self.inner.lock().unwrap().send_enter()
+    }
+
+    fn output(&self) -> Option<OutputBuffer> {
+        self.output.clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Cr,
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_bytes(self) -> &'static [u8] {
+        match self {
+            LineEnding::Cr => b"\r",
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf => b"\r\n",
+        }
+    }
+
+    fn parse(value: &str) -> Option<LineEnding> {
+        match value.to_ascii_lowercase().as_str() {
+            "cr" => Some(LineEnding::Cr),
+            "lf" => Some(LineEnding::Lf),
+            "crlf" => Some(LineEnding::CrLf),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            LineEnding::Cr => "cr",
+            LineEnding::Lf => "lf",
+            LineEnding::CrLf => "crlf",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowSelector {
+    /// 标题中包含该文本的第一个可见顶层窗口
+    Title(String),
+    Handle(isize),
+}
+
+/// 目标规则, 格式为 `类型:地址[?选项=值&...]`, 例如
+/// `serial:/dev/ttyUSB0?baud=115200`、`tcp:192.168.1.10:23?eol=crlf`、
+/// `pty:/dev/pts/3`、`spawn:qemu-system-x86_64 -nographic -serial stdio`、
+/// `tmux:work:0.1`、`screen:board?window=2`、`qmp:/tmp/qmp.sock`、`hmp:127.0.0.1:5555`、
+/// `window:PuTTY`、`hwnd:0x1a2b`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetSpec {
+    Serial { port: String, baud: u32, eol: LineEnding },
+    Tcp { address: String, eol: LineEnding },
+    Pty { path: PathBuf, eol: LineEnding },
+    /// 在新的伪终端中启动命令
+    Spawn { command: String, eol: LineEnding },
+    Multiplexer(Pane),
+    /// QEMU 监视器, address 为 TCP 地址或 Unix 套接字路径
+    Qemu { address: String, monitor: Monitor },
+    /// VNC 服务器, 省略端口时为 5900
+    Vnc { address: String, password: Option<String> },
+    Window(WindowSelector),
+}
+
+#[derive(Debug)]
+pub enum TargetError {
+    InvalidRule(String),
+    UnknownKind(String),
+    InvalidOption { option: String, value: String },
+    Unsupported(String),
+    NotFound(String),
+    Open { target: String, source: io::Error },
+}
+
+impl fmt::Display for TargetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetError::InvalidRule(rule) => write!(f, "invalid target rule: {}", rule),
+            TargetError::UnknownKind(kind) => write!(f, "unknown target type: {}", kind),
+            TargetError::InvalidOption { option, value } => {
+                write!(f, "invalid target option {}={}", option, value)
+            }
+            TargetError::Unsupported(kind) => {
+                write!(f, "target type {} is not supported on this platform", kind)
+            }
+            TargetError::NotFound(target) => write!(f, "target not found: {}", target),
+            TargetError::Open { target, source } => write!(f, "cannot open {}: {}", target, source),
+        }
+    }
+}
+
+impl std::error::Error for TargetError {}
+
+const DEFAULT_BAUD: u32 = 115200;
+
+impl TargetSpec {
+    pub fn parse(rule: &str) -> Result<TargetSpec, TargetError> {
+        let (kind, rest) = rule
+            .split_once(':')
+            .ok_or_else(|| TargetError::InvalidRule(rule.to_string()))?;
+        // spawn 的命令中可能含有 `?` (如通配符、URL), 只有最后一个 `?` 之后全是已知选项时才当作选项
+        let split = match kind {
+            "spawn" => rest.rsplit_once('?').filter(|(_, query)| {
+                query.split('&').all(|pair| pair.split_once('=').is_some_and(|(option, _)| option == "eol"))
+            }),
+            _ => rest.split_once('?'),
+        };
+        let (address, query) = split.unwrap_or((rest, ""));
+        if address.is_empty() {
+            return Err(TargetError::InvalidRule(rule.to_string()));
+        }
+
+        let mut baud = DEFAULT_BAUD;
+        let mut eol = LineEnding::Cr;
+        let mut socket = None;
+        let mut window = None;
+        let mut method = KeyMethod::SendKey;
+        let mut password = None;
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (option, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let invalid = || TargetError::InvalidOption {
+                option: option.to_string(),
+                value: value.to_string(),
+            };
+            match option {
+                "baud" => baud = value.parse().map_err(|_| invalid())?,
+                "eol" => eol = LineEnding::parse(value).ok_or_else(invalid)?,
+                "socket" if kind == "tmux" => socket = Some(value.to_string()),
+                "window" if kind == "screen" => window = Some(value.to_string()),
+                "method" if kind == "qmp" => method = KeyMethod::parse(value).ok_or_else(invalid)?,
+                "password" if kind == "vnc" => password = Some(value.to_string()),
+                _ => return Err(invalid()),
+            }
+        }
+
+        match kind {
+            "serial" => Ok(TargetSpec::Serial { port: address.to_string(), baud, eol }),
+            "tcp" => Ok(TargetSpec::Tcp { address: address.to_string(), eol }),
+            "pty" => Ok(TargetSpec::Pty { path: PathBuf::from(address), eol }),
+            "spawn" => Ok(TargetSpec::Spawn { command: address.to_string(), eol }),
+            "tmux" => Ok(TargetSpec::Multiplexer(Pane::Tmux { pane: address.to_string(), socket })),
+            "screen" => Ok(TargetSpec::Multiplexer(Pane::Screen { session: address.to_string(), window })),
+            "qmp" => Ok(TargetSpec::Qemu { address: address.to_string(), monitor: Monitor::Qmp(method) }),
+            "hmp" => Ok(TargetSpec::Qemu { address: address.to_string(), monitor: Monitor::Hmp }),
+            "vnc" => {
+                let address = if address.contains(':') {
+                    address.to_string()
+                } else {
+                    format!("{}:{}", address, vnc::DEFAULT_VNC_PORT)
+                };
+                Ok(TargetSpec::Vnc { address, password })
+            }
+            "window" => Ok(TargetSpec::Window(WindowSelector::Title(address.to_string()))),
+            "hwnd" => {
+                let digits = address.trim_start_matches("0x").trim_start_matches("0X");
+                isize::from_str_radix(digits, 16)
+                    .map(|handle| TargetSpec::Window(WindowSelector::Handle(handle)))
+                    .map_err(|_| TargetError::InvalidRule(rule.to_string()))
+            }
+            _ => Err(TargetError::UnknownKind(kind.to_string())),
+        }
+    }
+
+    pub fn open(&self) -> Result<Box<dyn Target>, TargetError> {
+        let describe = self.to_string();
+        let open_error = |source: io::Error| TargetError::Open { target: describe.clone(), source };
+        match self {
+            TargetSpec::Serial { port, baud, eol } => {
+                Ok(Box::new(StreamTarget::open_serial(port, *baud, *eol).map_err(open_error)?))
+            }
+            TargetSpec::Tcp { address, eol } => {
+                Ok(Box::new(StreamTarget::open_tcp(address, *eol).map_err(open_error)?))
+            }
+            #[cfg(unix)]
+            TargetSpec::Pty { path, eol } => {
+                Ok(Box::new(StreamTarget::open_pty(path, *eol).map_err(open_error)?))
+            }
+            #[cfg(not(unix))]
+            TargetSpec::Pty { .. } => Err(TargetError::Unsupported("pty".to_string())),
+            #[cfg(unix)]
+            TargetSpec::Spawn { command, eol } => {
+                Ok(Box::new(PtyTarget::spawn(command, *eol).map_err(open_error)?))
+            }
+            #[cfg(not(unix))]
+            TargetSpec::Spawn { .. } => Err(TargetError::Unsupported("spawn".to_string())),
+            TargetSpec::Multiplexer(pane) => Ok(Box::new(MultiplexerTarget::open(pane.clone())?)),
+            TargetSpec::Qemu { address, monitor } => {
+                Ok(Box::new(QemuTarget::connect(address, *monitor).map_err(open_error)?))
+            }
+            TargetSpec::Vnc { address, password } => {
+                Ok(Box::new(VncTarget::connect(address, password.as_deref()).map_err(open_error)?))
+            }
+            #[cfg(windows)]
+            TargetSpec::Window(selector) => Ok(Box::new(WindowTarget::find(selector)?)),
+            #[cfg(not(windows))]
+            TargetSpec::Window(_) => Err(TargetError::Unsupported("window".to_string())),
+        }
+    }
+}
+
+impl fmt::Display for TargetSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetSpec::Serial { port, baud, eol } => {
+                write!(f, "serial:{}?baud={}&eol={}", port, baud, eol.name())
+            }
+            TargetSpec::Tcp { address, eol } => write!(f, "tcp:{}?eol={}", address, eol.name()),
+            TargetSpec::Pty { path, eol } => write!(f, "pty:{}?eol={}", path.display(), eol.name()),
+            TargetSpec::Spawn { command, eol } => write!(f, "spawn:{}?eol={}", command, eol.name()),
+            TargetSpec::Multiplexer(Pane::Tmux { pane, socket: Some(socket) }) => {
+                write!(f, "tmux:{}?socket={}", pane, socket)
+            }
+            TargetSpec::Multiplexer(Pane::Tmux { pane, socket: None }) => write!(f, "tmux:{}", pane),
+            TargetSpec::Multiplexer(Pane::Screen { session, window: Some(window) }) => {
+                write!(f, "screen:{}?window={}", session, window)
+            }
+            TargetSpec::Multiplexer(Pane::Screen { session, window: None }) => write!(f, "screen:{}", session),
+            TargetSpec::Qemu { address, monitor: Monitor::Qmp(KeyMethod::SendKey) } => write!(f, "qmp:{}", address),
+            TargetSpec::Qemu { address, monitor: Monitor::Qmp(method) } => {
+                write!(f, "qmp:{}?method={}", address, method.name())
+            }
+            TargetSpec::Qemu { address, monitor: Monitor::Hmp } => write!(f, "hmp:{}", address),
+            // 不在消息和日志中显示密码
+            TargetSpec::Vnc { address, password: Some(_) } => write!(f, "vnc:{}?password=***", address),
+            TargetSpec::Vnc { address, password: None } => write!(f, "vnc:{}", address),
+            TargetSpec::Window(WindowSelector::Title(title)) => write!(f, "window:{}", title),
+            TargetSpec::Window(WindowSelector::Handle(handle)) => write!(f, "hwnd:{:#x}", handle),
+        }
+    }
+}
+
+/// 本机上可用的目标 (规则, 说明), 供 list-targets 使用
+pub fn discover() -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    if let Ok(ports) = serialport::available_ports() {
+        for port in ports {
+            let description = match port.port_type {
+                serialport::SerialPortType::UsbPort(info) => {
+                    format!("USB {:04x}:{:04x} {}", info.vid, info.pid, info.product.unwrap_or_default())
+                }
+                serialport::SerialPortType::PciPort => "PCI".to_string(),
+                serialport::SerialPortType::BluetoothPort => "Bluetooth".to_string(),
+                serialport::SerialPortType::Unknown => String::new(),
+            };
+            found.push((format!("serial:{}", port.port_name), description));
+        }
+    }
+    found.extend(multiplexer::list_panes());
+    #[cfg(windows)]
+    found.extend(window::list_windows());
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rules_with_options() {
+        assert_eq!(
+            TargetSpec::parse("serial:/dev/ttyUSB0?baud=9600&eol=crlf").unwrap(),
+            TargetSpec::Serial { port: "/dev/ttyUSB0".to_string(), baud: 9600, eol: LineEnding::CrLf }
+        );
+        assert_eq!(
+            TargetSpec::parse("tcp:10.0.0.2:23").unwrap(),
+            TargetSpec::Tcp { address: "10.0.0.2:23".to_string(), eol: LineEnding::Cr }
+        );
+        assert_eq!(
+            TargetSpec::parse("hwnd:0x1A2b").unwrap(),
+            TargetSpec::Window(WindowSelector::Handle(0x1a2b))
+        );
+    }
+
+    #[test]
+    fn vnc_rule_defaults_port_and_hides_password() {
+        let spec = TargetSpec::parse("vnc:kvm.lab?password=secret").unwrap();
+        assert_eq!(
+            spec,
+            TargetSpec::Vnc { address: "kvm.lab:5900".to_string(), password: Some("secret".to_string()) }
+        );
+        assert_eq!(spec.to_string(), "vnc:kvm.lab:5900?password=***");
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        assert!(matches!(TargetSpec::parse("COM3"), Err(TargetError::InvalidRule(_))));
+        assert!(matches!(TargetSpec::parse("serial:"), Err(TargetError::InvalidRule(_))));
+        assert!(matches!(TargetSpec::parse("ftp:host"), Err(TargetError::UnknownKind(_))));
+        assert!(matches!(
+            TargetSpec::parse("serial:COM3?baud=fast"),
+            Err(TargetError::InvalidOption { .. })
+        ));
+        assert!(matches!(
+            TargetSpec::parse("tcp:host:23?parity=odd"),
+            Err(TargetError::InvalidOption { .. })
+        ));
+        assert!(matches!(
+            TargetSpec::parse("serial:COM3?socket=x"),
+            Err(TargetError::InvalidOption { .. })
+        ));
+    }
+
+    #[test]
+    fn keeps_question_marks_in_spawn_commands() {
+        let spawn = |command: &str, eol| TargetSpec::Spawn { command: command.to_string(), eol };
+        assert_eq!(TargetSpec::parse("spawn:ls /dev/tty?").unwrap(), spawn("ls /dev/tty?", LineEnding::Cr));
+        assert_eq!(
+            TargetSpec::parse("spawn:curl http://host/a?b=1&c=2").unwrap(),
+            spawn("curl http://host/a?b=1&c=2", LineEnding::Cr)
+        );
+        assert_eq!(TargetSpec::parse("spawn:ls /dev/tty??eol=lf").unwrap(), spawn("ls /dev/tty?", LineEnding::Lf));
+        assert!(matches!(TargetSpec::parse("spawn:sh?eol=nul"), Err(TargetError::InvalidOption { .. })));
+        let spec = spawn("ls /dev/tty?", LineEnding::CrLf);
+        assert_eq!(TargetSpec::parse(&spec.to_string()).unwrap(), spec);
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let rules = [
+            "serial:COM3?baud=115200&eol=cr",
+            "tcp:host:23?eol=lf",
+            "spawn:/bin/sh -i?eol=cr",
+            "tmux:work:0.1?socket=lab",
+            "screen:board?window=2",
+            "qmp:/tmp/qmp.sock",
+            "qmp:localhost:4444?method=input-send-event",
+            "hmp:127.0.0.1:5555",
+            "vnc:kvm.lab:5901",
+            "window:PuTTY",
+            "hwnd:0x10",
+        ];
+        for rule in rules {
+            assert_eq!(TargetSpec::parse(rule).unwrap().to_string(), rule);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stream_target_writes_line_ending() {
+        let (reader, writer) = std::os::unix::net::UnixStream::pair().unwrap();
+        let mut target = StreamTarget::new("test".to_string(), Box::new(writer), LineEnding::CrLf);
+        target.send_str("ls").unwrap();
+        target.send_enter().unwrap();
+        drop(target);
+        let mut received = String::new();
+        io::Read::read_to_string(&mut &reader, &mut received).unwrap();
+        assert_eq!(received, "ls\r\n");
+    }
+}
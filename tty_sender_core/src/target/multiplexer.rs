@@ -0,0 +1,262 @@
+use super::{OutputBuffer, Target, TargetError};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// 抓取窗格内容的间隔
+const CAPTURE_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 已分配的 screen hardcopy 临时文件数, 使同一进程中的各个目标使用不同的文件
+static HARDCOPY_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// 终端复用器中的窗格
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pane {
+    /// tmux 目标窗格 (`-t` 的写法, 如 `work:0.1`), socket 对应 `-L`
+    Tmux { pane: String, socket: Option<String> },
+    /// GNU screen 会话及其中的窗口
+    Screen { session: String, window: Option<String> },
+}
+
+/// 通过 tmux send-keys / screen stuff 向窗格输入, 并定时抓取窗格内容作为输出
+pub struct MultiplexerTarget {
+    pane: Pane,
+    output: OutputBuffer,
+    stop_capture: Arc<AtomicBool>,
+}
+
+impl MultiplexerTarget {
+    pub fn open(pane: Pane) -> Result<Self, TargetError> {
+        let pane = match pane {
+            // 解析为 %N 形式的窗格 id, 之后窗口重排也不会发错地方
+            Pane::Tmux { pane, socket } => {
+                let id = run(tmux(&socket).args(["display-message", "-p", "-t", &pane, "#{pane_id}"]))
+                    .map_err(|_| TargetError::NotFound(format!("tmux:{}", pane)))?;
+                Pane::Tmux { pane: id.trim().to_string(), socket }
+            }
+            Pane::Screen { session, window } => {
+                let mut query = screen(&session, &window);
+                query.args(["-Q", "windows"]);
+                run(&mut query).map_err(|_| TargetError::NotFound(format!("screen:{}", session)))?;
+                Pane::Screen { session, window }
+            }
+        };
+
+        let output = OutputBuffer::new();
+        let stop_capture = Arc::new(AtomicBool::new(false));
+        let (capture_pane, writer, stop) = (pane.clone(), output.clone(), stop_capture.clone());
+        let hardcopy = hardcopy_path();
+        thread::spawn(move || {
+            let mut previous = String::new();
+            while !stop.load(Ordering::SeqCst) {
+                match capture(&capture_pane, &hardcopy) {
+                    Ok(snapshot) => {
+                        let added = new_output(&previous, &snapshot);
+                        if !added.is_empty() {
+                            writer.push(added.as_bytes());
+                        }
+                        previous = snapshot;
+                    }
+                    // 窗格已关闭
+                    Err(_) => break,
+                }
+                thread::sleep(CAPTURE_INTERVAL);
+            }
+            writer.close();
+        });
+
+        Ok(Self { pane, output, stop_capture })
+    }
+}
+
+impl Target for MultiplexerTarget {
+    fn describe(&self) -> String {
+        match &self.pane {
+            Pane::Tmux { pane, .. } => format!("tmux:{}", pane),
+            Pane::Screen { session, window: Some(window) } => format!("screen:{}?window={}", session, window),
+            Pane::Screen { session, window: None } => format!("screen:{}", session),
+        }
+    }
+
+    fn send_str(&mut self, text: &str) -> io::Result<()> {
+        match &self.pane {
+            Pane::Tmux { pane, socket } => {
+                run(tmux(socket).args(["send-keys", "-t", pane, "-l", "--", text])).map(drop)
+            }
+            Pane::Screen { session, window } => {
+                run(screen(session, window).args(["-X", "stuff", &escape_stuff(text)])).map(drop)
+            }
+        }
+    }
+
+    fn send_enter(&mut self) -> io::Result<()> {
+        match &self.pane {
+            Pane::Tmux { pane, socket } => run(tmux(socket).args(["send-keys", "-t", pane, "Enter"])).map(drop),
+            Pane::Screen { session, window } => run(screen(session, window).args(["-X", "stuff", "^M"])).map(drop),
+        }
+    }
+
+    fn output(&self) -> Option<OutputBuffer> {
+        Some(self.output.clone())
+    }
+}
+
+impl Drop for MultiplexerTarget {
+    fn drop(&mut self) {
+        self.stop_capture.store(true, Ordering::SeqCst);
+    }
+}
+
+fn tmux(socket: &Option<String>) -> Command {
+    let mut command = Command::new("tmux");
+    if let Some(socket) = socket {
+        command.args(["-L", socket]);
+    }
+    command
+}
+
+fn screen(session: &str, window: &Option<String>) -> Command {
+    let mut command = Command::new("screen");
+    command.args(["-S", session]);
+    if let Some(window) = window {
+        command.args(["-p", window]);
+    }
+    command
+}
+
+/// 执行命令并返回标准输出, 失败时把标准错误作为错误信息
+fn run(command: &mut Command) -> io::Result<String> {
+    let output = command.output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(io::Error::other(message));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// screen 的 stuff 会解释 `\` 和 `^` 转义
+fn escape_stuff(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('^', "\\^")
+}
+
+/// 本进程中未用过的 screen hardcopy 临时文件
+fn hardcopy_path() -> PathBuf {
+    let index = HARDCOPY_FILES.fetch_add(1, Ordering::SeqCst);
+    std::env::temp_dir().join(format!("tty_sender_screen_{}_{}", std::process::id(), index))
+}
+
+/// 窗格当前的历史和屏幕内容, 去掉末尾的空行; screen 的内容经 hardcopy 文件读取
+fn capture(pane: &Pane, hardcopy: &Path) -> io::Result<String> {
+    let text = match pane {
+        Pane::Tmux { pane, socket } => {
+            run(tmux(socket).args(["capture-pane", "-p", "-J", "-S", "-", "-t", pane]))?
+        }
+        Pane::Screen { session, window } => {
+            let path_arg = hardcopy.to_string_lossy().into_owned();
+            run(screen(session, window).args(["-X", "hardcopy", "-h", &path_arg]))?;
+            let text = fs::read_to_string(hardcopy)?;
+            let _ = fs::remove_file(hardcopy);
+            text
+        }
+    };
+    Ok(text.trim_end_matches(['\n', ' ']).to_string())
+}
+
+/// 比较前后两次抓取的内容, 得到新增的输出
+fn new_output(previous: &str, current: &str) -> String {
+    if let Some(added) = current.strip_prefix(previous) {
+        return added.to_string();
+    }
+    // 历史行数达到上限后开头会被丢弃: 用上次的最后几行定位
+    let tail_start = previous
+        .rmatch_indices('\n')
+        .nth(2)
+        .map(|(i, _)| i + 1)
+        .unwrap_or(0);
+    let tail = &previous[tail_start..];
+    if !tail.is_empty() {
+        if let Some(pos) = current.find(tail) {
+            return current[pos + tail.len()..].to_string();
+        }
+    }
+    // 无法对齐 (如窗格被清屏), 视为全部是新输出
+    format!("\n{}", current)
+}
+
+/// 本机的 tmux 窗格和 screen 会话 (规则, 说明)
+pub fn list_panes() -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    let format = "#{session_name}:#{window_index}.#{pane_index}\t#{pane_current_command} #{pane_title}";
+    if let Ok(panes) = run(tmux(&None).args(["list-panes", "-a", "-F", format])) {
+        for line in panes.lines() {
+            if let Some((pane, description)) = line.split_once('\t') {
+                found.push((format!("tmux:{}", pane), description.to_string()));
+            }
+        }
+    }
+    // screen -ls 在有会话时也可能返回非 0, 直接读取标准输出
+    if let Ok(sessions) = Command::new("screen").arg("-ls").output() {
+        for line in String::from_utf8_lossy(&sessions.stdout).lines() {
+            let mut fields = line.split('\t').filter(|field| !field.is_empty());
+            if let (Some(session), Some(state)) = (fields.next(), fields.next()) {
+                if line.starts_with('\t') {
+                    found.push((format!("screen:{}", session), state.to_string()));
+                }
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn diffs_appended_and_scrolled_snapshots() {
+        assert_eq!(new_output("$", "$ ls\nfile\n$"), " ls\nfile\n$");
+        assert_eq!(new_output("a\nb\nc\n$ ls", "b\nc\n$ ls\nfile\n$"), "\nfile\n$");
+        assert_eq!(new_output("old", "new"), "\nnew");
+        assert_eq!(escape_stuff(r"echo ^C \n"), r"echo \^C \\n");
+    }
+
+    #[test]
+    fn gives_each_target_its_own_hardcopy_file() {
+        assert_ne!(hardcopy_path(), hardcopy_path());
+    }
+
+    #[test]
+    fn drives_a_tmux_pane() {
+        if Command::new("tmux").arg("-V").output().is_err() {
+            return;
+        }
+        let socket = format!("tty_sender_test_{}", std::process::id());
+        let socket_arg = Some(socket.clone());
+        run(tmux(&socket_arg).args(["new-session", "-d", "-s", "t", "-x", "80", "-y", "24", "/bin/sh"])).unwrap();
+
+        let result = std::panic::catch_unwind(|| {
+            let mut target =
+                MultiplexerTarget::open(Pane::Tmux { pane: "t:0.0".to_string(), socket: socket_arg.clone() }).unwrap();
+            assert!(target.describe().starts_with("tmux:%"));
+            let output = target.output().unwrap();
+            let mark = output.position();
+            target.send_str("echo $((6 * 7))").unwrap();
+            target.send_enter().unwrap();
+            let answer = Regex::new(r"\n42\n").unwrap();
+            assert!(output.wait_for(&answer, mark, Duration::from_secs(10)).is_some());
+        });
+        let _ = run(tmux(&socket_arg).arg("kill-server"));
+        result.unwrap();
+
+        assert!(matches!(
+            MultiplexerTarget::open(Pane::Tmux { pane: "t:0.0".to_string(), socket: Some(socket) }),
+            Err(TargetError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,185 @@
+use regex::Regex;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// 最多保留的输出字节数, 超出后丢弃最早的部分
+const MAX_RETAINED: usize = 1 << 20;
+
+/// 目标的输出, 由后台读线程写入, 发送引擎在其中等待提示符
+#[derive(Clone, Default)]
+pub struct OutputBuffer {
+    inner: Arc<(Mutex<OutputState>, Condvar)>,
+}
+
+#[derive(Default)]
+struct OutputState {
+    text: String,
+    /// text 开头在全部输出中的位置 (字节)
+    start: usize,
+    /// 还不足一个完整 UTF-8 字符的字节
+    pending: Vec<u8>,
+    closed: bool,
+}
+
+impl OutputBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, bytes: &[u8]) {
+        let (lock, changed) = &*self.inner;
+        let mut state = lock.lock().unwrap();
+        let mut data = std::mem::take(&mut state.pending);
+        data.extend_from_slice(bytes);
+
+        let mut rest = data.as_slice();
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(text) => {
+                    state.text.push_str(text);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    state.text.push_str(std::str::from_utf8(valid).unwrap());
+                    match e.error_len() {
+                        Some(len) => {
+                            state.text.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after[len..];
+                        }
+                        None => {
+                            state.pending = after.to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        if state.text.len() > MAX_RETAINED {
+            let mut cut = state.text.len() - MAX_RETAINED;
+            while !state.text.is_char_boundary(cut) {
+                cut += 1;
+            }
+            state.text.drain(..cut);
+            state.start += cut;
+        }
+        changed.notify_all();
+    }
+
+    /// 目标的输出已结束 (例如子进程退出)
+    pub fn close(&self) {
+        let (lock, changed) = &*self.inner;
+        lock.lock().unwrap().closed = true;
+        changed.notify_all();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.0.lock().unwrap().closed
+    }
+
+    /// 目前为止的输出长度, 作为之后读取或等待的起点
+    pub fn position(&self) -> usize {
+        let state = self.inner.0.lock().unwrap();
+        state.start + state.text.len()
+    }
+
+    pub fn text_since(&self, position: usize) -> String {
+        self.read_since(position).0
+    }
+
+    /// position 之后的输出及其结束位置, 供持续读取的一方作为下次的起点
+    pub fn read_since(&self, position: usize) -> (String, usize) {
+        let state = self.inner.0.lock().unwrap();
+        (state.text[state.offset(position)..].to_string(), state.start + state.text.len())
+    }
+
+    /// 等待 position 之后的输出匹配 pattern, 返回匹配结束的位置;
+    /// 超时或输出已结束仍未匹配时返回 None
+    pub fn wait_for(&self, pattern: &Regex, position: usize, timeout: Duration) -> Option<usize> {
+        let deadline = Instant::now() + timeout;
+        let (lock, changed) = &*self.inner;
+        let mut state = lock.lock().unwrap();
+        loop {
+            let offset = state.offset(position);
+            if let Some(found) = pattern.find(&state.text[offset..]) {
+                return Some(state.start + offset + found.end());
+            }
+            let now = Instant::now();
+            if state.closed || now >= deadline {
+                return None;
+            }
+            state = changed.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// 等待 position 之后出现新的输出或输出结束; 超时仍没有时返回 false
+    pub fn wait_new(&self, position: usize, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let (lock, changed) = &*self.inner;
+        let mut state = lock.lock().unwrap();
+        loop {
+            if state.start + state.text.len() > position || state.closed {
+                return true;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            state = changed.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+}
+
+impl OutputState {
+    fn offset(&self, position: usize) -> usize {
+        let mut offset = position.saturating_sub(self.start).min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset += 1;
+        }
+        offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn joins_split_utf8_sequences() {
+        let output = OutputBuffer::new();
+        let bytes = "提示".as_bytes();
+        output.push(&bytes[..2]);
+        output.push(&bytes[2..]);
+        output.push(b"\xff!");
+        assert_eq!(output.text_since(0), "提示\u{fffd}!");
+    }
+
+    #[test]
+    fn waits_only_for_new_output() {
+        let output = OutputBuffer::new();
+        output.push(b"$ ");
+        let mark = output.position();
+        let prompt = Regex::new(r"\$ $").unwrap();
+        assert_eq!(output.wait_for(&prompt, mark, Duration::from_millis(10)), None);
+
+        let writer = output.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            writer.push(b"ls\r\nfile\r\n$ ");
+        });
+        assert_eq!(output.wait_for(&prompt, mark, Duration::from_secs(5)), Some(output.position()));
+        handle.join().unwrap();
+        assert_eq!(output.text_since(mark), "ls\r\nfile\r\n$ ");
+    }
+
+    #[test]
+    fn stops_waiting_when_closed() {
+        let output = OutputBuffer::new();
+        output.close();
+        let pattern = Regex::new("never").unwrap();
+        assert_eq!(output.wait_for(&pattern, 0, Duration::from_secs(5)), None);
+        assert!(output.is_closed());
+    }
+}
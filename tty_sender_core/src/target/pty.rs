@@ -0,0 +1,188 @@
+use super::{LineEnding, OutputBuffer, Target};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command};
+use std::ptr::null_mut;
+use std::thread;
+
+/// 在伪终端中启动一个程序 (shell、`qemu -serial stdio`、厂商命令行等) 并向其输入
+pub struct PtyTarget {
+    command: String,
+    master: File,
+    child: Child,
+    output: OutputBuffer,
+    eol: LineEnding,
+}
+
+impl PtyTarget {
+    pub fn spawn(command_line: &str, eol: LineEnding) -> io::Result<Self> {
+        let args = split_command(command_line)?;
+        let (master, slave) = open_pty()?;
+
+        let mut command = Command::new(&args[0]);
+        command
+            .args(&args[1..])
+            .env("TERM", "vt100")
+            .stdin(slave.try_clone()?)
+            .stdout(slave.try_clone()?)
+            .stderr(slave);
+        unsafe {
+            // 新会话并把伪终端设为控制终端, 使 shell 的作业控制和 Ctrl+C 正常工作
+            command.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = command.spawn()?;
+        // 关闭本进程持有的从端, 子进程退出后读主端才会结束
+        drop(command);
+
+        let output = OutputBuffer::new();
+        let mut reader = master.try_clone()?;
+        let writer = output.clone();
+        thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => writer.push(&buf[..n]),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    // 子进程退出后 Linux 上返回 EIO
+                    Err(_) => break,
+                }
+            }
+            writer.close();
+        });
+
+        Ok(Self {
+            command: command_line.to_string(),
+            master,
+            child,
+            output,
+            eol,
+        })
+    }
+}
+
+impl Target for PtyTarget {
+    fn describe(&self) -> String {
+        format!("spawn:{}", self.command)
+    }
+
+    fn send_str(&mut self, text: &str) -> io::Result<()> {
+        self.master.write_all(text.as_bytes())
+    }
+
+    fn send_enter(&mut self) -> io::Result<()> {
+        self.master.write_all(self.eol.as_bytes())
+    }
+
+    fn output(&self) -> Option<OutputBuffer> {
+        Some(self.output.clone())
+    }
+}
+
+impl Drop for PtyTarget {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn open_pty() -> io::Result<(File, File)> {
+    let mut master = 0;
+    let mut slave = 0;
+    let size = libc::winsize { ws_row: 24, ws_col: 80, ws_xpixel: 0, ws_ypixel: 0 };
+    unsafe {
+        if libc::openpty(&mut master, &mut slave, null_mut(), null_mut(), &size) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let (master, slave) = (File::from_raw_fd(master), File::from_raw_fd(slave));
+        // 主端不应被子进程继承
+        libc::fcntl(master.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC);
+        Ok((master, slave))
+    }
+}
+
+/// 按空白拆分命令行, 支持单引号和双引号
+fn split_command(command_line: &str) -> io::Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    for c in command_line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "unterminated quote in command"));
+    }
+    if in_arg {
+        args.push(current);
+    }
+    if args.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty command"));
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+    use std::time::Duration;
+
+    #[test]
+    fn splits_quoted_arguments() {
+        assert_eq!(
+            split_command(r#"qemu-system-x86_64 -append "console=ttyS0 quiet" -name 'a b'"#).unwrap(),
+            vec!["qemu-system-x86_64", "-append", "console=ttyS0 quiet", "-name", "a b"]
+        );
+        assert!(split_command("  ").is_err());
+        assert!(split_command("sh -c 'echo").is_err());
+    }
+
+    #[test]
+    fn drives_a_shell() {
+        let mut target = PtyTarget::spawn("/bin/sh", LineEnding::Cr).unwrap();
+        let output = target.output().unwrap();
+        let mark = output.position();
+        // 回显的是表达式本身, 只有 shell 计算出的结果才会匹配
+        target.send_str("echo $((6 * 7))").unwrap();
+        target.send_enter().unwrap();
+        let answer = Regex::new(r"\b42\r?\n").unwrap();
+        assert!(output.wait_for(&answer, mark, Duration::from_secs(10)).is_some());
+        assert_eq!(target.describe(), "spawn:/bin/sh");
+    }
+
+    #[test]
+    fn output_closes_when_child_exits() {
+        let mut target = PtyTarget::spawn("/bin/sh", LineEnding::Lf).unwrap();
+        let output = target.output().unwrap();
+        target.send_str("exit").unwrap();
+        target.send_enter().unwrap();
+        let never = Regex::new("never matches").unwrap();
+        assert_eq!(output.wait_for(&never, 0, Duration::from_secs(10)), None);
+        assert!(output.is_closed());
+    }
+}
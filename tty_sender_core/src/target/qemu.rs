@@ -0,0 +1,332 @@
+use super::stream::Socket;
+use super::Target;
+use crate::keys::{parse_keys, us_layout, Key, SpecialKey};
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+/// 虚拟机监视器协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Monitor {
+    /// QMP (`-qmp unix:/tmp/qmp.sock,server,nowait`)
+    Qmp(KeyMethod),
+    /// 文本监视器 (`-monitor unix:/tmp/hmp.sock,server,nowait`), 使用 sendkey 命令
+    Hmp,
+}
+
+/// QMP 发送按键的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyMethod {
+    SendKey,
+    InputSendEvent,
+}
+
+impl KeyMethod {
+    pub(crate) fn parse(value: &str) -> Option<KeyMethod> {
+        match value {
+            "send-key" => Some(KeyMethod::SendKey),
+            "input-send-event" => Some(KeyMethod::InputSendEvent),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            KeyMethod::SendKey => "send-key",
+            KeyMethod::InputSendEvent => "input-send-event",
+        }
+    }
+}
+
+const HMP_PROMPT: &str = "(qemu) ";
+
+/// 通过 QEMU 监视器向虚拟机发送真实按键, 适用于图形控制台、安装程序和 BIOS 界面
+pub struct QemuTarget {
+    address: String,
+    monitor: Monitor,
+    reader: BufReader<Socket>,
+    writer: Socket,
+}
+
+impl QemuTarget {
+    pub fn connect(address: &str, monitor: Monitor) -> io::Result<Self> {
+        let writer = Socket::connect(address)?;
+        let mut target = Self {
+            address: address.to_string(),
+            monitor,
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+        };
+        match monitor {
+            Monitor::Qmp(_) => {
+                // 先读欢迎信息, 再进入命令模式
+                let greeting = target.read_message()?;
+                if greeting.get("QMP").is_none() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "not a QMP socket"));
+                }
+                target.qmp_command(json!({ "execute": "qmp_capabilities" }))?;
+            }
+            Monitor::Hmp => target.read_hmp_prompt()?,
+        }
+        Ok(target)
+    }
+
+    /// 同时按下 keys 中的键 (修饰键在前) 后松开
+    fn press(&mut self, keys: &[&str]) -> io::Result<()> {
+        match self.monitor {
+            Monitor::Qmp(KeyMethod::SendKey) => {
+                let keys: Vec<Value> = keys.iter().map(|key| json!({ "type": "qcode", "data": key })).collect();
+                self.qmp_command(json!({ "execute": "send-key", "arguments": { "keys": keys } }))
+            }
+            Monitor::Qmp(KeyMethod::InputSendEvent) => {
+                let event = |key: &&str, down: bool| {
+                    json!({ "type": "key", "data": { "down": down, "key": { "type": "qcode", "data": key } } })
+                };
+                let mut events: Vec<Value> = keys.iter().map(|key| event(key, true)).collect();
+                events.extend(keys.iter().rev().map(|key| event(key, false)));
+                self.qmp_command(json!({ "execute": "input-send-event", "arguments": { "events": events } }))
+            }
+            Monitor::Hmp => {
+                writeln!(self.writer, "sendkey {}", keys.join("-"))?;
+                self.writer.flush()?;
+                self.read_hmp_prompt()
+            }
+        }
+    }
+
+    fn press_key(&mut self, key: Key) -> io::Result<()> {
+        match key {
+            Key::Special(special) => self.press(&[special_qcode(special)]),
+            Key::Char(c) => {
+                let (base, shift) = us_layout(c).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("no key for {:?} on a US keyboard", c))
+                })?;
+                let qcode = char_qcode(base);
+                if shift {
+                    self.press(&["shift", &qcode])
+                } else {
+                    self.press(&[&qcode])
+                }
+            }
+        }
+    }
+
+    fn qmp_command(&mut self, command: Value) -> io::Result<()> {
+        self.writer.write_all(command.to_string().as_bytes())?;
+        self.writer.write_all(b"\r\n")?;
+        self.writer.flush()?;
+        loop {
+            let reply = self.read_message()?;
+            if reply.get("return").is_some() {
+                return Ok(());
+            }
+            if let Some(error) = reply.get("error") {
+                let desc = error.get("desc").and_then(Value::as_str).unwrap_or("QMP error");
+                return Err(io::Error::other(desc.to_string()));
+            }
+            // 其余是异步事件, 忽略
+        }
+    }
+
+    fn read_message(&mut self) -> io::Result<Value> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "QMP connection closed"));
+        }
+        serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// 读到下一个 "(qemu) " 提示符为止
+    fn read_hmp_prompt(&mut self) -> io::Result<()> {
+        let mut received = Vec::new();
+        let mut byte = [0u8; 1];
+        while !received.ends_with(HMP_PROMPT.as_bytes()) {
+            if self.reader.read(&mut byte)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "monitor connection closed"));
+            }
+            received.push(byte[0]);
+        }
+        Ok(())
+    }
+}
+
+impl Target for QemuTarget {
+    fn describe(&self) -> String {
+        match self.monitor {
+            Monitor::Qmp(_) => format!("qmp:{}", self.address),
+            Monitor::Hmp => format!("hmp:{}", self.address),
+        }
+    }
+
+    fn send_str(&mut self, text: &str) -> io::Result<()> {
+        for key in parse_keys(text) {
+            self.press_key(key)?;
+        }
+        Ok(())
+    }
+
+    fn send_enter(&mut self) -> io::Result<()> {
+        self.press(&["ret"])
+    }
+}
+
+/// QEMU 的 qcode 按键名
+fn char_qcode(base: char) -> String {
+    let name = match base {
+        ' ' => "spc",
+        '-' => "minus",
+        '=' => "equal",
+        '[' => "bracket_left",
+        ']' => "bracket_right",
+        '\\' => "backslash",
+        ';' => "semicolon",
+        '\'' => "apostrophe",
+        '`' => "grave_accent",
+        ',' => "comma",
+        '.' => "dot",
+        '/' => "slash",
+        // 字母和数字与 qcode 同名
+        c => return c.to_string(),
+    };
+    name.to_string()
+}
+
+fn special_qcode(key: SpecialKey) -> &'static str {
+    const F_KEYS: [&str; 12] = ["f1", "f2", "f3", "f4", "f5", "f6", "f7", "f8", "f9", "f10", "f11", "f12"];
+    match key {
+        SpecialKey::Enter => "ret",
+        SpecialKey::Tab => "tab",
+        SpecialKey::Backspace => "backspace",
+        SpecialKey::Escape => "esc",
+        SpecialKey::Insert => "insert",
+        SpecialKey::Delete => "delete",
+        SpecialKey::Home => "home",
+        SpecialKey::End => "end",
+        SpecialKey::PageUp => "pgup",
+        SpecialKey::PageDown => "pgdn",
+        SpecialKey::Up => "up",
+        SpecialKey::Down => "down",
+        SpecialKey::Left => "left",
+        SpecialKey::Right => "right",
+        SpecialKey::F(n) => F_KEYS[n as usize - 1],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// 本地模拟的 QMP 服务器, 记录收到的命令; 对 send-key 先回一个事件再回结果
+    fn mock_qmp_server() -> (String, Arc<Mutex<Vec<Value>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let record = received.clone();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            writer.write_all(b"{\"QMP\": {\"version\": {}, \"capabilities\": []}}\r\n").unwrap();
+            for line in BufReader::new(stream).lines() {
+                let command: Value = serde_json::from_str(&line.unwrap()).unwrap();
+                let reply = match command["execute"].as_str() {
+                    Some("send-key") if command["arguments"]["keys"][0]["data"] == "f13" => {
+                        "{\"error\": {\"class\": \"GenericError\", \"desc\": \"Invalid parameter 'f13'\"}}\r\n"
+                    }
+                    Some("send-key") => "{\"event\": \"RESUME\", \"data\": {}}\r\n{\"return\": {}}\r\n",
+                    _ => "{\"return\": {}}\r\n",
+                };
+                record.lock().unwrap().push(command);
+                writer.write_all(reply.as_bytes()).unwrap();
+            }
+        });
+        (address, received)
+    }
+
+    fn sent_keys(command: &Value) -> Vec<String> {
+        command["arguments"]["keys"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|key| key["data"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn sends_keys_over_qmp() {
+        let (address, received) = mock_qmp_server();
+        let mut target = QemuTarget::connect(&address, Monitor::Qmp(KeyMethod::SendKey)).unwrap();
+        target.send_str("aB ?{F2}").unwrap();
+        target.send_enter().unwrap();
+
+        let commands = received.lock().unwrap();
+        assert_eq!(commands[0]["execute"], "qmp_capabilities");
+        let keys: Vec<Vec<String>> = commands[1..].iter().map(sent_keys).collect();
+        assert_eq!(
+            keys,
+            vec![
+                vec!["a"],
+                vec!["shift", "b"],
+                vec!["spc"],
+                vec!["shift", "slash"],
+                vec!["f2"],
+                vec!["ret"],
+            ]
+        );
+    }
+
+    #[test]
+    fn sends_press_and_release_events() {
+        let (address, received) = mock_qmp_server();
+        let mut target = QemuTarget::connect(&address, Monitor::Qmp(KeyMethod::InputSendEvent)).unwrap();
+        target.send_str("%").unwrap();
+
+        let commands = received.lock().unwrap();
+        assert_eq!(commands[1]["execute"], "input-send-event");
+        let events: Vec<(bool, String)> = commands[1]["arguments"]["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| {
+                let data = &event["data"];
+                (data["down"].as_bool().unwrap(), data["key"]["data"].as_str().unwrap().to_string())
+            })
+            .collect();
+        let expected = [(true, "shift"), (true, "5"), (false, "5"), (false, "shift")];
+        assert_eq!(events, expected.map(|(down, key)| (down, key.to_string())));
+    }
+
+    #[test]
+    fn reports_qmp_errors_and_unmapped_characters() {
+        let (address, _) = mock_qmp_server();
+        let mut target = QemuTarget::connect(&address, Monitor::Qmp(KeyMethod::SendKey)).unwrap();
+        let error = target.press(&["f13"]).unwrap_err();
+        assert!(error.to_string().contains("f13"));
+        assert_eq!(target.send_str("é").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn sends_keys_over_hmp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            writer.write_all(b"QEMU 8.2.0 monitor - type 'help' for more information\r\n(qemu) ").unwrap();
+            let mut commands = Vec::new();
+            for line in BufReader::new(stream).lines() {
+                let line = line.unwrap();
+                writer.write_all(format!("{}\r\n(qemu) ", line).as_bytes()).unwrap();
+                commands.push(line);
+            }
+            commands
+        });
+
+        let mut target = QemuTarget::connect(&address, Monitor::Hmp).unwrap();
+        target.send_str("L:").unwrap();
+        target.send_enter().unwrap();
+        drop(target);
+        assert_eq!(server.join().unwrap(), vec!["sendkey shift-l", "sendkey shift-semicolon", "sendkey ret"]);
+    }
+}
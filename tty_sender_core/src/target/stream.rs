@@ -0,0 +1,166 @@
+use super::{LineEnding, OutputBuffer, Target};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// 基于字节流的目标 (串口、TCP、已有的伪终端设备)
+pub struct StreamTarget {
+    name: String,
+    writer: Box<dyn Write + Send>,
+    eol: LineEnding,
+    output: Option<OutputBuffer>,
+    /// 通知读线程退出; 串口读取带超时, 会定期检查
+    stop: Arc<AtomicBool>,
+    /// TCP 连接在结束时关闭, 使阻塞的读取返回
+    tcp: Option<TcpStream>,
+}
+
+impl StreamTarget {
+    pub fn new(name: String, writer: Box<dyn Write + Send>, eol: LineEnding) -> Self {
+        Self { name, writer, eol, output: None, stop: Arc::new(AtomicBool::new(false)), tcp: None }
+    }
+
+    /// 在后台线程中读取目标的输出
+    pub fn with_reader(mut self, mut reader: Box<dyn Read + Send>) -> Self {
+        let output = OutputBuffer::new();
+        let writer = output.clone();
+        let stop = Arc::clone(&self.stop);
+        thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            while !stop.load(Ordering::SeqCst) {
+                match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => writer.push(&buf[..n]),
+                    Err(e) if matches!(e.kind(), io::ErrorKind::Interrupted | io::ErrorKind::TimedOut) => continue,
+                    Err(_) => break,
+                }
+            }
+            writer.close();
+        });
+        self.output = Some(output);
+        self
+    }
+
+    pub fn open_serial(port: &str, baud: u32, eol: LineEnding) -> io::Result<Self> {
+        let serial = serialport::new(port, baud)
+            .timeout(Duration::from_millis(100))
+            .open()?;
+        let reader = serial.try_clone()?;
+        Ok(Self::new(format!("serial:{}@{}", port, baud), serial, eol).with_reader(Box::new(reader)))
+    }
+
+    pub fn open_tcp(address: &str, eol: LineEnding) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        let reader = stream.try_clone()?;
+        let mut target = Self::new(format!("tcp:{}", address), Box::new(stream.try_clone()?), eol);
+        target.tcp = Some(stream);
+        Ok(target.with_reader(Box::new(reader)))
+    }
+
+    #[cfg(unix)]
+    pub fn open_pty(path: &Path, eol: LineEnding) -> io::Result<Self> {
+        let file = std::fs::OpenOptions::new().read(true).write(true).open(path)?;
+        let reader = file.try_clone()?;
+        Ok(Self::new(format!("pty:{}", path.display()), Box::new(file), eol).with_reader(Box::new(reader)))
+    }
+}
+
+impl Drop for StreamTarget {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(stream) = &self.tcp {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// TCP 连接或 Unix 套接字连接, 供监视器、VNC 等基于协议的目标使用
+pub(crate) enum Socket {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixStream),
+}
+
+impl Socket {
+    /// 连接 TCP 地址, 或在类 Unix 系统上连接含 `/` 的 Unix 套接字路径
+    pub(crate) fn connect(address: &str) -> io::Result<Socket> {
+        #[cfg(unix)]
+        if address.contains('/') {
+            return Ok(Socket::Unix(std::os::unix::net::UnixStream::connect(address)?));
+        }
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        Ok(Socket::Tcp(stream))
+    }
+
+    pub(crate) fn try_clone(&self) -> io::Result<Socket> {
+        match self {
+            Socket::Tcp(stream) => stream.try_clone().map(Socket::Tcp),
+            #[cfg(unix)]
+            Socket::Unix(stream) => stream.try_clone().map(Socket::Unix),
+        }
+    }
+
+    /// 关闭连接, 其他线程中的克隆读取会随之结束
+    pub(crate) fn shutdown(&self) {
+        let _ = match self {
+            Socket::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            #[cfg(unix)]
+            Socket::Unix(stream) => stream.shutdown(Shutdown::Both),
+        };
+    }
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Socket::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Socket::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Socket::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Socket::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Socket::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Socket::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+impl Target for StreamTarget {
+    fn describe(&self) -> String {
+        self.name.clone()
+    }
+
+    fn send_str(&mut self, text: &str) -> io::Result<()> {
+        self.writer.write_all(text.as_bytes())?;
+        self.writer.flush()
+    }
+
+    fn send_enter(&mut self) -> io::Result<()> {
+        self.writer.write_all(self.eol.as_bytes())?;
+        self.writer.flush()
+    }
+
+    fn output(&self) -> Option<OutputBuffer> {
+        self.output.clone()
+    }
+}
@@ -0,0 +1,350 @@
+use super::stream::Socket;
+use super::Target;
+use crate::keys::{parse_keys, us_layout, Key, SpecialKey};
+use des::cipher::generic_array::GenericArray;
+use des::cipher::{BlockEncrypt, KeyInit};
+use des::Des;
+use std::io::{self, Read, Write};
+use std::thread;
+
+pub const DEFAULT_VNC_PORT: u16 = 5900;
+
+const SECURITY_NONE: u8 = 1;
+const SECURITY_VNC_AUTH: u8 = 2;
+const KEY_EVENT: u8 = 4;
+const XK_SHIFT_L: u32 = 0xffe1;
+
+/// 通过 RFB 协议的 KeyEvent 发送按键, 用于 KVM over IP 和暴露 VNC 的控制台
+pub struct VncTarget {
+    address: String,
+    writer: Socket,
+}
+
+impl VncTarget {
+    pub fn connect(address: &str, password: Option<&str>) -> io::Result<Self> {
+        let mut writer = Socket::connect(address)?;
+        let mut reader = writer.try_clone()?;
+        handshake(&mut reader, &mut writer, password)?;
+        // 不请求画面更新, 但服务器仍可能发来响铃、剪贴板等消息; 读出丢弃以免缓冲区塞满
+        thread::spawn(move || io::copy(&mut reader, &mut io::sink()));
+        Ok(Self { address: address.to_string(), writer })
+    }
+
+    fn key_event(&mut self, keysym: u32, down: bool) -> io::Result<()> {
+        let mut message = [KEY_EVENT, down as u8, 0, 0, 0, 0, 0, 0];
+        message[4..].copy_from_slice(&keysym.to_be_bytes());
+        self.writer.write_all(&message)
+    }
+
+    fn tap(&mut self, keysym: u32, shift: bool) -> io::Result<()> {
+        // 很多服务器按键码而不是字符处理, 需要的 Shift 要显式按下
+        if shift {
+            self.key_event(XK_SHIFT_L, true)?;
+        }
+        self.key_event(keysym, true)?;
+        self.key_event(keysym, false)?;
+        if shift {
+            self.key_event(XK_SHIFT_L, false)?;
+        }
+        self.writer.flush()
+    }
+}
+
+impl Drop for VncTarget {
+    fn drop(&mut self) {
+        self.writer.shutdown();
+    }
+}
+
+impl Target for VncTarget {
+    fn describe(&self) -> String {
+        format!("vnc:{}", self.address)
+    }
+
+    fn send_str(&mut self, text: &str) -> io::Result<()> {
+        for key in parse_keys(text) {
+            match key {
+                Key::Special(special) => self.tap(special_keysym(special), false)?,
+                Key::Char(c) => {
+                    let shift = us_layout(c).is_some_and(|(_, shift)| shift);
+                    self.tap(char_keysym(c), shift)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn send_enter(&mut self) -> io::Result<()> {
+        self.tap(special_keysym(SpecialKey::Enter), false)
+    }
+}
+
+fn handshake(reader: &mut dyn Read, writer: &mut dyn Write, password: Option<&str>) -> io::Result<()> {
+    let mut version = [0u8; 12];
+    reader.read_exact(&mut version)?;
+    let minor = parse_version(&version)?;
+    // 支持 3.3、3.7、3.8, 更高版本按 3.8 处理
+    let minor = match minor {
+        3..=6 => 3,
+        7 => 7,
+        _ => 8,
+    };
+    writer.write_all(format!("RFB 003.{:03}\n", minor).as_bytes())?;
+    writer.flush()?;
+
+    let security = if minor == 3 {
+        match read_u32(reader)? {
+            0 => return Err(failure(reader)),
+            kind => kind as u8,
+        }
+    } else {
+        let count = read_u8(reader)?;
+        if count == 0 {
+            return Err(failure(reader));
+        }
+        let mut offered = vec![0u8; count as usize];
+        reader.read_exact(&mut offered)?;
+        let chosen = if password.is_some() && offered.contains(&SECURITY_VNC_AUTH) {
+            SECURITY_VNC_AUTH
+        } else if offered.contains(&SECURITY_NONE) {
+            SECURITY_NONE
+        } else if offered.contains(&SECURITY_VNC_AUTH) {
+            SECURITY_VNC_AUTH
+        } else {
+            return Err(protocol_error(format!("unsupported security types {:?}", offered)));
+        };
+        writer.write_all(&[chosen])?;
+        chosen
+    };
+
+    match security {
+        SECURITY_NONE => {}
+        SECURITY_VNC_AUTH => {
+            let password = password.ok_or_else(|| protocol_error("server requires a VNC password".to_string()))?;
+            let mut challenge = [0u8; 16];
+            reader.read_exact(&mut challenge)?;
+            writer.write_all(&vnc_auth_response(password, &challenge))?;
+            writer.flush()?;
+        }
+        other => return Err(protocol_error(format!("unsupported security type {}", other))),
+    }
+
+    // 3.8 的每种方式, 以及较早版本的 VNC 认证之后都有认证结果
+    if (minor == 8 || security == SECURITY_VNC_AUTH) && read_u32(reader)? != 0 {
+        return Err(if minor == 8 {
+            failure(reader)
+        } else {
+            protocol_error("authentication failed".to_string())
+        });
+    }
+
+    // ClientInit: 允许与其他客户端共享
+    writer.write_all(&[1])?;
+    writer.flush()?;
+
+    // ServerInit: 宽、高、像素格式 (16 字节) 与名称
+    let mut server_init = [0u8; 20];
+    reader.read_exact(&mut server_init)?;
+    let name_length = read_u32(reader)?;
+    io::copy(&mut reader.take(name_length as u64), &mut io::sink())?;
+    Ok(())
+}
+
+fn parse_version(version: &[u8; 12]) -> io::Result<u32> {
+    let text = std::str::from_utf8(version).unwrap_or("");
+    text.strip_prefix("RFB 003.")
+        .and_then(|minor| minor.trim_end().parse().ok())
+        .ok_or_else(|| protocol_error(format!("not an RFB server: {:?}", text)))
+}
+
+/// VNC 认证: 以密码 (截断或补零到 8 字节, 每字节位序反转) 为 DES 密钥加密挑战
+fn vnc_auth_response(password: &str, challenge: &[u8; 16]) -> [u8; 16] {
+    let mut key = [0u8; 8];
+    for (slot, byte) in key.iter_mut().zip(password.bytes()) {
+        *slot = byte.reverse_bits();
+    }
+    let cipher = Des::new(GenericArray::from_slice(&key));
+    let mut response = *challenge;
+    for block in response.chunks_mut(8) {
+        cipher.encrypt_block(GenericArray::from_mut_slice(block));
+    }
+    response
+}
+
+/// 读取服务器给出的失败原因
+fn failure(reader: &mut dyn Read) -> io::Error {
+    let reason = read_u32(reader).and_then(|length| {
+        let mut reason = vec![0u8; length as usize];
+        reader.read_exact(&mut reason)?;
+        Ok(String::from_utf8_lossy(&reason).into_owned())
+    });
+    io::Error::new(io::ErrorKind::PermissionDenied, reason.unwrap_or_else(|e| e.to_string()))
+}
+
+fn protocol_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u8(reader: &mut dyn Read) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u32(reader: &mut dyn Read) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+/// X11 keysym: Latin-1 字符与码位相同, 其余 Unicode 字符为 0x01000000 + 码位
+fn char_keysym(c: char) -> u32 {
+    match c as u32 {
+        code @ (0x20..=0x7e | 0xa0..=0xff) => code,
+        code => 0x0100_0000 | code,
+    }
+}
+
+fn special_keysym(key: SpecialKey) -> u32 {
+    match key {
+        SpecialKey::Enter => 0xff0d,
+        SpecialKey::Tab => 0xff09,
+        SpecialKey::Backspace => 0xff08,
+        SpecialKey::Escape => 0xff1b,
+        SpecialKey::Insert => 0xff63,
+        SpecialKey::Delete => 0xffff,
+        SpecialKey::Home => 0xff50,
+        SpecialKey::End => 0xff57,
+        SpecialKey::PageUp => 0xff55,
+        SpecialKey::PageDown => 0xff56,
+        SpecialKey::Up => 0xff52,
+        SpecialKey::Down => 0xff54,
+        SpecialKey::Left => 0xff51,
+        SpecialKey::Right => 0xff53,
+        SpecialKey::F(n) => 0xffbe + n as u32 - 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{TcpListener, TcpStream};
+
+    /// 收到的 KeyEvent (按下, keysym)
+    type KeyEvents = io::Result<Vec<(bool, u32)>>;
+
+    /// 本地替身 RFB 服务器: 完成握手后记录收到的按键
+    fn stand_in_server(version: &'static str, password: Option<&'static str>) -> (String, thread::JoinHandle<KeyEvents>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept()?;
+            stream.write_all(version.as_bytes())?;
+            let mut client_version = [0u8; 12];
+            stream.read_exact(&mut client_version)?;
+            let minor = parse_version(&client_version)?;
+            let security = if password.is_some() { SECURITY_VNC_AUTH } else { SECURITY_NONE };
+            if minor == 3 {
+                stream.write_all(&(security as u32).to_be_bytes())?;
+            } else {
+                stream.write_all(&[1, security])?;
+                assert_eq!(read_u8(&mut stream)?, security);
+            }
+            if let Some(password) = password {
+                let challenge: [u8; 16] = *b"0123456789abcdef";
+                stream.write_all(&challenge)?;
+                let mut response = [0u8; 16];
+                stream.read_exact(&mut response)?;
+                if response != vnc_auth_response(password, &challenge) {
+                    stream.write_all(&1u32.to_be_bytes())?;
+                    stream.write_all(&[0, 0, 0, 12])?;
+                    stream.write_all(b"bad password")?;
+                    return Ok(Vec::new());
+                }
+            }
+            if minor == 8 || password.is_some() {
+                stream.write_all(&0u32.to_be_bytes())?;
+            }
+            assert_eq!(read_u8(&mut stream)?, 1);
+            let mut server_init = vec![0u8; 20];
+            server_init.extend_from_slice(&4u32.to_be_bytes());
+            server_init.extend_from_slice(b"test");
+            stream.write_all(&server_init)?;
+
+            let mut events = Vec::new();
+            let mut message = [0u8; 8];
+            while stream.read_exact(&mut message).is_ok() {
+                assert_eq!(message[0], KEY_EVENT);
+                events.push((message[1] == 1, u32::from_be_bytes(message[4..].try_into().unwrap())));
+            }
+            Ok(events)
+        });
+        (address, server)
+    }
+
+    #[test]
+    fn sends_key_events_after_vnc_auth() {
+        let (address, server) = stand_in_server("RFB 003.008\n", Some("secret"));
+        let mut target = VncTarget::connect(&address, Some("secret")).unwrap();
+        target.send_str("a!{F2}").unwrap();
+        target.send_enter().unwrap();
+        drop(target);
+
+        let events = server.join().unwrap().unwrap();
+        let expected = [
+            (true, 0x61), (false, 0x61),
+            (true, XK_SHIFT_L), (true, 0x21), (false, 0x21), (false, XK_SHIFT_L),
+            (true, 0xffbf), (false, 0xffbf),
+            (true, 0xff0d), (false, 0xff0d),
+        ];
+        assert_eq!(events, expected);
+    }
+
+    #[test]
+    fn connects_without_auth_to_older_servers() {
+        let (address, server) = stand_in_server("RFB 003.003\n", None);
+        let mut target = VncTarget::connect(&address, None).unwrap();
+        target.send_str("é").unwrap();
+        drop(target);
+        assert_eq!(server.join().unwrap().unwrap(), [(true, 0xe9), (false, 0xe9)]);
+    }
+
+    #[test]
+    fn reports_rejected_password() {
+        let (address, server) = stand_in_server("RFB 003.008\n", Some("secret"));
+        let error = VncTarget::connect(&address, Some("wrong")).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(error.to_string(), "bad password");
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn rejects_non_rfb_servers() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (mut stream, _): (TcpStream, _) = listener.accept().unwrap();
+            stream.write_all(b"SSH-2.0-Open").unwrap();
+        });
+        let error = VncTarget::connect(&address, None).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn computes_vnc_auth_response() {
+        // 用 openssl des-ecb 以位序反转后的密钥算出的结果
+        let expected = [
+            0x75, 0x24, 0x40, 0xee, 0x2b, 0xfc, 0xc2, 0xa0, 0xd9, 0x01, 0x3f, 0xd2, 0x03, 0x71, 0xe2, 0x3b,
+        ];
+        assert_eq!(vnc_auth_response("secret", b"0123456789abcdef"), expected);
+    }
+
+    #[test]
+    fn maps_characters_to_keysyms() {
+        assert_eq!(char_keysym('A'), 0x41);
+        assert_eq!(char_keysym('ü'), 0xfc);
+        assert_eq!(char_keysym('中'), 0x0100_4e2d);
+        assert_eq!(special_keysym(SpecialKey::F(12)), 0xffc9);
+    }
+}
@@ -0,0 +1,146 @@
+use super::{Target, TargetError, WindowSelector};
+use std::io;
+use winapi::shared::minwindef::{BOOL, LPARAM, TRUE, WPARAM};
+use winapi::shared::windef::HWND;
+use winapi::um::processthreadsapi::GetCurrentThreadId;
+use winapi::um::winuser::*;
+
+/// 通过 WM_CHAR 向窗口模拟键盘输入
+pub struct WindowTarget {
+    // HWND 不是 Send, 以整数保存以便在发送线程中使用
+    hwnd: isize,
+    attached_thread_id: Option<u32>,
+}
+
+impl WindowTarget {
+    pub fn new(hwnd: isize) -> Result<Self, TargetError> {
+        if unsafe { IsWindow(hwnd as HWND) } == 0 {
+            return Err(TargetError::NotFound(format!("hwnd:{:#x}", hwnd)));
+        }
+        Ok(Self { hwnd, attached_thread_id: None })
+    }
+
+    pub fn find(selector: &WindowSelector) -> Result<Self, TargetError> {
+        match selector {
+            WindowSelector::Handle(handle) => Self::new(*handle),
+            WindowSelector::Title(title) => top_level_windows()
+                .into_iter()
+                .find(|(_, window_title)| window_title.contains(title.as_str()))
+                .ok_or_else(|| TargetError::NotFound(format!("window:{}", title)))
+                .and_then(|(handle, _)| Self::new(handle)),
+        }
+    }
+
+    fn hwnd(&self) -> HWND {
+        self.hwnd as HWND
+    }
+
+    /// 将目标窗口切到前台, 必要时附加到其输入线程
+    fn focus(&mut self) {
+        unsafe {
+            let mut target_process_id = 0;
+            let target_thread_id = GetWindowThreadProcessId(self.hwnd(), &mut target_process_id);
+            let current_thread_id = GetCurrentThreadId();
+
+            if let Some(attached) = self.attached_thread_id {
+                if attached != target_thread_id {
+                    AttachThreadInput(current_thread_id, attached, 0);
+                    self.attached_thread_id = None;
+                }
+            }
+
+            if target_thread_id != current_thread_id
+                && self.attached_thread_id.is_none()
+                && AttachThreadInput(current_thread_id, target_thread_id, 1) != 0
+            {
+                self.attached_thread_id = Some(target_thread_id);
+            }
+
+            let mut retry_count = 3;
+            while retry_count > 0 {
+                ShowWindow(self.hwnd(), SW_RESTORE);
+                BringWindowToTop(self.hwnd());
+                SetForegroundWindow(self.hwnd());
+                SetFocus(self.hwnd());
+
+                if GetForegroundWindow() == self.hwnd() {
+                    break;
+                }
+
+                retry_count -= 1;
+                std::thread::sleep(std::time::Duration::from_millis(20));
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+    }
+
+    fn post_char(&self, unit: u16) -> io::Result<()> {
+        unsafe {
+            if IsWindow(self.hwnd()) == 0 {
+                return Err(io::Error::new(io::ErrorKind::NotFound, "target window no longer exists"));
+            }
+            PostMessageW(self.hwnd(), WM_CHAR, unit as WPARAM, 0);
+        }
+        Ok(())
+    }
+}
+
+impl Target for WindowTarget {
+    fn describe(&self) -> String {
+        format!("hwnd:{:#x}", self.hwnd)
+    }
+
+    fn prepare(&mut self) {
+        self.focus();
+    }
+
+    fn send_str(&mut self, text: &str) -> io::Result<()> {
+        for unit in text.encode_utf16() {
+            self.post_char(unit)?;
+        }
+        Ok(())
+    }
+
+    fn send_enter(&mut self) -> io::Result<()> {
+        self.post_char(VK_RETURN as u16)
+    }
+}
+
+impl Drop for WindowTarget {
+    fn drop(&mut self) {
+        if let Some(attached) = self.attached_thread_id {
+            unsafe {
+                AttachThreadInput(GetCurrentThreadId(), attached, 0);
+            }
+        }
+    }
+}
+
+/// 可见且有标题的顶层窗口 (规则, 标题)
+pub fn list_windows() -> Vec<(String, String)> {
+    top_level_windows()
+        .into_iter()
+        .map(|(handle, title)| (format!("hwnd:{:#x}", handle), title))
+        .collect()
+}
+
+fn top_level_windows() -> Vec<(isize, String)> {
+    unsafe extern "system" fn collect(hwnd: HWND, l_param: LPARAM) -> BOOL {
+        let windows = &mut *(l_param as *mut Vec<(isize, String)>);
+        if IsWindowVisible(hwnd) != 0 {
+            let length = GetWindowTextLengthW(hwnd);
+            if length > 0 {
+                let mut buffer = vec![0u16; length as usize + 1];
+                let copied = GetWindowTextW(hwnd, buffer.as_mut_ptr(), length + 1);
+                windows.push((hwnd as isize, String::from_utf16_lossy(&buffer[..copied as usize])));
+            }
+        }
+        TRUE
+    }
+
+    let mut windows: Vec<(isize, String)> = Vec::new();
+    unsafe {
+        EnumWindows(Some(collect), &mut windows as *mut _ as LPARAM);
+    }
+    windows
+}
@@ -0,0 +1,788 @@
+//! 终端模拟: 把目标输出中的 VT100/xterm 控制序列解释为字符网格, 不依赖界面;
+//! 界面按 Screen 的内容绘制, 按键经 key_bytes 转换后直接发给目标
+
+use crate::keys::{terminal_bytes, SpecialKey};
+use std::collections::VecDeque;
+use vte::{Params, Parser, Perform};
+
+/// 默认保留的滚动历史行数
+pub const DEFAULT_SCROLLBACK: usize = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Color {
+    #[default]
+    Default,
+    /// 256 色表中的颜色, 0~15 为基本色
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// xterm 调色板中的颜色, 默认色返回 None, 由界面使用自己的前景色或背景色
+    pub fn rgb(self) -> Option<(u8, u8, u8)> {
+        const BASIC: [(u8, u8, u8); 16] = [
+            (0, 0, 0),
+            (205, 0, 0),
+            (0, 205, 0),
+            (205, 205, 0),
+            (0, 0, 238),
+            (205, 0, 205),
+            (0, 205, 205),
+            (229, 229, 229),
+            (127, 127, 127),
+            (255, 0, 0),
+            (0, 255, 0),
+            (255, 255, 0),
+            (92, 92, 255),
+            (255, 0, 255),
+            (0, 255, 255),
+            (255, 255, 255),
+        ];
+        match self {
+            Color::Default => None,
+            Color::Rgb(r, g, b) => Some((r, g, b)),
+            Color::Indexed(index @ 0..=15) => Some(BASIC[index as usize]),
+            // 6x6x6 色块
+            Color::Indexed(index @ 16..=231) => {
+                let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+                let n = index - 16;
+                Some((level(n / 36), level(n / 6 % 6), level(n % 6)))
+            }
+            Color::Indexed(index) => {
+                let gray = 8 + (index - 232) * 10;
+                Some((gray, gray, gray))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Attrs {
+    pub fg: Color,
+    pub bg: Color,
+    pub bold: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    /// 宽字符右半边的占位格为 '\0', 绘制时跳过
+    pub ch: char,
+    pub attrs: Attrs,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self { ch: ' ', attrs: Attrs::default() }
+    }
+}
+
+/// 解析器和屏幕分开存放, 以便解析时可变借用屏幕
+pub struct Terminal {
+    parser: Parser,
+    screen: Screen,
+}
+
+impl Terminal {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Self { parser: Parser::new(), screen: Screen::new(cols.max(1), rows.max(1)) }
+    }
+
+    /// 处理目标的输出
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.parser.advance(&mut self.screen, bytes);
+    }
+
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        self.screen.resize(cols.max(1), rows.max(1));
+    }
+
+    pub fn set_scrollback_limit(&mut self, lines: usize) {
+        self.screen.max_scrollback = lines;
+        self.screen.trim_scrollback();
+    }
+
+    /// 终端应回复给目标的内容 (如光标位置报告), 取出后清空
+    pub fn take_responses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.screen.responses)
+    }
+
+    /// 特殊键对应的字节序列, 方向键随应用光标模式变化
+    pub fn key_bytes(&self, key: SpecialKey) -> Vec<u8> {
+        terminal_bytes(key, self.screen.app_cursor)
+    }
+}
+
+/// 屏幕内容、光标和各种模式
+pub struct Screen {
+    cols: usize,
+    rows: usize,
+    lines: Vec<Vec<Cell>>,
+    scrollback: VecDeque<Vec<Cell>>,
+    max_scrollback: usize,
+    row: usize,
+    col: usize,
+    /// 在最后一列写入后置位, 下一个字符才换行
+    wrap_pending: bool,
+    attrs: Attrs,
+    saved: (usize, usize, Attrs),
+    /// 滚动区域的首行和末行 (包含)
+    top: usize,
+    bottom: usize,
+    autowrap: bool,
+    cursor_visible: bool,
+    app_cursor: bool,
+    /// 切到备用屏幕时保存的主屏幕内容和光标
+    primary: Option<(Vec<Vec<Cell>>, usize, usize)>,
+    /// G0、G1 是否为 DEC 制表符字符集, 以及当前使用 G1
+    line_drawing: [bool; 2],
+    shifted: bool,
+    title: String,
+    responses: Vec<u8>,
+}
+
+impl Screen {
+    fn new(cols: usize, rows: usize) -> Self {
+        Self {
+            cols,
+            rows,
+            lines: vec![vec![Cell::default(); cols]; rows],
+            scrollback: VecDeque::new(),
+            max_scrollback: DEFAULT_SCROLLBACK,
+            row: 0,
+            col: 0,
+            wrap_pending: false,
+            attrs: Attrs::default(),
+            saved: (0, 0, Attrs::default()),
+            top: 0,
+            bottom: rows - 1,
+            autowrap: true,
+            cursor_visible: true,
+            app_cursor: false,
+            primary: None,
+            line_drawing: [false; 2],
+            shifted: false,
+            title: String::new(),
+            responses: Vec::new(),
+        }
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// 光标位置 (行, 列), 从 0 开始
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    /// 是否在备用屏幕 (全屏程序如 vi、less) 中, 此时不产生滚动历史
+    pub fn alternate(&self) -> bool {
+        self.primary.is_some()
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn line(&self, row: usize) -> &[Cell] {
+        &self.lines[row]
+    }
+
+    pub fn scrollback_len(&self) -> usize {
+        self.scrollback.len()
+    }
+
+    /// 向上滚动 scroll 行时看到的第 row 行; 历史行的宽度可能与当前屏幕不同
+    pub fn view_line(&self, scroll: usize, row: usize) -> &[Cell] {
+        let index = self.scrollback.len() - scroll.min(self.scrollback.len()) + row;
+        match index.checked_sub(self.scrollback.len()) {
+            Some(index) => &self.lines[index],
+            None => &self.scrollback[index],
+        }
+    }
+
+    /// 某行的文字, 去掉行尾空格
+    pub fn line_text(&self, row: usize) -> String {
+        cells_text(&self.lines[row])
+    }
+
+    fn blank(&self) -> Cell {
+        // 擦除时使用当前背景色
+        Cell { ch: ' ', attrs: Attrs { bg: self.attrs.bg, ..Attrs::default() } }
+    }
+
+    fn blank_line(&self) -> Vec<Cell> {
+        vec![self.blank(); self.cols]
+    }
+
+    fn resize(&mut self, cols: usize, rows: usize) {
+        // 行数减少时把光标以上的行推入历史, 保持光标所在行可见
+        if rows < self.rows {
+            let excess = (self.row + 1).saturating_sub(rows);
+            for line in self.lines.drain(..excess) {
+                if self.primary.is_none() {
+                    self.scrollback.push_back(line);
+                }
+            }
+            self.lines.truncate(rows);
+            self.row -= excess;
+        }
+        let blank = Cell::default();
+        self.lines.resize(rows, vec![blank; cols]);
+        for line in &mut self.lines {
+            line.resize(cols, blank);
+        }
+        if let Some((lines, _, _)) = &mut self.primary {
+            lines.resize(rows, vec![blank; cols]);
+            for line in lines.iter_mut() {
+                line.resize(cols, blank);
+            }
+        }
+        self.cols = cols;
+        self.rows = rows;
+        self.top = 0;
+        self.bottom = rows - 1;
+        self.row = self.row.min(rows - 1);
+        self.col = self.col.min(cols - 1);
+        self.wrap_pending = false;
+        self.trim_scrollback();
+    }
+
+    fn trim_scrollback(&mut self) {
+        while self.scrollback.len() > self.max_scrollback {
+            self.scrollback.pop_front();
+        }
+    }
+
+    fn put(&mut self, c: char) {
+        let width = char_width(c).min(self.cols);
+        if self.wrap_pending {
+            self.wrap_pending = false;
+            if self.autowrap {
+                self.col = 0;
+                self.linefeed();
+            }
+        }
+        if self.col + width > self.cols {
+            if !self.autowrap {
+                self.col = self.cols - width;
+            } else {
+                self.col = 0;
+                self.linefeed();
+            }
+        }
+        let attrs = self.attrs;
+        let line = &mut self.lines[self.row];
+        line[self.col] = Cell { ch: c, attrs };
+        if width == 2 {
+            line[self.col + 1] = Cell { ch: '\0', attrs };
+        }
+        if self.col + width >= self.cols {
+            self.col = self.cols - 1;
+            self.wrap_pending = true;
+        } else {
+            self.col += width;
+        }
+    }
+
+    fn linefeed(&mut self) {
+        if self.row == self.bottom {
+            self.scroll_up(1);
+        } else if self.row + 1 < self.rows {
+            self.row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        if self.row == self.top {
+            self.scroll_down(1);
+        } else if self.row > 0 {
+            self.row -= 1;
+        }
+    }
+
+    /// 滚动区域内容上移 n 行; 区域从首行开始且不在备用屏幕时, 移出的行进入历史
+    fn scroll_up(&mut self, n: usize) {
+        let n = n.min(self.bottom - self.top + 1);
+        for _ in 0..n {
+            let line = self.lines.remove(self.top);
+            if self.top == 0 && self.primary.is_none() {
+                self.scrollback.push_back(line);
+            }
+            self.lines.insert(self.bottom, self.blank_line());
+        }
+        self.trim_scrollback();
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        let n = n.min(self.bottom - self.top + 1);
+        for _ in 0..n {
+            self.lines.remove(self.bottom);
+            self.lines.insert(self.top, self.blank_line());
+        }
+    }
+
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.row = row.min(self.rows - 1);
+        self.col = col.min(self.cols - 1);
+        self.wrap_pending = false;
+    }
+
+    fn erase_in_display(&mut self, mode: u16) {
+        let blank = self.blank();
+        match mode {
+            0 => {
+                self.erase_in_line(0);
+                for line in &mut self.lines[self.row + 1..] {
+                    line.fill(blank);
+                }
+            }
+            1 => {
+                self.erase_in_line(1);
+                for line in &mut self.lines[..self.row] {
+                    line.fill(blank);
+                }
+            }
+            2 => {
+                for line in &mut self.lines {
+                    line.fill(blank);
+                }
+            }
+            3 => self.scrollback.clear(),
+            _ => {}
+        }
+    }
+
+    fn erase_in_line(&mut self, mode: u16) {
+        let blank = self.blank();
+        let line = &mut self.lines[self.row];
+        match mode {
+            0 => line[self.col..].fill(blank),
+            1 => line[..=self.col].fill(blank),
+            2 => line.fill(blank),
+            _ => {}
+        }
+    }
+
+    /// 在光标处插入或删除 n 个字符, 行内其后的字符随之移动
+    fn shift_chars(&mut self, n: usize, insert: bool) {
+        let blank = self.blank();
+        let col = self.col;
+        let line = &mut self.lines[self.row];
+        let n = n.min(line.len() - col);
+        if insert {
+            line[col..].rotate_right(n);
+            line[col..col + n].fill(blank);
+        } else {
+            line[col..].rotate_left(n);
+            let len = line.len();
+            line[len - n..].fill(blank);
+        }
+    }
+
+    /// 在光标所在行插入或删除 n 行, 只影响滚动区域
+    fn shift_lines(&mut self, n: usize, insert: bool) {
+        if self.row < self.top || self.row > self.bottom {
+            return;
+        }
+        let n = n.min(self.bottom - self.row + 1);
+        for _ in 0..n {
+            if insert {
+                self.lines.remove(self.bottom);
+                self.lines.insert(self.row, self.blank_line());
+            } else {
+                self.lines.remove(self.row);
+                self.lines.insert(self.bottom, self.blank_line());
+            }
+        }
+        self.col = 0;
+    }
+
+    fn set_alternate(&mut self, on: bool) {
+        if on && self.primary.is_none() {
+            let lines = std::mem::replace(&mut self.lines, vec![vec![Cell::default(); self.cols]; self.rows]);
+            self.primary = Some((lines, self.row, self.col));
+        } else if !on {
+            if let Some((lines, row, col)) = self.primary.take() {
+                self.lines = lines;
+                self.move_to(row, col);
+            }
+        }
+    }
+
+    fn set_private_mode(&mut self, mode: u16, on: bool) {
+        match mode {
+            1 => self.app_cursor = on,
+            7 => self.autowrap = on,
+            25 => self.cursor_visible = on,
+            47 | 1047 | 1049 => self.set_alternate(on),
+            _ => {}
+        }
+    }
+
+    fn select_graphic_rendition(&mut self, params: &Params) {
+        let mut values = params.iter().flat_map(|param| param.iter().copied()).peekable();
+        if values.peek().is_none() {
+            self.attrs = Attrs::default();
+            return;
+        }
+        while let Some(value) = values.next() {
+            match value {
+                0 => self.attrs = Attrs::default(),
+                1 => self.attrs.bold = true,
+                4 => self.attrs.underline = true,
+                7 => self.attrs.reverse = true,
+                22 => self.attrs.bold = false,
+                24 => self.attrs.underline = false,
+                27 => self.attrs.reverse = false,
+                30..=37 => self.attrs.fg = Color::Indexed((value - 30) as u8),
+                39 => self.attrs.fg = Color::Default,
+                40..=47 => self.attrs.bg = Color::Indexed((value - 40) as u8),
+                49 => self.attrs.bg = Color::Default,
+                90..=97 => self.attrs.fg = Color::Indexed((value - 90 + 8) as u8),
+                100..=107 => self.attrs.bg = Color::Indexed((value - 100 + 8) as u8),
+                38 | 48 => {
+                    // 38;5;n / 38;2;r;g;b, 参数之间用分号或冒号分隔均可
+                    let color = match values.next() {
+                        Some(5) => values.next().map(|n| Color::Indexed(n as u8)),
+                        Some(2) => {
+                            let mut channel = || values.next().unwrap_or(0) as u8;
+                            Some(Color::Rgb(channel(), channel(), channel()))
+                        }
+                        _ => None,
+                    };
+                    if let Some(color) = color {
+                        if value == 38 {
+                            self.attrs.fg = color;
+                        } else {
+                            self.attrs.bg = color;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Perform for Screen {
+    fn print(&mut self, c: char) {
+        let c = if self.line_drawing[self.shifted as usize] { line_drawing(c) } else { c };
+        self.put(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            b'\r' => {
+                self.col = 0;
+                self.wrap_pending = false;
+            }
+            b'\n' | 0x0b | 0x0c => {
+                self.wrap_pending = false;
+                self.linefeed();
+            }
+            0x08 => {
+                self.col = self.col.saturating_sub(1);
+                self.wrap_pending = false;
+            }
+            b'\t' => {
+                self.col = ((self.col / 8 + 1) * 8).min(self.cols - 1);
+            }
+            0x0e => self.shifted = true,
+            0x0f => self.shifted = false,
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, action: char) {
+        let values: Vec<u16> = params.iter().map(|param| param[0]).collect();
+        // 省略或为 0 的参数取默认值
+        let arg = |index: usize, default: usize| match values.get(index) {
+            Some(&value) if value > 0 => value as usize,
+            _ => default,
+        };
+        if intermediates == b"?" {
+            if action == 'h' || action == 'l' {
+                for &mode in &values {
+                    self.set_private_mode(mode, action == 'h');
+                }
+            }
+            return;
+        }
+        if !intermediates.is_empty() {
+            return;
+        }
+        match action {
+            'A' => self.move_to(self.row.saturating_sub(arg(0, 1)), self.col),
+            'B' | 'e' => self.move_to(self.row + arg(0, 1), self.col),
+            'C' | 'a' => self.move_to(self.row, self.col + arg(0, 1)),
+            'D' => self.move_to(self.row, self.col.saturating_sub(arg(0, 1))),
+            'E' => self.move_to(self.row + arg(0, 1), 0),
+            'F' => self.move_to(self.row.saturating_sub(arg(0, 1)), 0),
+            'G' | '`' => self.move_to(self.row, arg(0, 1) - 1),
+            'd' => self.move_to(arg(0, 1) - 1, self.col),
+            'H' | 'f' => self.move_to(arg(0, 1) - 1, arg(1, 1) - 1),
+            'J' => self.erase_in_display(values.first().copied().unwrap_or(0)),
+            'K' => self.erase_in_line(values.first().copied().unwrap_or(0)),
+            'L' => self.shift_lines(arg(0, 1), true),
+            'M' => self.shift_lines(arg(0, 1), false),
+            '@' => self.shift_chars(arg(0, 1), true),
+            'P' => self.shift_chars(arg(0, 1), false),
+            'X' => {
+                let blank = self.blank();
+                let end = (self.col + arg(0, 1)).min(self.cols);
+                self.lines[self.row][self.col..end].fill(blank);
+            }
+            'S' => self.scroll_up(arg(0, 1)),
+            'T' => self.scroll_down(arg(0, 1)),
+            'r' => {
+                let top = arg(0, 1) - 1;
+                let bottom = arg(1, self.rows).min(self.rows) - 1;
+                if top < bottom {
+                    self.top = top;
+                    self.bottom = bottom;
+                    self.move_to(0, 0);
+                }
+            }
+            'm' => self.select_graphic_rendition(params),
+            's' => self.saved = (self.row, self.col, self.attrs),
+            'u' => {
+                let (row, col, attrs) = self.saved;
+                self.move_to(row, col);
+                self.attrs = attrs;
+            }
+            'n' => match arg(0, 0) {
+                5 => self.responses.extend_from_slice(b"\x1b[0n"),
+                6 => self.responses.extend(format!("\x1b[{};{}R", self.row + 1, self.col + 1).bytes()),
+                _ => {}
+            },
+            // 自称带高级视频选项的 VT100
+            'c' => self.responses.extend_from_slice(b"\x1b[?1;2c"),
+            _ => {}
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        match (intermediates, byte) {
+            ([], b'7') => self.saved = (self.row, self.col, self.attrs),
+            ([], b'8') => {
+                let (row, col, attrs) = self.saved;
+                self.move_to(row, col);
+                self.attrs = attrs;
+            }
+            ([], b'D') => self.linefeed(),
+            ([], b'E') => {
+                self.col = 0;
+                self.linefeed();
+            }
+            ([], b'M') => self.reverse_index(),
+            ([], b'c') => {
+                let (cols, rows, scrollback) = (self.cols, self.rows, std::mem::take(&mut self.scrollback));
+                *self = Screen { scrollback, ..Screen::new(cols, rows) };
+            }
+            ([b'('], set) => self.line_drawing[0] = set == b'0',
+            ([b')'], set) => self.line_drawing[1] = set == b'0',
+            _ => {}
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        if let [b"0" | b"2", title, ..] = params {
+            self.title = String::from_utf8_lossy(title).into_owned();
+        }
+    }
+}
+
+fn cells_text(cells: &[Cell]) -> String {
+    let text: String = cells.iter().filter(|cell| cell.ch != '\0').map(|cell| cell.ch).collect();
+    text.trim_end().to_string()
+}
+
+/// 字符占用的列数: 中日韩文字、全角符号和常见表情为 2, 其余为 1
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115f
+        | 0x2e80..=0x303e
+        | 0x3041..=0x33ff
+        | 0x3400..=0x4dbf
+        | 0x4e00..=0x9fff
+        | 0xa000..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x1f300..=0x1f64f
+        | 0x1f900..=0x1f9ff
+        | 0x20000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+/// DEC 特殊字符集中的制表符
+fn line_drawing(c: char) -> char {
+    match c {
+        '`' => '◆',
+        'a' => '▒',
+        'f' => '°',
+        'g' => '±',
+        'j' => '┘',
+        'k' => '┐',
+        'l' => '┌',
+        'm' => '└',
+        'n' => '┼',
+        'o' => '⎺',
+        'p' => '⎻',
+        'q' => '─',
+        'r' => '⎼',
+        's' => '⎽',
+        't' => '├',
+        'u' => '┤',
+        'v' => '┴',
+        'w' => '┬',
+        'x' => '│',
+        'y' => '≤',
+        'z' => '≥',
+        '{' => 'π',
+        '|' => '≠',
+        '}' => '£',
+        '~' => '·',
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(cols: usize, rows: usize, input: &str) -> Terminal {
+        let mut terminal = Terminal::new(cols, rows);
+        terminal.feed(input.as_bytes());
+        terminal
+    }
+
+    fn lines(terminal: &Terminal) -> Vec<String> {
+        let screen = terminal.screen();
+        (0..screen.rows()).map(|row| screen.line_text(row)).collect()
+    }
+
+    #[test]
+    fn wraps_and_scrolls_into_history() {
+        let terminal = feed(5, 2, "abcdefg\r\nxy\r\nz");
+        assert_eq!(lines(&terminal), vec!["xy", "z"]);
+        let screen = terminal.screen();
+        assert_eq!(screen.scrollback_len(), 2);
+        assert_eq!(cells_text(screen.view_line(2, 0)), "abcde");
+        assert_eq!(cells_text(screen.view_line(2, 1)), "fg");
+        assert_eq!(cells_text(screen.view_line(1, 1)), "xy");
+        assert_eq!(screen.cursor(), (1, 1));
+    }
+
+    #[test]
+    fn writes_last_column_without_scrolling() {
+        let terminal = feed(3, 2, "abc\r\ndef");
+        assert_eq!(lines(&terminal), vec!["abc", "def"]);
+        assert_eq!(terminal.screen().scrollback_len(), 0);
+        assert_eq!(terminal.screen().cursor(), (1, 2));
+    }
+
+    #[test]
+    fn moves_cursor_and_erases() {
+        let terminal = feed(10, 3, "hello\r\nworld\x1b[1;3HX\x1b[2;4H\x1b[K\x1b[3;1Hab\x1b[1D\x1b[1P");
+        assert_eq!(lines(&terminal), vec!["heXlo", "wor", "a"]);
+
+        let terminal = feed(10, 3, "one\r\ntwo\r\nthree\x1b[2;2H\x1b[1J");
+        assert_eq!(lines(&terminal), vec!["", "  o", "three"]);
+        let terminal = feed(10, 3, "one\r\ntwo\x1b[2J");
+        assert_eq!(lines(&terminal), vec!["", "", ""]);
+    }
+
+    #[test]
+    fn scrolls_inside_region_only() {
+        // 第 2~3 行为滚动区域, 首行和末行保持不动, 也不进入历史
+        let mut terminal = feed(8, 4, "status\x1b[4;1Hfooter\x1b[2;3r\x1b[2;1Ha\r\nb\r\nc");
+        assert_eq!(lines(&terminal), vec!["status", "b", "c", "footer"]);
+        assert_eq!(terminal.screen().scrollback_len(), 0);
+        terminal.feed(b"\x1b[2;1H\x1bM");
+        assert_eq!(lines(&terminal), vec!["status", "", "b", "footer"]);
+        terminal.feed(b"\x1b[3;1H\x1b[L");
+        assert_eq!(lines(&terminal), vec!["status", "", "", "footer"]);
+    }
+
+    #[test]
+    fn tracks_colors_and_attributes() {
+        let terminal = feed(10, 1, "\x1b[1;31ma\x1b[0;44mb\x1b[38;5;208mc\x1b[38:2:1:2:3;7md\x1b[mx");
+        let line = terminal.screen().line(0);
+        assert_eq!(line[0].attrs, Attrs { fg: Color::Indexed(1), bold: true, ..Attrs::default() });
+        assert_eq!(line[1].attrs, Attrs { bg: Color::Indexed(4), ..Attrs::default() });
+        assert_eq!(line[2].attrs.fg, Color::Indexed(208));
+        assert_eq!(line[3].attrs.fg, Color::Rgb(1, 2, 3));
+        assert!(line[3].attrs.reverse);
+        assert_eq!(line[4].attrs, Attrs::default());
+        assert_eq!(Color::Indexed(208).rgb(), Some((255, 135, 0)));
+        assert_eq!(Color::Indexed(244).rgb(), Some((128, 128, 128)));
+    }
+
+    #[test]
+    fn restores_screen_after_alternate_buffer() {
+        let mut terminal = feed(10, 2, "$ vi\r\n");
+        terminal.feed(b"\x1b[?1049h\x1b[Hediting\r\n~\r\n~");
+        assert!(terminal.screen().alternate());
+        assert_eq!(lines(&terminal), vec!["~", "~"]);
+        assert_eq!(terminal.screen().scrollback_len(), 0);
+        terminal.feed(b"\x1b[?1049l");
+        assert_eq!(lines(&terminal), vec!["$ vi", ""]);
+        assert_eq!(terminal.screen().cursor(), (1, 0));
+    }
+
+    #[test]
+    fn answers_status_queries() {
+        let mut terminal = feed(10, 5, "\x1b[3;4H\x1b[6n\x1b[c");
+        assert_eq!(terminal.take_responses(), b"\x1b[3;4R\x1b[?1;2c");
+        assert!(terminal.take_responses().is_empty());
+    }
+
+    #[test]
+    fn draws_lines_and_wide_characters() {
+        let terminal = feed(6, 2, "\x1b(0lqk\x1b(B\r\n中文ab");
+        assert_eq!(lines(&terminal), vec!["┌─┐", "中文ab"]);
+        let line = terminal.screen().line(1);
+        assert_eq!(line[1].ch, '\0');
+        assert_eq!(line[4].ch, 'a');
+    }
+
+    #[test]
+    fn encodes_cursor_keys_by_mode() {
+        let mut terminal = Terminal::new(10, 2);
+        assert_eq!(terminal.key_bytes(SpecialKey::Up), b"\x1b[A");
+        terminal.feed(b"\x1b[?1h");
+        assert_eq!(terminal.key_bytes(SpecialKey::Up), b"\x1bOA");
+        assert_eq!(terminal.key_bytes(SpecialKey::F(2)), b"\x1bOQ");
+        assert_eq!(terminal.key_bytes(SpecialKey::F(5)), b"\x1b[15~");
+    }
+
+    #[test]
+    fn keeps_cursor_line_when_resized() {
+        let mut terminal = feed(10, 4, "a\r\nb\r\nc\r\nd");
+        terminal.resize(4, 2);
+        assert_eq!(lines(&terminal), vec!["c", "d"]);
+        assert_eq!(terminal.screen().scrollback_len(), 2);
+        assert_eq!(terminal.screen().cursor(), (1, 1));
+        terminal.resize(6, 3);
+        assert_eq!(lines(&terminal), vec!["c", "d", ""]);
+    }
+}
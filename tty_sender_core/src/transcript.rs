@@ -0,0 +1,171 @@
+//! 会话记录: 每个发送出去的行都写入日志目录, 同时写一份便于阅读的纯文本和一份
+//! JSON Lines, 按本地日期分文件, 用于事后审计
+
+use chrono::{DateTime, Local, NaiveDate};
+use serde_json::json;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// 某一行的发送结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Sent,
+    /// 已发送, 但等待提示符超时
+    PromptTimeout,
+    /// 已发送, 但等待提示符时目标的输出已结束
+    OutputClosed,
+    /// 发送中途被中止
+    Aborted,
+    /// 已发送, 命令以非零状态退出
+    ExitStatus(i32),
+    Failed(String),
+}
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Sent => "sent",
+            Outcome::PromptTimeout => "prompt-timeout",
+            Outcome::OutputClosed => "output-closed",
+            Outcome::Aborted => "aborted",
+            Outcome::ExitStatus(_) => "exit-status",
+            Outcome::Failed(_) => "failed",
+        }
+    }
+}
+
+/// 一条记录; file 和 line 指向该行真正所在的位置, 被包含的行指向被包含的文件
+#[derive(Debug, Clone)]
+pub struct Record<'a> {
+    pub target: &'a str,
+    pub file: Option<&'a Path>,
+    pub line: usize,
+    pub text: &'a str,
+    pub outcome: &'a Outcome,
+}
+
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    dir: PathBuf,
+    /// 同一次运行的记录带相同的会话号, 便于在同一天的文件中区分
+    session: String,
+}
+
+impl Transcript {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let session = format!("{}-{}", Local::now().format("%Y%m%d%H%M%S"), std::process::id());
+        Self { dir: dir.into(), session }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+
+    /// 某天的纯文本和 JSON Lines 文件
+    pub fn files_for(&self, date: NaiveDate) -> (PathBuf, PathBuf) {
+        let stem = format!("transcript-{}", date.format("%Y-%m-%d"));
+        (self.dir.join(format!("{}.log", stem)), self.dir.join(format!("{}.jsonl", stem)))
+    }
+
+    pub fn record(&self, record: &Record) -> io::Result<()> {
+        self.record_at(Local::now(), record)
+    }
+
+    fn record_at(&self, time: DateTime<Local>, record: &Record) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let (text_path, json_path) = self.files_for(time.date_naive());
+        let file = record.file.map(|file| file.display().to_string());
+
+        let location = match &file {
+            Some(file) => format!("{}:{}", file, record.line),
+            None => format!("line {}", record.line),
+        };
+        let outcome = match record.outcome {
+            Outcome::Failed(error) => format!("failed: {}", error),
+            Outcome::ExitStatus(status) => format!("exit-status {}", status),
+            other => other.name().to_string(),
+        };
+        let text_line = format!(
+            "{} [{}] {} {} ({}) {}\n",
+            time.format("%Y-%m-%d %H:%M:%S%.3f"),
+            self.session,
+            record.target,
+            location,
+            outcome,
+            record.text
+        );
+        append(&text_path, text_line.as_bytes())?;
+
+        let mut entry = json!({
+            "time": time.to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            "session": self.session,
+            "target": record.target,
+            "file": file,
+            "line": record.line,
+            "text": record.text,
+            "outcome": record.outcome.name(),
+        });
+        match record.outcome {
+            Outcome::Failed(error) => entry["error"] = json!(error),
+            Outcome::ExitStatus(status) => entry["status"] = json!(status),
+            _ => {}
+        }
+        append(&json_path, format!("{}\n", entry).as_bytes())
+    }
+}
+
+fn append(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::env;
+
+    #[test]
+    fn writes_text_and_json_per_day() {
+        let dir = env::temp_dir().join(format!("tty_sender_transcript_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let transcript = Transcript::new(&dir);
+        let failed = Outcome::Failed("broken pipe".to_string());
+        let record = |line, outcome| Record {
+            target: "serial:COM3",
+            file: Some(Path::new("deploy.txt")),
+            line,
+            text: "echo \"hi\"",
+            outcome,
+        };
+        let day1 = Local.with_ymd_and_hms(2026, 3, 1, 23, 59, 0).unwrap();
+        let day2 = Local.with_ymd_and_hms(2026, 3, 2, 0, 0, 1).unwrap();
+        transcript.record_at(day1, &record(3, &Outcome::Sent)).unwrap();
+        transcript.record_at(day1, &record(4, &failed)).unwrap();
+        transcript.record_at(day2, &record(5, &Outcome::Aborted)).unwrap();
+
+        let (text1, json1) = transcript.files_for(day1.date_naive());
+        let text = fs::read_to_string(text1).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.contains("serial:COM3 deploy.txt:4 (failed: broken pipe) echo \"hi\""));
+
+        let entries: Vec<serde_json::Value> = fs::read_to_string(json1)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries[0]["outcome"], "sent");
+        assert_eq!(entries[0]["line"], 3);
+        assert_eq!(entries[0]["text"], "echo \"hi\"");
+        assert_eq!(entries[1]["error"], "broken pipe");
+        assert_eq!(entries[1]["session"], transcript.session());
+
+        let (text2, _) = transcript.files_for(day2.date_naive());
+        assert!(fs::read_to_string(text2).unwrap().contains("(aborted)"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
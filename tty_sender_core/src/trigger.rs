@@ -0,0 +1,258 @@
+//! 输出触发器: 后台读取目标的输出, 出现匹配的文字时自动回复一行或一串按键,
+//! 用于 U-Boot 的 "Hit any key to stop autoboot"、登录提示、`[y/N]` 确认等需要及时回应的场合。
+//! 触发器与多行发送互不依赖, 目标连接期间一直有效
+
+use crate::capture::Plain;
+use crate::config::TriggerRule;
+use crate::keys::terminal_text;
+use crate::target::{OutputBuffer, Target};
+use regex::Regex;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use vte::Parser;
+
+/// 尚未匹配的输出最多保留的字节数, 超出时丢弃较早的部分
+const MAX_PENDING: usize = 4096;
+
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    pub name: String,
+    pattern: Regex,
+    /// 回复的内容, `{名称}` 特殊键已换成终端字节序列
+    response: String,
+    enter: bool,
+    once: bool,
+}
+
+impl Trigger {
+    pub fn new(name: &str, rule: &TriggerRule) -> Result<Trigger, regex::Error> {
+        Ok(Trigger {
+            name: name.to_string(),
+            pattern: Regex::new(&rule.pattern)?,
+            response: terminal_text(&rule.send),
+            enter: rule.enter,
+            once: rule.once,
+        })
+    }
+
+    fn respond(&self, target: &mut dyn Target) -> io::Result<()> {
+        if !self.response.is_empty() {
+            target.send_str(&self.response)?;
+        }
+        if self.enter {
+            target.send_enter()?;
+        }
+        Ok(())
+    }
+}
+
+/// 目标配置中引用的触发器无效
+#[derive(Debug)]
+pub enum TriggerError {
+    /// [triggers] 中没有该名称
+    Unknown(String),
+    Pattern(String, regex::Error),
+}
+
+/// 在输出中查找触发器的匹配, 控制序列去掉后再匹配; 已匹配的文字不会再次触发
+struct Matcher {
+    triggers: Vec<Trigger>,
+    /// 一次性的触发器是否已触发
+    fired: Vec<bool>,
+    pending: String,
+    parser: Parser,
+}
+
+impl Matcher {
+    fn new(triggers: Vec<Trigger>) -> Matcher {
+        let fired = vec![false; triggers.len()];
+        Matcher { triggers, fired, pending: String::new(), parser: Parser::new() }
+    }
+
+    /// 加入新的输出, 按出现的先后返回触发的规则下标
+    fn feed(&mut self, bytes: &[u8]) -> Vec<usize> {
+        let mut plain = Plain(String::new());
+        self.parser.advance(&mut plain, bytes);
+        self.pending.push_str(&plain.0);
+
+        let mut hits = Vec::new();
+        loop {
+            let earliest = self
+                .triggers
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !self.fired[*index])
+                .filter_map(|(index, trigger)| trigger.pattern.find(&self.pending).map(|m| (m.start(), m.end(), index)))
+                .min();
+            let Some((_, end, index)) = earliest else { break };
+            hits.push(index);
+            self.fired[index] = self.triggers[index].once;
+            self.pending.drain(..end);
+        }
+        if self.pending.len() > MAX_PENDING {
+            let mut cut = self.pending.len() - MAX_PENDING;
+            while !self.pending.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.pending.drain(..cut);
+        }
+        hits
+    }
+}
+
+pub enum TriggerEvent<'a> {
+    Fired { name: &'a str },
+    Failed { name: &'a str, error: io::Error },
+}
+
+/// 在后台线程中监视输出并回复, 停止或丢弃时结束
+pub struct TriggerRunner {
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TriggerRunner {
+    /// 从 output 当前位置开始监视, 之前的输出不触发
+    pub fn start<T, F>(triggers: Vec<Trigger>, output: OutputBuffer, mut target: T, mut on_event: F) -> TriggerRunner
+    where
+        T: Target + 'static,
+        F: FnMut(TriggerEvent) + Send + 'static,
+    {
+        // 分段等待, 以便停止后及时退出
+        const SLICE: Duration = Duration::from_millis(100);
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stop = Arc::clone(&stopped);
+        let mut position = output.position();
+        let thread = thread::spawn(move || {
+            let mut matcher = Matcher::new(triggers);
+            while !stop.load(Ordering::SeqCst) {
+                if !output.wait_new(position, SLICE) {
+                    continue;
+                }
+                let (text, end) = output.read_since(position);
+                position = end;
+                for index in matcher.feed(text.as_bytes()) {
+                    let trigger = &matcher.triggers[index];
+                    match trigger.respond(&mut target) {
+                        Ok(()) => on_event(TriggerEvent::Fired { name: &trigger.name }),
+                        Err(error) => on_event(TriggerEvent::Failed { name: &trigger.name, error }),
+                    }
+                }
+                if output.is_closed() {
+                    break;
+                }
+            }
+        });
+        TriggerRunner { stopped, thread: Some(thread) }
+    }
+
+    /// 停止监视并等待后台线程结束
+    pub fn stop(mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for TriggerRunner {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::sync::Mutex;
+
+    fn trigger(name: &str, pattern: &str, send: &str, enter: bool, once: bool) -> Trigger {
+        let rule = TriggerRule { pattern: pattern.to_string(), send: send.to_string(), enter, once };
+        Trigger::new(name, &rule).unwrap()
+    }
+
+    #[test]
+    fn matches_across_chunks_and_respects_once() {
+        let mut matcher = Matcher::new(vec![
+            trigger("autoboot", "Hit any key to stop autoboot", " ", false, true),
+            trigger("confirm", r"\[y/N\]", "y", true, false),
+        ]);
+        assert!(matcher.feed(b"U-Boot 2024.01\r\nHit any key to st").is_empty());
+        // 控制序列不影响匹配
+        assert_eq!(matcher.feed(b"op \x1b[1mautoboot\x1b[0m:  3 \x08\x08\x082 "), vec![0]);
+        assert!(matcher.feed(b"Hit any key to stop autoboot:  1 ").is_empty());
+        assert_eq!(matcher.feed(b"Erase? [y/N] ok\r\nAgain? [y/N] "), vec![1, 1]);
+        assert!(matcher.feed(b"\r\n").is_empty());
+    }
+
+    /// 记录收到的内容, 测试中可随时取出
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<String>>);
+
+    impl Target for Recorder {
+        fn describe(&self) -> String {
+            "recorder".to_string()
+        }
+
+        fn send_str(&mut self, text: &str) -> io::Result<()> {
+            self.0.lock().unwrap().push_str(text);
+            Ok(())
+        }
+
+        fn send_enter(&mut self) -> io::Result<()> {
+            self.0.lock().unwrap().push('\n');
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn responds_in_background() {
+        let output = OutputBuffer::new();
+        output.push(b"login: ");
+        let target = Recorder::default();
+        let (sender, receiver) = mpsc::channel();
+        let runner = TriggerRunner::start(
+            vec![trigger("login", "login: $", "root", true, false), trigger("menu", "Boot menu", "{Down}{Enter}", false, true)],
+            output.clone(),
+            target.clone(),
+            move |event| {
+                if let TriggerEvent::Fired { name } = event {
+                    sender.send(name.to_string()).unwrap();
+                }
+            },
+        );
+
+        output.push(b"\r\nBoot menu\r\nboard login: ");
+        let timeout = Duration::from_secs(5);
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), "menu");
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), "login");
+        runner.stop();
+        assert_eq!(*target.0.lock().unwrap(), "\x1b[B\rroot\n");
+    }
+
+    #[test]
+    fn sees_output_arriving_right_after_start() {
+        // 起始位置在启动时读取, 后台线程晚于输出开始运行也不会漏掉; 多次重复以暴露竞争
+        for _ in 0..20 {
+            let output = OutputBuffer::new();
+            let (sender, receiver) = mpsc::channel();
+            let runner = TriggerRunner::start(
+                vec![trigger("autoboot", "Hit any key", " ", false, true)],
+                output.clone(),
+                Recorder::default(),
+                move |event| {
+                    if let TriggerEvent::Fired { name } = event {
+                        sender.send(name.to_string()).unwrap();
+                    }
+                },
+            );
+            output.push(b"Hit any key to stop autoboot");
+            assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap(), "autoboot");
+            runner.stop();
+        }
+    }
+}